@@ -45,6 +45,7 @@ impl DisableCommand {
                 .with_context(|| format!("unable to read graph from file {:?}", graph_file))?[..],
         )
         .context("unable to deserialize the dependency graph")?;
+        graph.preferred_providers = config.providers;
         if self.atomic_changes {
             for service in &self.services {
                 // Check runlevel of all services to disable
@@ -56,7 +57,7 @@ impl DisableCommand {
                         .with_context(|| format!("the service {service} is not enabled"))?
                         .service
                         .runlevel()
-                        == self.runlevel,
+                        == &self.runlevel,
                     "service {service} must be of the runlevel {:?}",
                     self.runlevel
                 )
@@ -77,7 +78,7 @@ impl DisableCommand {
                             .with_context(|| format!("the service {service} is not enabled"))?
                             .service
                             .runlevel()
-                            == self.runlevel,
+                            == &self.runlevel,
                         "service {service} must be of the runlevel {:?}",
                         self.runlevel
                     );
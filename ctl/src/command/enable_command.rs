@@ -54,6 +54,8 @@ impl EnableCommand {
         } else {
             DependencyGraph::new()
         };
+        graph.preferred_providers = config.providers.clone();
+        graph.runlevels = config.runlevels();
 
         let uid = unsafe { libc::getuid() };
         let system_mode = uid == 0;
@@ -75,19 +77,19 @@ impl EnableCommand {
         if self.atomic_changes {
             let services = parse_services(self.services.clone(), &config.dirs, system_mode)
                 .context("unable to parse services")?;
-            // The dependency graph ensure that all the dependencies have the same runlevel
+            // The dependency graph ensure that no dependency is in a later runlevel
             // So we just check that we the services passed on the command line are the
             // same runlevel requested
             ensure!(
                 services
                     .iter()
                     .filter(|service| self.services.contains(&service.name().to_string()))
-                    .all(|service| service.runlevel() == self.runlevel),
+                    .all(|service| service.runlevel() == &self.runlevel),
                 "service {} must be of the runlevel {:?}",
                 services
                     .iter()
                     .filter(|service| self.services.contains(&service.name().to_string()))
-                    .find(|service| service.runlevel() != self.runlevel)
+                    .find(|service| service.runlevel() != &self.runlevel)
                     .unwrap()
                     .name(),
                 self.runlevel
@@ -106,7 +108,7 @@ impl EnableCommand {
                 // If the user asked us to start the services, try to start them one by one
                 if self.start {
                     for service in &self.services {
-                        if start_service(&mut conn, service, self.runlevel).await? {
+                        if start_service(&mut conn, service, self.runlevel.clone()).await? {
                             println!("Service {service} started successfully.");
                         } else {
                             println!("Service {service} failed to start.");
@@ -147,7 +149,7 @@ impl EnableCommand {
                         .find(|s| service == s.name())
                         .unwrap()
                         .runlevel()
-                        == self.runlevel,
+                        == &self.runlevel,
                     "service {service} must be of the runlevel {:?}",
                     self.runlevel
                 );
@@ -169,12 +171,14 @@ impl EnableCommand {
                     if self.stop_at_errors {
                         bail!(err);
                     } else {
-                        eprintln!("err");
+                        eprintln!("{err:?}");
                         success = false;
+                        // The graph has been left untouched, there is nothing to save
+                        continue;
                     }
                 }
-                // Always save the graph. We save after each service, so that in case of any
-                // error, we have already it saved to disk and we can exit this function
+                // Save after each service, so that the services enabled so far are
+                // already on disk if a later one fails
                 save_graph(&graph)?;
                 println!("Service {service} has been enabled");
                 if let Some(conn) = &mut conn {
@@ -182,7 +186,7 @@ impl EnableCommand {
                     conn.send_request(request).await??;
 
                     if self.start {
-                        let res = start_service(conn, &service, self.runlevel)
+                        let res = start_service(conn, &service, self.runlevel.clone())
                             .await
                             .with_context(|| format!("Could not start service {service}"));
                         if let Err(err) = res {
@@ -1,13 +1,19 @@
 mod disable_command;
 mod enable_command;
 mod reload_command;
+mod reset_failed_command;
+mod runlevel_command;
 mod start_command;
 mod status_command;
 mod stop_command;
+mod timers_command;
 
 pub use disable_command::DisableCommand;
 pub use enable_command::EnableCommand;
 pub use reload_command::ReloadCommand;
+pub use reset_failed_command::ResetFailedCommand;
+pub use runlevel_command::RunLevelCommand;
 pub use start_command::StartCommand;
 pub use status_command::StatusCommand;
 pub use stop_command::StopCommand;
+pub use timers_command::TimersCommand;
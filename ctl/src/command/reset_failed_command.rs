@@ -0,0 +1,47 @@
+use anyhow::{
+    ensure,
+    Result,
+};
+use clap::Parser;
+use rinit_ipc::{
+    AsyncConnection,
+    Reply,
+    Request,
+};
+use rinit_service::config::Config;
+
+#[derive(Parser)]
+pub struct ResetFailedCommand {
+    #[clap(required = true)]
+    services: Vec<String>,
+}
+
+impl ResetFailedCommand {
+    pub async fn run(
+        self,
+        _config: Config,
+    ) -> Result<()> {
+        // TODO: Print duplicated service
+        ensure!(
+            !(1..self.services.len()).any(|i| self.services[i..].contains(&self.services[i - 1])),
+            "duplicated service found"
+        );
+
+        let mut conn = AsyncConnection::new_host_address().await?;
+        for service in self.services {
+            let request = Request::ResetFailedService(service.clone());
+            match conn.send_request(request).await?? {
+                Reply::Success(failed) => {
+                    if failed {
+                        println!("Service {service} has been reset.");
+                    } else {
+                        println!("Service {service} has not failed.");
+                    }
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use anyhow::{
+    ensure,
+    Result,
+};
+use clap::{
+    Parser,
+    Subcommand,
+};
+use rinit_ipc::{
+    AsyncConnection,
+    Reply,
+    Request,
+};
+use rinit_service::{
+    config::Config,
+    types::RunLevel,
+};
+
+#[derive(Parser)]
+pub struct RunLevelCommand {
+    #[clap(subcommand)]
+    subcmd: RunLevelSubcommand,
+}
+
+#[derive(Subcommand)]
+enum RunLevelSubcommand {
+    /// Start the services up to the runlevel passed and stop the ones in the
+    /// runlevels coming after it
+    Switch { runlevel: RunLevel },
+}
+
+impl RunLevelCommand {
+    pub async fn run(
+        self,
+        _config: Config,
+    ) -> Result<()> {
+        let mut conn = AsyncConnection::new_host_address().await?;
+        match self.subcmd {
+            RunLevelSubcommand::Switch { runlevel } => {
+                let request = Request::SwitchRunLevel(runlevel.clone());
+                match conn.send_request(request).await?? {
+                    Reply::Success(success) => {
+                        ensure!(
+                            success,
+                            "some services failed to start while switching to runlevel {}",
+                            runlevel.to_string()
+                        );
+                        println!("Switched to runlevel {}.", runlevel.to_string());
+                    }
+                    _ => unreachable!(),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -32,7 +32,7 @@ impl StartCommand {
         let mut conn = AsyncConnection::new_host_address().await?;
         let mut error = false;
         for service in self.services {
-            if start_service(&mut conn, &service, self.runlevel).await? {
+            if start_service(&mut conn, &service, self.runlevel.clone()).await? {
                 println!("Service {service} started successfully.");
             } else {
                 println!("Service {service} failed to start.");
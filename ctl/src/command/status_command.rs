@@ -77,7 +77,7 @@ impl StatusCommand {
             })
             .map(|reply| {
                 match reply {
-                    Reply::ServiceState(service, state) => (service, state),
+                    Reply::ServiceState(status) => status,
                     _ => unreachable!(),
                 }
             })
@@ -86,10 +86,10 @@ impl StatusCommand {
         };
         states
             .iter()
-            .sorted_by(|a, b| Ord::cmp(&a.0, &b.0))
-            .for_each(|state| {
+            .sorted_by(|a, b| Ord::cmp(&a.name, &b.name))
+            .for_each(|status| {
                 // TODO: Add better formatting
-                println!("{}: {}", state.0, state.1);
+                println!("{status}");
             });
 
         Ok(())
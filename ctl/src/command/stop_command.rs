@@ -41,12 +41,12 @@ impl StopCommand {
         let success = futures::stream::iter(
             self.services
                 .into_iter()
-                .map(|service| (service, conn.clone())),
+                .map(|service| (service, conn.clone(), self.runlevel.clone())),
         )
-        .map(async move |(service, conn)| -> Result<()> {
+        .map(async move |(service, conn, runlevel)| -> Result<()> {
             let request = Request::StopService {
                 service: service.clone(),
-                runlevel: self.runlevel,
+                runlevel,
             };
             let res = conn.borrow_mut().send_request(request).await?;
 
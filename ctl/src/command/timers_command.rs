@@ -0,0 +1,49 @@
+use anyhow::Result;
+use clap::Parser;
+use itertools::Itertools;
+use rinit_ipc::{
+    AsyncConnection,
+    Reply,
+    Request,
+};
+use rinit_service::{
+    config::Config,
+    types::format_local_time,
+};
+
+#[derive(Parser)]
+pub struct TimersCommand {}
+
+fn format_time(timestamp: Option<i64>) -> String {
+    timestamp.map_or_else(|| "n/a".to_string(), format_local_time)
+}
+
+impl TimersCommand {
+    pub async fn run(
+        self,
+        _config: Config,
+    ) -> Result<()> {
+        let mut conn = AsyncConnection::new_host_address().await?;
+        let states = match conn.send_request(Request::ServicesStatus).await?? {
+            Reply::ServicesStates(states) => states,
+            _ => unreachable!(),
+        };
+        println!("{:<20} {:<20} {:<20}", "NEXT", "LAST", "SERVICE");
+        states
+            .iter()
+            .filter_map(|status| status.timer.map(|timer| (&status.name, timer)))
+            .sorted_by(|(_, a), (_, b)| {
+                // The timers that will not run anymore go last
+                Ord::cmp(&a.next.unwrap_or(i64::MAX), &b.next.unwrap_or(i64::MAX))
+            })
+            .for_each(|(name, timer)| {
+                println!(
+                    "{:<20} {:<20} {name}",
+                    format_time(timer.next),
+                    format_time(timer.last)
+                );
+            });
+
+        Ok(())
+    }
+}
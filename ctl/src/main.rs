@@ -15,6 +15,9 @@ enum Command {
     Start(StartCommand),
     Stop(StopCommand),
     Reload(ReloadCommand),
+    Runlevel(RunLevelCommand),
+    ResetFailed(ResetFailedCommand),
+    Timers(TimersCommand),
 }
 
 #[derive(Parser)]
@@ -28,9 +31,12 @@ use command::{
     DisableCommand,
     EnableCommand,
     ReloadCommand,
+    ResetFailedCommand,
+    RunLevelCommand,
     StartCommand,
     StatusCommand,
     StopCommand,
+    TimersCommand,
 };
 use rinit_service::config::Config;
 
@@ -47,6 +53,9 @@ async fn main() -> Result<()> {
         Command::Start(start_command) => start_command.run(config).await?,
         Command::Stop(stop_command) => stop_command.run(config).await?,
         Command::Reload(reload_command) => reload_command.run(config).await?,
+        Command::Runlevel(runlevel_command) => runlevel_command.run(config).await?,
+        Command::ResetFailed(reset_failed_command) => reset_failed_command.run(config).await?,
+        Command::Timers(timers_command) => timers_command.run(config).await?,
     }
 
     Ok(())
@@ -3,6 +3,7 @@ mod get_host_address;
 mod reply;
 mod request;
 pub mod request_error;
+mod service_status;
 
 pub use async_connection::{
     AsyncConnection,
@@ -12,6 +13,7 @@ pub use get_host_address::get_host_address;
 pub use reply::Reply;
 pub use request::Request;
 pub use request_error::RequestError;
+pub use service_status::ServiceStatus;
 
 #[macro_use]
 extern crate lazy_static;
@@ -1,13 +1,14 @@
-use rinit_service::service_state::ServiceState;
 use serde::{
     Deserialize,
     Serialize,
 };
 
+use crate::ServiceStatus;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum Reply {
-    ServicesStates(Vec<(String, ServiceState)>),
-    ServiceState(String, ServiceState),
+    ServicesStates(Vec<ServiceStatus>),
+    ServiceState(ServiceStatus),
     Success(bool),
     Empty,
 }
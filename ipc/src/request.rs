@@ -1,7 +1,10 @@
 use rinit_service::{
     service_state::{
+        Health,
         IdleServiceState,
+        NotifyState,
         ServiceState,
+        TimerState,
     },
     types::RunLevel,
 };
@@ -13,12 +16,19 @@ use serde::{
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum Request {
     UpdateServiceStatus(String, IdleServiceState),
+    UpdateNotifyState(String, NotifyState),
+    UpdateHealth(String, Option<Health>),
+    UpdateTimer(String, TimerState),
+    UpdateFailureReason(String, Option<String>),
     ServicesStatus,
     ServiceStatus(String),
     StartService { service: String, runlevel: RunLevel },
+    TriggerService(String),
     StopService { service: String, runlevel: RunLevel },
     StartAllServices,
     StopAllServices,
+    SwitchRunLevel(RunLevel),
+    ResetFailedService(String),
     ReloadGraph,
 }
 
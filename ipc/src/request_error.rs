@@ -24,10 +24,25 @@ pub enum LogicError {
     },
     #[snafu(display("dependency graph not found in path {path}"))]
     DependencyGraphNotFound { path: String },
+    #[snafu(display(
+        "none of the services {alternatives:?} required by {service} could be started"
+    ))]
+    NoRequiredServiceUp {
+        service: String,
+        alternatives: Vec<String>,
+    },
+    #[snafu(display("required service {required} failed to start for service {service}"))]
+    RequiredServiceFailedToStart { service: String, required: String },
     #[snafu(display("service {service} has a different runlevel then the one requested"))]
     RunLevelMustMatch { service: String },
+    #[snafu(display(
+        "service {service} has failed too many times, reset it before starting it again"
+    ))]
+    ServiceFailed { service: String },
     #[snafu(display("service {service} failed to start"))]
     ServiceFailedToStart { service: String },
     #[snafu(display("service {service} does not exists"))]
     ServiceNotFound { service: String },
+    #[snafu(display("runlevel {runlevel} is not declared in the configuration"))]
+    UnknownRunLevel { runlevel: String },
 }
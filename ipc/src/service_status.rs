@@ -0,0 +1,60 @@
+use std::fmt;
+
+use rinit_service::service_state::{
+    Health,
+    NotifyState,
+    ServiceState,
+    TimerState,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// The status of a service as reported by rsvc
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: ServiceState,
+    /// The concrete service backing a virtual service
+    pub provider: Option<String>,
+    /// What the service reported through its notify socket
+    pub notify: NotifyState,
+    /// The result of the liveness probe, if the service has one
+    pub health: Option<Health>,
+    /// When the timer has run and will run next, if the service has one
+    pub timer: Option<TimerState>,
+    /// Why the process of the service exited last, when it hit a limit
+    pub failure_reason: Option<String>,
+}
+
+impl fmt::Display for ServiceStatus {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.state)?;
+        if let Some(provider) = &self.provider {
+            write!(f, " (provided by {provider})")?;
+        }
+        if let Some(health) = &self.health {
+            write!(f, " ({health})")?;
+        }
+        if self.notify.reloading {
+            write!(f, " (reloading)")?;
+        }
+        if self.notify.stopping {
+            write!(f, " (stopping)")?;
+        }
+        if let Some(main_pid) = self.notify.main_pid {
+            write!(f, " [pid {main_pid}]")?;
+        }
+        if let Some(status) = &self.notify.status {
+            write!(f, ": {status}")?;
+        }
+        if let Some(reason) = &self.failure_reason {
+            write!(f, " ({reason})")?;
+        }
+        Ok(())
+    }
+}
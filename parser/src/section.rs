@@ -1,11 +1,23 @@
 mod bundle_options_builder;
+mod path_builder;
+mod resources_builder;
+mod sandbox_builder;
 mod script_builder;
 mod script_environment_builder;
 mod section_builder;
 mod service_options_builder;
+mod socket_builder;
+mod timer_builder;
+mod virtual_options_builder;
 
 pub use bundle_options_builder::*;
+pub use path_builder::*;
+pub use resources_builder::*;
+pub use sandbox_builder::*;
 pub use script_builder::*;
 pub use script_environment_builder::*;
 pub use section_builder::*;
 pub use service_options_builder::*;
+pub use socket_builder::*;
+pub use timer_builder::*;
+pub use virtual_options_builder::*;
@@ -0,0 +1,186 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+};
+
+use rinit_service::types::{
+    PathTrigger,
+    PathTriggers,
+};
+use snafu::{
+    ensure,
+    OptionExt,
+    Snafu,
+};
+
+use super::SectionBuilder;
+
+#[derive(Snafu, Debug)]
+pub enum PathBuilderError {
+    #[snafu(display("no path trigger has been declared"))]
+    NoTriggers,
+    #[snafu(display("{path} is not an absolute path"))]
+    RelativePath { path: String },
+    #[snafu(display("{key} must be a positive integer"))]
+    InvalidInteger { key: String },
+}
+
+pub struct PathBuilder {
+    pub path: Option<Result<PathTriggers, PathBuilderError>>,
+}
+
+type Result<T, E = PathBuilderError> = std::result::Result<T, E>;
+
+impl PathBuilder {
+    pub fn new() -> Self {
+        Self { path: None }
+    }
+}
+
+fn parse_triggers(
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+    key: &'static str,
+    trigger: fn(PathBuf) -> PathTrigger,
+) -> Result<Vec<PathTrigger>> {
+    array_values
+        .remove(key)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            ensure!(path.starts_with('/'), RelativePathSnafu { path });
+            Ok(trigger(PathBuf::from(path)))
+        })
+        .collect()
+}
+
+fn parse_positive<T: std::str::FromStr + PartialOrd + Default>(
+    values: &mut HashMap<&'static str, String>,
+    key: &'static str,
+    default: T,
+) -> Result<T> {
+    match values.remove(key) {
+        Some(value) => {
+            value
+                .parse::<T>()
+                .ok()
+                .filter(|value| *value > T::default())
+                .with_context(|| InvalidIntegerSnafu { key })
+        }
+        None => Ok(default),
+    }
+}
+
+fn parse_path(
+    values: &mut HashMap<&'static str, String>,
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+) -> Result<PathTriggers> {
+    let mut triggers = parse_triggers(array_values, "path_exists", PathTrigger::Exists)?;
+    triggers.extend(parse_triggers(
+        array_values,
+        "path_changed",
+        PathTrigger::Changed,
+    )?);
+    triggers.extend(parse_triggers(
+        array_values,
+        "path_modified",
+        PathTrigger::Modified,
+    )?);
+    triggers.extend(parse_triggers(
+        array_values,
+        "directory_not_empty",
+        PathTrigger::DirectoryNotEmpty,
+    )?);
+    ensure!(!triggers.is_empty(), NoTriggersSnafu);
+    Ok(PathTriggers {
+        triggers,
+        trigger_limit_interval_sec: parse_positive(
+            values,
+            "trigger_limit_interval_sec",
+            PathTriggers::DEFAULT_TRIGGER_LIMIT_INTERVAL_SEC,
+        )?,
+        trigger_limit_burst: parse_positive(
+            values,
+            "trigger_limit_burst",
+            PathTriggers::DEFAULT_TRIGGER_LIMIT_BURST,
+        )?,
+    })
+}
+
+impl SectionBuilder for PathBuilder {
+    fn build(
+        &mut self,
+        values: &mut HashMap<&'static str, String>,
+        array_values: &mut HashMap<&'static str, Vec<String>>,
+        _code_values: &mut HashMap<&'static str, String>,
+    ) {
+        self.path = Some(parse_path(values, array_values));
+    }
+
+    fn section_name(&self) -> &'static str {
+        "path"
+    }
+
+    fn get_fields(&self) -> &'static [&'static str] {
+        &["trigger_limit_interval_sec", "trigger_limit_burst"]
+    }
+
+    fn get_array_fields(&self) -> &'static [&'static str] {
+        &[
+            "path_exists",
+            "path_changed",
+            "path_modified",
+            "directory_not_empty",
+        ]
+    }
+
+    fn get_code_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_section() {
+        let mut builder = PathBuilder::new();
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "path_exists = [ /run/foo.ready ]",
+                    "directory_not_empty = [ /var/spool/foo ]",
+                    "trigger_limit_burst = 10",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let path = builder.path.unwrap().unwrap();
+        assert_eq!(
+            path.triggers,
+            vec![
+                PathTrigger::Exists(PathBuf::from("/run/foo.ready")),
+                PathTrigger::DirectoryNotEmpty(PathBuf::from("/var/spool/foo")),
+            ]
+        );
+        assert_eq!(
+            path.trigger_limit_interval_sec,
+            PathTriggers::DEFAULT_TRIGGER_LIMIT_INTERVAL_SEC
+        );
+        assert_eq!(path.trigger_limit_burst, 10);
+    }
+
+    #[test]
+    fn parse_section_relative_path() {
+        let mut builder = PathBuilder::new();
+        builder
+            .parse_until_next_section(&["path_changed = [ foo.conf ]"])
+            .unwrap();
+
+        assert!(matches!(
+            builder.path.unwrap(),
+            Err(PathBuilderError::RelativePath { .. })
+        ));
+    }
+}
@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use rinit_service::types::Resources;
+use snafu::{
+    OptionExt,
+    Snafu,
+};
+
+use super::SectionBuilder;
+
+#[derive(Snafu, Debug)]
+pub enum ResourcesBuilderError {
+    #[snafu(display(
+        "{key} must be a size in bytes, optionally followed by K, M, G or T, or max"
+    ))]
+    InvalidSize { key: String },
+    #[snafu(display("{key} must be a number between 1 and 10000"))]
+    WeightOutOfRange { key: String },
+    #[snafu(display("{key} must be a percentage, like 50%, or max"))]
+    InvalidPercentage { key: String },
+    #[snafu(display("{key} must be a positive integer or max"))]
+    InvalidInteger { key: String },
+}
+
+pub struct ResourcesBuilder {
+    pub resources: Option<Result<Resources, ResourcesBuilderError>>,
+}
+
+type Result<T, E = ResourcesBuilderError> = std::result::Result<T, E>;
+
+impl ResourcesBuilder {
+    pub fn new() -> Self {
+        Self { resources: None }
+    }
+}
+
+// Parse a limit, where max means no limit
+fn parse_limit<T>(
+    values: &mut HashMap<&'static str, String>,
+    key: &'static str,
+    parse: fn(&str) -> Option<T>,
+) -> std::result::Result<Option<T>, ()> {
+    match values.remove(key) {
+        Some(value) if value == "max" => Ok(None),
+        Some(value) => parse(&value).map(Some).ok_or(()),
+        None => Ok(None),
+    }
+}
+
+fn parse_size(size: &str) -> Option<u64> {
+    let (number, shift) = match size.chars().last()? {
+        'K' => (&size[..size.len() - 1], 10),
+        'M' => (&size[..size.len() - 1], 20),
+        'G' => (&size[..size.len() - 1], 30),
+        'T' => (&size[..size.len() - 1], 40),
+        _ => (size, 0),
+    };
+    number.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+fn parse_weight(weight: &str) -> Option<u32> {
+    weight
+        .parse()
+        .ok()
+        .filter(|weight| (1..=10000).contains(weight))
+}
+
+fn parse_percentage(percentage: &str) -> Option<u32> {
+    percentage
+        .strip_suffix('%')?
+        .parse()
+        .ok()
+        .filter(|percentage| *percentage > 0)
+}
+
+fn parse_resources(values: &mut HashMap<&'static str, String>) -> Result<Resources> {
+    Ok(Resources {
+        memory_max: parse_limit(values, "memory_max", parse_size)
+            .ok()
+            .context(InvalidSizeSnafu { key: "memory_max" })?,
+        memory_high: parse_limit(values, "memory_high", parse_size)
+            .ok()
+            .context(InvalidSizeSnafu { key: "memory_high" })?,
+        cpu_weight: values
+            .remove("cpu_weight")
+            .map(|weight| {
+                parse_weight(&weight).context(WeightOutOfRangeSnafu { key: "cpu_weight" })
+            })
+            .transpose()?,
+        cpu_max: parse_limit(values, "cpu_max", parse_percentage)
+            .ok()
+            .context(InvalidPercentageSnafu { key: "cpu_max" })?,
+        io_weight: values
+            .remove("io_weight")
+            .map(|weight| parse_weight(&weight).context(WeightOutOfRangeSnafu { key: "io_weight" }))
+            .transpose()?,
+        pids_max: parse_limit(values, "pids_max", |pids| {
+            pids.parse().ok().filter(|pids| *pids > 0)
+        })
+        .ok()
+        .context(InvalidIntegerSnafu { key: "pids_max" })?,
+    })
+}
+
+impl SectionBuilder for ResourcesBuilder {
+    fn build(
+        &mut self,
+        values: &mut HashMap<&'static str, String>,
+        _array_values: &mut HashMap<&'static str, Vec<String>>,
+        _code_values: &mut HashMap<&'static str, String>,
+    ) {
+        self.resources = Some(parse_resources(values));
+    }
+
+    fn section_name(&self) -> &'static str {
+        "resources"
+    }
+
+    fn get_fields(&self) -> &'static [&'static str] {
+        &[
+            "memory_max",
+            "memory_high",
+            "cpu_weight",
+            "cpu_max",
+            "io_weight",
+            "pids_max",
+        ]
+    }
+
+    fn get_array_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn get_code_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_section() {
+        let mut builder = ResourcesBuilder::new();
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "memory_max = 512M",
+                    "memory_high = max",
+                    "cpu_weight = 50",
+                    "cpu_max = 150%",
+                    "pids_max = 64",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(
+            builder.resources.unwrap().unwrap(),
+            Resources {
+                memory_max: Some(512 << 20),
+                memory_high: None,
+                cpu_weight: Some(50),
+                cpu_max: Some(150),
+                io_weight: None,
+                pids_max: Some(64),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_section_invalid_weight() {
+        let mut builder = ResourcesBuilder::new();
+        builder
+            .parse_until_next_section(&["io_weight = 0"])
+            .unwrap();
+
+        assert!(matches!(
+            builder.resources.unwrap(),
+            Err(ResourcesBuilderError::WeightOutOfRange { .. })
+        ));
+    }
+}
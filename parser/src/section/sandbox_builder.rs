@@ -0,0 +1,231 @@
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use rinit_service::types::Sandbox;
+use snafu::{
+    ensure,
+    Snafu,
+};
+
+use super::SectionBuilder;
+
+#[derive(Snafu, Debug)]
+pub enum SandboxBuilderError {
+    #[snafu(display("{key} must be either 'yes' or 'no'"))]
+    InvalidBoolean { key: String },
+    #[snafu(display("{path:?} in {key} is not an absolute path"))]
+    RelativePath { key: String, path: PathBuf },
+    #[snafu(display("{path:?} in read_only_paths is hidden by {option}"))]
+    ConflictingPath { path: PathBuf, option: String },
+}
+
+pub struct SandboxBuilder {
+    pub sandbox: Option<Result<Sandbox, SandboxBuilderError>>,
+}
+
+type Result<T, E = SandboxBuilderError> = std::result::Result<T, E>;
+
+impl SandboxBuilder {
+    pub fn new() -> Self {
+        Self { sandbox: None }
+    }
+}
+
+fn parse_boolean(
+    values: &mut HashMap<&'static str, String>,
+    key: &'static str,
+) -> Result<bool> {
+    values.remove(key).map_or(Ok(false), |value| {
+        match value.as_str() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => InvalidBooleanSnafu { key }.fail(),
+        }
+    })
+}
+
+fn parse_paths(
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+    key: &'static str,
+) -> Result<Vec<PathBuf>> {
+    array_values
+        .remove(key)
+        .unwrap_or_default()
+        .into_iter()
+        .map(PathBuf::from)
+        .map(|path| {
+            ensure!(path.is_absolute(), RelativePathSnafu { key, path });
+            Ok(path)
+        })
+        .collect()
+}
+
+// The read-only paths are remounted first, a tmpfs mounted afterwards on one
+// of their ancestors would hide them
+fn check_conflicts(sandbox: &Sandbox) -> Result<()> {
+    let mut hidden: Vec<(&Path, &str)> = Vec::new();
+    if sandbox.private_tmp {
+        hidden.push((Path::new("/tmp"), "private_tmp"));
+        hidden.push((Path::new("/var/tmp"), "private_tmp"));
+    }
+    if sandbox.private_devices {
+        hidden.push((Path::new("/dev"), "private_devices"));
+    }
+    if sandbox.protect_home {
+        hidden.extend(
+            Sandbox::PROTECT_HOME_PATHS
+                .iter()
+                .map(|path| (Path::new(*path), "protect_home")),
+        );
+    }
+    hidden.extend(
+        sandbox
+            .inaccessible_paths
+            .iter()
+            .map(|path| (path.as_path(), "inaccessible_paths")),
+    );
+    for path in &sandbox.read_only_paths {
+        if let Some((_, option)) = hidden.iter().find(|(hidden, _)| path.starts_with(hidden)) {
+            return ConflictingPathSnafu {
+                path: path.clone(),
+                option: *option,
+            }
+            .fail();
+        }
+    }
+    Ok(())
+}
+
+fn parse_sandbox(
+    values: &mut HashMap<&'static str, String>,
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+) -> Result<Sandbox> {
+    let sandbox = Sandbox {
+        private_tmp: parse_boolean(values, "private_tmp")?,
+        protect_system: parse_boolean(values, "protect_system")?,
+        protect_home: parse_boolean(values, "protect_home")?,
+        read_only_paths: parse_paths(array_values, "read_only_paths")?,
+        inaccessible_paths: parse_paths(array_values, "inaccessible_paths")?,
+        private_network: parse_boolean(values, "private_network")?,
+        private_devices: parse_boolean(values, "private_devices")?,
+        no_new_privileges: parse_boolean(values, "no_new_privileges")?,
+    };
+    check_conflicts(&sandbox)?;
+    Ok(sandbox)
+}
+
+impl SectionBuilder for SandboxBuilder {
+    fn build(
+        &mut self,
+        values: &mut HashMap<&'static str, String>,
+        array_values: &mut HashMap<&'static str, Vec<String>>,
+        _code_values: &mut HashMap<&'static str, String>,
+    ) {
+        self.sandbox = Some(parse_sandbox(values, array_values));
+    }
+
+    fn section_name(&self) -> &'static str {
+        "sandbox"
+    }
+
+    fn get_fields(&self) -> &'static [&'static str] {
+        &[
+            "private_tmp",
+            "protect_system",
+            "protect_home",
+            "private_network",
+            "private_devices",
+            "no_new_privileges",
+        ]
+    }
+
+    fn get_array_fields(&self) -> &'static [&'static str] {
+        &["read_only_paths", "inaccessible_paths"]
+    }
+
+    fn get_code_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::section::SectionBuilderError;
+
+    #[test]
+    fn parse_section() {
+        let mut builder = SandboxBuilder::new();
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "private_tmp = yes",
+                    "protect_system = yes",
+                    "read_only_paths = [ /var/lib/foo ]",
+                    "inaccessible_paths = [ /srv/secrets /var/lib/foo/keys ]",
+                    "no_new_privileges = yes",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(
+            builder.sandbox.unwrap().unwrap(),
+            Sandbox {
+                private_tmp: true,
+                protect_system: true,
+                read_only_paths: vec![PathBuf::from("/var/lib/foo")],
+                inaccessible_paths: vec![
+                    PathBuf::from("/srv/secrets"),
+                    PathBuf::from("/var/lib/foo/keys")
+                ],
+                no_new_privileges: true,
+                ..Sandbox::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_section_invalid() {
+        assert_eq!(
+            SandboxBuilder::new()
+                .parse_until_next_section(&["private_users = yes"])
+                .unwrap_err(),
+            SectionBuilderError::InvalidField {
+                field: "private_users".to_string()
+            }
+        );
+
+        let mut builder = SandboxBuilder::new();
+        builder
+            .parse_until_next_section(&["private_network = true"])
+            .unwrap();
+        assert!(matches!(
+            builder.sandbox.unwrap(),
+            Err(SandboxBuilderError::InvalidBoolean { .. })
+        ));
+
+        let mut builder = SandboxBuilder::new();
+        builder
+            .parse_until_next_section(&["read_only_paths = [ var/lib ]"])
+            .unwrap();
+        assert!(matches!(
+            builder.sandbox.unwrap(),
+            Err(SandboxBuilderError::RelativePath { .. })
+        ));
+
+        let mut builder = SandboxBuilder::new();
+        builder
+            .parse_until_next_section(&["private_tmp = yes", "read_only_paths = [ /tmp/foo ]"])
+            .unwrap();
+        assert!(matches!(
+            builder.sandbox.unwrap(),
+            Err(SandboxBuilderError::ConflictingPath { option, .. }) if option == "private_tmp"
+        ));
+    }
+}
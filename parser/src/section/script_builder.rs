@@ -1,15 +1,39 @@
 use std::{
     collections::HashMap,
-    convert::TryInto,
+    convert::{
+        TryFrom,
+        TryInto,
+    },
+    fmt::Display,
     num::ParseIntError,
+    ops::RangeInclusive,
+    path::PathBuf,
+    str::FromStr,
 };
 
 use nix::sys::signal::Signal;
 use rinit_service::types::{
+    ExecOptions,
+    InvalidIoPrioClassError,
+    InvalidKillModeError,
+    InvalidRestartPolicyError,
+    InvalidSchedPolicyError,
     InvalidScriptPrefixError,
+    IoPrioClass,
+    IoPriority,
+    KillMode,
+    Probe,
+    RLimit,
+    RLimitResource,
+    RestartPolicy,
+    SchedPolicy,
+    Scheduling,
     Script,
+    ScriptPrefix,
+    SignalStep,
 };
 use snafu::{
+    ensure,
     OptionExt,
     ResultExt,
     Snafu,
@@ -31,8 +55,64 @@ pub enum ScriptBuilderError {
     InvalidSignal { source: nix::Error },
     #[snafu(display("no execute found"))]
     NoExecuteFound,
+    #[snafu(display("{}", source))]
+    InvalidRestartPolicy { source: InvalidRestartPolicyError },
+    #[snafu(display("{}", source))]
+    InvalidKillMode { source: InvalidKillModeError },
+    #[snafu(display("only one {} probe can be set", kind))]
+    MultipleProbes { kind: String },
+    #[snafu(display("{} is not a valid signal step, use SIGNAL or SIGNAL:wait", step))]
+    InvalidSignalStep { step: String },
+    #[snafu(display("{} must be between {} and {}", key, min, max))]
+    OutOfRange {
+        key: String,
+        min: String,
+        max: String,
+    },
+    #[snafu(display("{} must be a number, soft:hard or infinity", key))]
+    InvalidRLimit { key: String },
+    #[snafu(display("limit_nofile can't be infinity, the kernel caps it at fs.nr_open"))]
+    InfiniteNofile,
+    #[snafu(display("{}", source))]
+    InvalidIoPrioClass { source: InvalidIoPrioClassError },
+    #[snafu(display("{}", source))]
+    InvalidSchedPolicy { source: InvalidSchedPolicyError },
+    #[snafu(display("cpu_affinity must be a list of CPUs like 0-3,6, not {}", value))]
+    InvalidCpuAffinity { value: String },
+    #[snafu(display("sched_priority can only be set for the fifo and rr policies"))]
+    SchedPriorityNotRealtime,
+    #[snafu(display("{} must be an absolute path", key))]
+    RelativePath { key: String },
+    #[snafu(display("umask must be an octal number between 0000 and 0777"))]
+    InvalidUmask,
+    #[snafu(display("{} can only be set in the [run] section of a longrun", field))]
+    RunOnlyField { field: String },
 }
 
+// The fields that only apply to the process supervised by a longrun, the
+// other scripts would silently ignore them
+const RUN_ONLY_FIELDS: &[&str] = &[
+    "restart",
+    "restart_delay",
+    "restart_delay_max",
+    "restart_limit_burst",
+    "restart_limit_interval",
+    "notify_socket",
+    "ready_unix_socket",
+    "ready_tcp_port",
+    "ready_path",
+    "ready_script",
+    "liveness_unix_socket",
+    "liveness_tcp_port",
+    "liveness_path",
+    "liveness_script",
+    "liveness_interval",
+    "liveness_timeout",
+    "liveness_threshold",
+    "watchdog",
+    "watchdog_signal",
+];
+
 pub struct ScriptBuilder {
     name: &'static str,
     pub script: Option<Result<Script, ScriptBuilderError>>,
@@ -64,16 +144,290 @@ where
         })
 }
 
+// A probe can be a unix socket, a TCP port, a path or a script, e.g.
+// ready_unix_socket, ready_tcp_port, ready_path and ready_script for the
+// readiness probe
+fn parse_probe(
+    values: &mut HashMap<&'static str, String>,
+    code_values: &mut HashMap<&'static str, String>,
+    kind: &str,
+) -> Result<Option<Probe>> {
+    let mut probes = Vec::new();
+    if let Some(path) = values.remove(format!("{kind}_unix_socket").as_str()) {
+        probes.push(Probe::UnixSocket(PathBuf::from(path)));
+    }
+    if let Some(port) = values.remove(format!("{kind}_tcp_port").as_str()) {
+        probes.push(Probe::TcpPort(port.parse().with_context(|_| {
+            InvalidIntegerSnafu {
+                key: format!("{kind}_tcp_port"),
+            }
+        })?));
+    }
+    if let Some(path) = values.remove(format!("{kind}_path").as_str()) {
+        probes.push(Probe::PathExists(PathBuf::from(path)));
+    }
+    if let Some(execute) = code_values.remove(format!("{kind}_script").as_str()) {
+        // The probe is polled, do not run the script again when it fails
+        probes.push(Probe::Script(Box::new(Script {
+            max_deaths: 1,
+            ..Script::new(ScriptPrefix::Sh, execute)
+        })));
+    }
+    ensure!(probes.len() <= 1, MultipleProbesSnafu { kind });
+    Ok(probes.pop())
+}
+
+// Each step is a signal, optionally followed by the time to wait for the
+// process to exit, e.g. SIGTERM:5000. The default wait is timeout_kill
+fn parse_signal_steps(
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+    timeout_kill: u32,
+) -> Result<Vec<SignalStep>> {
+    array_values
+        .remove("down_signals")
+        .unwrap_or_default()
+        .into_iter()
+        .map(|step| {
+            let (signal, wait) = match step.split_once(':') {
+                Some((signal, wait)) => (signal, wait.parse().ok()),
+                None => (step.as_str(), Some(timeout_kill)),
+            };
+            match (signal.parse::<Signal>(), wait) {
+                (Ok(signal), Some(wait)) => {
+                    Ok(SignalStep {
+                        signal: signal as i32,
+                        wait,
+                    })
+                }
+                _ => InvalidSignalStepSnafu { step: step.clone() }.fail(),
+            }
+        })
+        .collect()
+}
+
+fn get_int_in_range<T>(
+    values: &mut HashMap<&'static str, String>,
+    key: &'static str,
+    range: RangeInclusive<T>,
+) -> Result<Option<T>>
+where
+    T: FromStr<Err = ParseIntError> + PartialOrd + Display,
+{
+    values
+        .remove(key)
+        .map(|value| {
+            let value: T = value.parse().with_context(|_| {
+                InvalidIntegerSnafu {
+                    key: key.to_string(),
+                }
+            })?;
+            ensure!(
+                range.contains(&value),
+                OutOfRangeSnafu {
+                    key,
+                    min: range.start().to_string(),
+                    max: range.end().to_string(),
+                }
+            );
+            Ok(value)
+        })
+        .transpose()
+}
+
+// A limit is either the same value for the soft and hard limits, or soft:hard.
+// infinity means no limit
+fn parse_rlimit(
+    value: &str,
+    resource: RLimitResource,
+) -> Option<RLimit> {
+    let parse = |limit: &str| -> Option<Option<u64>> {
+        match limit {
+            "infinity" => Some(None),
+            _ => limit.parse().ok().map(Some),
+        }
+    };
+    let (soft, hard) = match value.split_once(':') {
+        Some((soft, hard)) => (parse(soft)?, parse(hard)?),
+        None => (parse(value)?, parse(value)?),
+    };
+    // The soft limit can't be higher than the hard one
+    match (soft, hard) {
+        (None, Some(_)) => return None,
+        (Some(soft), Some(hard)) if soft > hard => return None,
+        _ => {}
+    }
+    Some(RLimit {
+        resource,
+        soft,
+        hard,
+    })
+}
+
+// A list of CPUs and ranges of CPUs, separated by commas or spaces. The CPUs
+// must fit in a cpu_set_t, which also bounds the size of the list
+fn parse_cpu_list(value: &str) -> Result<Vec<usize>> {
+    let max = nix::libc::CPU_SETSIZE as usize - 1;
+    let parse_cpu = |cpu: &str| -> Result<usize> {
+        let cpu = cpu
+            .parse()
+            .ok()
+            .with_context(|| InvalidCpuAffinitySnafu { value })?;
+        ensure!(
+            cpu <= max,
+            OutOfRangeSnafu {
+                key: "cpu_affinity",
+                min: "0",
+                max: max.to_string(),
+            }
+        );
+        Ok(cpu)
+    };
+    let mut cpus = Vec::new();
+    for item in value.split(|c: char| c == ',' || c.is_whitespace()) {
+        if item.is_empty() {
+            continue;
+        }
+        match item.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_cpu(first)?, parse_cpu(last)?);
+                ensure!(first <= last, InvalidCpuAffinitySnafu { value });
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(parse_cpu(item)?),
+        }
+    }
+    ensure!(!cpus.is_empty(), InvalidCpuAffinitySnafu { value });
+    Ok(cpus)
+}
+
+fn get_absolute_path(
+    values: &mut HashMap<&'static str, String>,
+    key: &'static str,
+) -> Result<Option<PathBuf>> {
+    values
+        .remove(key)
+        .map(PathBuf::from)
+        .map(|path| {
+            ensure!(path.is_absolute(), RelativePathSnafu { key });
+            Ok(path)
+        })
+        .transpose()
+}
+
+fn parse_exec_options(
+    values: &mut HashMap<&'static str, String>,
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+) -> Result<ExecOptions> {
+    let rlimits = RLimitResource::ALL
+        .iter()
+        .filter_map(|resource| {
+            values.remove(resource.key()).map(|value| {
+                let rlimit = parse_rlimit(&value, *resource).with_context(|| {
+                    InvalidRLimitSnafu {
+                        key: resource.key(),
+                    }
+                })?;
+                // setrlimit fails with EPERM when the number of open files is
+                // higher than fs.nr_open, which is always the case for infinity
+                ensure!(
+                    rlimit.resource != RLimitResource::Nofile || rlimit.hard.is_some(),
+                    InfiniteNofileSnafu
+                );
+                Ok(rlimit)
+            })
+        })
+        .collect::<Result<_>>()?;
+    let nice = get_int_in_range(values, "nice", -20..=19)?;
+    let io_priority = values
+        .remove("ioprio_class")
+        .map(IoPrioClass::try_from)
+        .transpose()
+        .with_context(|_| InvalidIoPrioClassSnafu)?;
+    let io_level = get_int_in_range(values, "ioprio_level", 0..=7)?;
+    let io_priority = match (io_priority, io_level) {
+        (None, None) => None,
+        (class, level) => {
+            Some(IoPriority {
+                class: class.unwrap_or(IoPrioClass::BestEffort),
+                level: level.unwrap_or(IoPriority::DEFAULT_LEVEL),
+            })
+        }
+    };
+    let cpu_affinity = values
+        .remove("cpu_affinity")
+        .map(|value| parse_cpu_list(&value))
+        .transpose()?
+        .unwrap_or_default();
+    let policy = values
+        .remove("sched_policy")
+        .map(SchedPolicy::try_from)
+        .transpose()
+        .with_context(|_| InvalidSchedPolicySnafu)?;
+    let priority = get_int_in_range(values, "sched_priority", 1..=99)?;
+    let scheduling = match (policy, priority) {
+        (None, None) => None,
+        (Some(policy), priority) if policy.is_realtime() => {
+            Some(Scheduling {
+                policy,
+                priority: priority.unwrap_or(1),
+            })
+        }
+        (Some(policy), None) => {
+            Some(Scheduling {
+                policy,
+                priority: 0,
+            })
+        }
+        (_, Some(_)) => SchedPriorityNotRealtimeSnafu.fail()?,
+    };
+    let oom_score_adj = get_int_in_range(values, "oom_score_adj", -1000..=1000)?;
+    let working_directory = get_absolute_path(values, "working_directory")?;
+    let umask = values
+        .remove("umask")
+        .map(|umask| {
+            u32::from_str_radix(&umask, 8)
+                .ok()
+                .filter(|umask| *umask <= 0o777)
+                .context(InvalidUmaskSnafu)
+        })
+        .transpose()?;
+    let root_directory = get_absolute_path(values, "root_directory")?;
+    let supplementary_groups = array_values
+        .remove("supplementary_groups")
+        .unwrap_or_default();
+    Ok(ExecOptions {
+        rlimits,
+        nice,
+        io_priority,
+        cpu_affinity,
+        scheduling,
+        oom_score_adj,
+        working_directory,
+        umask,
+        root_directory,
+        supplementary_groups,
+    })
+}
+
 impl SectionBuilder for ScriptBuilder {
     fn build(
         &mut self,
         values: &mut HashMap<&'static str, String>,
-        _array_values: &mut HashMap<&'static str, Vec<String>>,
+        array_values: &mut HashMap<&'static str, Vec<String>>,
         code_values: &mut HashMap<&'static str, String>,
     ) {
+        let section = self.name;
         let args: (&mut HashMap<&str, String>,) = (values,);
         self.script = Some(FnMut::call_mut(
             &mut move |values: &mut HashMap<&'static str, String>| -> Result<Script, ScriptBuilderError> {
+                if section != "run" {
+                    if let Some(field) = RUN_ONLY_FIELDS
+                        .iter()
+                        .find(|field| values.contains_key(*field) || code_values.contains_key(*field))
+                    {
+                        return RunOnlyFieldSnafu { field: *field }.fail();
+                    }
+                }
                 let prefix = values
                     .remove("prefix")
                     .with_context(|| NoPrefixFoundSnafu)?
@@ -98,6 +452,36 @@ impl SectionBuilder for ScriptBuilder {
                     .remove("down_signal")
                     .map_or(Ok(Script::DEFAULT_DOWN_SIGNAL), |down_signal| down_signal.parse::<Signal>().map(|sig| sig as i32))
                     .with_context(|_| InvalidSignalSnafu)?;
+                let down_signals = parse_signal_steps(array_values, timeout_kill)?;
+                let exec = Box::new(parse_exec_options(values, array_values)?);
+                let kill_mode = values
+                    .remove("kill_mode")
+                    .map_or(Ok(KillMode::default()), KillMode::try_from)
+                    .with_context(|_| InvalidKillModeSnafu)?;
+                let restart = values
+                    .remove("restart")
+                    .map_or(Ok(RestartPolicy::default()), RestartPolicy::try_from)
+                    .with_context(|_| InvalidRestartPolicySnafu)?;
+                let restart_delay = get_int_or_default(
+                    values,
+                    "restart_delay",
+                    Script::DEFAULT_RESTART_DELAY,
+                )?;
+                let restart_delay_max = get_int_or_default(
+                    values,
+                    "restart_delay_max",
+                    Script::DEFAULT_RESTART_DELAY_MAX,
+                )?;
+                let restart_limit_burst = get_int_or_default(
+                    values,
+                    "restart_limit_burst",
+                    Script::DEFAULT_RESTART_LIMIT_BURST,
+                )?;
+                let restart_limit_interval = get_int_or_default(
+                    values,
+                    "restart_limit_interval",
+                    Script::DEFAULT_RESTART_LIMIT_INTERVAL,
+                )?;
 
                 let user = values.remove("user");
                 let group = values.remove("group");
@@ -111,6 +495,49 @@ impl SectionBuilder for ScriptBuilder {
                             key: "notify".to_string(),
                         }
                     })?;
+                let notify_socket = values
+                    .remove("notify_socket")
+                    .map_or(Ok(false), |notify_socket| {
+                        match notify_socket.as_str() {
+                            "yes" => Ok(true),
+                            "no" => Ok(false),
+                            _ => Err(snafu::NoneError),
+                        }
+                    })
+                    .with_context(|_| {
+                        InvalidBooleanSnafu {
+                            key: "notify_socket".to_string(),
+                        }
+                    })?;
+                let readiness = parse_probe(values, code_values, "ready")?;
+                let liveness = parse_probe(values, code_values, "liveness")?;
+                let liveness_interval = get_int_or_default(
+                    values,
+                    "liveness_interval",
+                    Script::DEFAULT_LIVENESS_INTERVAL,
+                )?;
+                let liveness_timeout = get_int_or_default(
+                    values,
+                    "liveness_timeout",
+                    Script::DEFAULT_LIVENESS_TIMEOUT,
+                )?;
+                let liveness_threshold = get_int_or_default(
+                    values,
+                    "liveness_threshold",
+                    Script::DEFAULT_LIVENESS_THRESHOLD,
+                )?;
+                let watchdog = values
+                    .remove("watchdog")
+                    .map_or(Ok(None), |watchdog| watchdog.parse::<u32>().map(Some))
+                    .with_context(|_| {
+                        InvalidIntegerSnafu {
+                            key: "watchdog".to_string(),
+                        }
+                    })?;
+                let watchdog_signal = values
+                    .remove("watchdog_signal")
+                    .map_or(Ok(Script::DEFAULT_WATCHDOG_SIGNAL), |signal| signal.parse::<Signal>().map(|sig| sig as i32))
+                    .with_context(|_| InvalidSignalSnafu)?;
                 Ok(Script {
                     prefix,
                     execute,
@@ -118,9 +545,25 @@ impl SectionBuilder for ScriptBuilder {
                     timeout_kill,
                     max_deaths,
                     down_signal,
+                    down_signals,
+                    kill_mode,
+                    restart,
+                    restart_delay,
+                    restart_delay_max,
+                    restart_limit_burst,
+                    restart_limit_interval,
                     user,
                     group,
                     notify,
+                    notify_socket,
+                    readiness,
+                    liveness,
+                    liveness_interval,
+                    liveness_timeout,
+                    liveness_threshold,
+                    watchdog,
+                    watchdog_signal,
+                    exec,
                 })
             },
             args,
@@ -138,25 +581,65 @@ impl SectionBuilder for ScriptBuilder {
             "timeout_kill",
             "max_deaths",
             "down_signal",
+            "kill_mode",
+            "restart",
+            "restart_delay",
+            "restart_delay_max",
+            "restart_limit_burst",
+            "restart_limit_interval",
             "user",
             "group",
             "notify",
+            "notify_socket",
+            "ready_unix_socket",
+            "ready_tcp_port",
+            "ready_path",
+            "liveness_unix_socket",
+            "liveness_tcp_port",
+            "liveness_path",
+            "liveness_interval",
+            "liveness_timeout",
+            "liveness_threshold",
+            "watchdog",
+            "watchdog_signal",
+            "limit_as",
+            "limit_core",
+            "limit_cpu",
+            "limit_data",
+            "limit_fsize",
+            "limit_memlock",
+            "limit_msgqueue",
+            "limit_nice",
+            "limit_nofile",
+            "limit_nproc",
+            "limit_rtprio",
+            "limit_rttime",
+            "limit_sigpending",
+            "limit_stack",
+            "nice",
+            "ioprio_class",
+            "ioprio_level",
+            "cpu_affinity",
+            "sched_policy",
+            "sched_priority",
+            "oom_score_adj",
+            "working_directory",
+            "umask",
+            "root_directory",
         ]
     }
 
     fn get_array_fields(&self) -> &'static [&'static str] {
-        &[]
+        &["down_signals", "supplementary_groups"]
     }
 
     fn get_code_fields(&self) -> &'static [&'static str] {
-        &["execute"]
+        &["execute", "ready_script", "liveness_script"]
     }
 }
 
 #[cfg(test)]
 mod test {
-    use rinit_service::types::ScriptPrefix;
-
     use super::*;
 
     #[test]
@@ -172,5 +655,286 @@ mod test {
         let script = builder.script.unwrap().unwrap();
         assert_eq!(script.prefix, ScriptPrefix::Bash);
         assert_eq!(script.execute, "    exit 0\n".to_string());
+        assert_eq!(script.restart, RestartPolicy::Always);
+    }
+
+    #[test]
+    fn parse_run_only_fields() {
+        for field in [
+            "restart = never",
+            "restart_limit_burst = 3",
+            "notify_socket = yes",
+            "ready_path = /run/foo",
+            "liveness_tcp_port = 8080",
+            "liveness_interval = 1000",
+            "watchdog = 5000",
+            "watchdog_signal = SIGABRT",
+        ] {
+            for section in ["start", "stop", "finish"] {
+                let mut builder = ScriptBuilder::new_for_section(section);
+                builder
+                    .parse_until_next_section(&[
+                        "prefix = sh",
+                        field,
+                        "execute = (",
+                        "    foo",
+                        ")",
+                    ])
+                    .unwrap();
+                assert!(
+                    matches!(
+                        builder.script.unwrap(),
+                        Err(ScriptBuilderError::RunOnlyField { .. })
+                    ),
+                    "{field} in [{section}]"
+                );
+            }
+            let mut builder = ScriptBuilder::new_for_section("run");
+            builder
+                .parse_until_next_section(&["prefix = sh", field, "execute = (", "    foo", ")"])
+                .unwrap();
+            assert!(builder.script.unwrap().is_ok(), "{field} in [run]");
+        }
+    }
+
+    #[test]
+    fn parse_restart_policy() {
+        let mut builder = ScriptBuilder::new_for_section("run");
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "prefix = bash",
+                    "restart = on-failure",
+                    "kill_mode = process",
+                    "down_signals = [ SIGCONT:0 SIGTERM ]",
+                    "restart_delay = 500",
+                    "restart_delay_max = 60000",
+                    "restart_limit_burst = 10",
+                    "restart_limit_interval = 5000",
+                    "execute = (",
+                    "    exit 0",
+                    ")",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let script = builder.script.unwrap().unwrap();
+        assert_eq!(script.restart, RestartPolicy::OnFailure);
+        assert_eq!(script.kill_mode, KillMode::Process);
+        assert_eq!(
+            script.down_signals,
+            vec![
+                SignalStep {
+                    signal: Signal::SIGCONT as i32,
+                    wait: 0,
+                },
+                SignalStep {
+                    signal: Signal::SIGTERM as i32,
+                    wait: Script::DEFAULT_TIMEOUT_KILL,
+                },
+            ]
+        );
+        assert_eq!(script.restart_delay, 500);
+        assert_eq!(script.restart_delay_max, 60000);
+        assert_eq!(script.restart_limit_burst, 10);
+        assert_eq!(script.restart_limit_interval, 5000);
+    }
+
+    #[test]
+    fn parse_notify_socket() {
+        let mut builder = ScriptBuilder::new_for_section("run");
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "prefix = path",
+                    "notify_socket = yes",
+                    "execute = (",
+                    "    nginx",
+                    ")",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(builder.script.unwrap().unwrap().notify_socket);
+    }
+
+    #[test]
+    fn parse_readiness_probe() {
+        let mut builder = ScriptBuilder::new_for_section("run");
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "prefix = path",
+                    "ready_tcp_port = 5432",
+                    "execute = (",
+                    "    postgres",
+                    ")",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(
+            builder.script.unwrap().unwrap().readiness,
+            Some(Probe::TcpPort(5432))
+        );
+
+        let mut builder = ScriptBuilder::new_for_section("run");
+        builder
+            .parse_until_next_section(&[
+                "prefix = path",
+                "ready_path = /run/foo.pid",
+                "ready_unix_socket = /run/foo.sock",
+                "execute = (",
+                "    foo",
+                ")",
+            ])
+            .unwrap();
+        assert!(matches!(
+            builder.script.unwrap(),
+            Err(ScriptBuilderError::MultipleProbes { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_liveness_probe() {
+        let mut builder = ScriptBuilder::new_for_section("run");
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "prefix = path",
+                    "liveness_interval = 5000",
+                    "liveness_threshold = 2",
+                    "liveness_script = (",
+                    "    curl -f http://localhost:8080/health",
+                    ")",
+                    "execute = (",
+                    "    foo",
+                    ")",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let script = builder.script.unwrap().unwrap();
+        assert!(matches!(
+            script.liveness,
+            Some(Probe::Script(probe)) if probe.execute == "    curl -f http://localhost:8080/health\n"
+        ));
+        assert_eq!(script.liveness_interval, 5000);
+        assert_eq!(script.liveness_timeout, Script::DEFAULT_LIVENESS_TIMEOUT);
+        assert_eq!(script.liveness_threshold, 2);
+    }
+
+    #[test]
+    fn parse_watchdog() {
+        let mut builder = ScriptBuilder::new_for_section("run");
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "prefix = path",
+                    "watchdog = 30000",
+                    "watchdog_signal = SIGTERM",
+                    "execute = (",
+                    "    foo",
+                    ")",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let script = builder.script.unwrap().unwrap();
+        assert_eq!(script.watchdog, Some(30000));
+        assert_eq!(script.watchdog_signal, Signal::SIGTERM as i32);
+    }
+
+    #[test]
+    fn parse_exec_options() {
+        let mut builder = ScriptBuilder::new_for_section("run");
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "prefix = path",
+                    "limit_nofile = 1024:65536",
+                    "limit_memlock = infinity",
+                    "nice = -5",
+                    "ioprio_class = idle",
+                    "cpu_affinity = 0-2,5",
+                    "sched_policy = fifo",
+                    "sched_priority = 50",
+                    "oom_score_adj = -500",
+                    "working_directory = /var/lib/foo",
+                    "umask = 0027",
+                    "supplementary_groups = [ audio video ]",
+                    "execute = (",
+                    "    foo",
+                    ")",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let script = builder.script.unwrap().unwrap();
+        assert_eq!(
+            *script.exec,
+            ExecOptions {
+                rlimits: vec![
+                    RLimit {
+                        resource: RLimitResource::Memlock,
+                        soft: None,
+                        hard: None,
+                    },
+                    RLimit {
+                        resource: RLimitResource::Nofile,
+                        soft: Some(1024),
+                        hard: Some(65536),
+                    },
+                ],
+                nice: Some(-5),
+                io_priority: Some(IoPriority {
+                    class: IoPrioClass::Idle,
+                    level: IoPriority::DEFAULT_LEVEL,
+                }),
+                cpu_affinity: vec![0, 1, 2, 5],
+                scheduling: Some(Scheduling {
+                    policy: SchedPolicy::Fifo,
+                    priority: 50,
+                }),
+                oom_score_adj: Some(-500),
+                working_directory: Some(PathBuf::from("/var/lib/foo")),
+                umask: Some(0o027),
+                root_directory: None,
+                supplementary_groups: vec!["audio".to_string(), "video".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn parse_exec_options_invalid() {
+        for field in [
+            "limit_nofile = 4096:1024",
+            "limit_nofile = infinity",
+            "limit_nofile = 1024:infinity",
+            "nice = 20",
+            "cpu_affinity = 3-1",
+            "cpu_affinity = 0-18446744073709551615",
+            "cpu_affinity = 1024",
+            "sched_priority = 10",
+            "working_directory = var/lib/foo",
+            "umask = 0999",
+        ] {
+            let mut builder = ScriptBuilder::new_for_section("run");
+            builder
+                .parse_until_next_section(&["prefix = path", field, "execute = (", "    foo", ")"])
+                .unwrap();
+            assert!(builder.script.unwrap().is_err(), "{field}");
+        }
+
+        // A huge range is rejected before any CPU is added to the list
+        assert!(matches!(
+            parse_cpu_list("0-18446744073709551615"),
+            Err(ScriptBuilderError::OutOfRange { key, .. }) if key == "cpu_affinity"
+        ));
     }
 }
@@ -1,11 +1,15 @@
 use std::{
     collections::HashMap,
+    path::PathBuf,
     str::FromStr,
 };
 
 use rinit_service::types::{
+    Condition,
     RunLevel,
     RunLevelParseError,
+    Script,
+    ScriptPrefix,
     ServiceOptions,
 };
 use snafu::{
@@ -35,43 +39,104 @@ impl ServiceOptionsBuilder {
     }
 }
 
+fn parse_boolean(
+    values: &mut HashMap<&'static str, String>,
+    key: &'static str,
+) -> Result<Option<bool>> {
+    values
+        .remove(key)
+        .map_or(Ok(None), |value| {
+            match value.as_str() {
+                "yes" => Ok(Some(true)),
+                "no" => Ok(Some(false)),
+                _ => Err(snafu::NoneError),
+            }
+        })
+        .with_context(|_| InvalidBooleanSnafu { key })
+}
+
+// A leading '!' negates the condition
+fn split_negation(value: &str) -> (&str, bool) {
+    match value.strip_prefix('!') {
+        Some(value) => (value, true),
+        None => (value, false),
+    }
+}
+
+fn parse_conditions(
+    values: &mut HashMap<&'static str, String>,
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+    code_values: &mut HashMap<&'static str, String>,
+) -> Result<Vec<Condition>> {
+    let mut conditions: Vec<Condition> = array_values
+        .remove("condition-path-exists")
+        .unwrap_or_default()
+        .iter()
+        .map(|path| {
+            let (path, negate) = split_negation(path);
+            Condition::PathExists {
+                path: PathBuf::from(path),
+                negate,
+            }
+        })
+        .collect();
+    conditions.extend(
+        array_values
+            .remove("condition-kernel-cmdline")
+            .unwrap_or_default()
+            .iter()
+            .map(|key| {
+                let (key, negate) = split_negation(key);
+                Condition::KernelCommandLine {
+                    key: key.to_owned(),
+                    negate,
+                }
+            }),
+    );
+    conditions.extend(parse_boolean(values, "condition-container")?.map(Condition::Container));
+    conditions.extend(code_values.remove("condition-script").map(|execute| {
+        // A false condition is not a failure, do not run the script again
+        Condition::Script(Box::new(Script {
+            max_deaths: 1,
+            ..Script::new(ScriptPrefix::Sh, execute)
+        }))
+    }));
+    Ok(conditions)
+}
+
 impl SectionBuilder for ServiceOptionsBuilder {
     fn build(
         &mut self,
         values: &mut HashMap<&'static str, String>,
         array_values: &mut HashMap<&'static str, Vec<String>>,
-        _code_values: &mut HashMap<&'static str, String>,
+        code_values: &mut HashMap<&'static str, String>,
     ) {
         let dependencies = array_values.remove("dependencies").unwrap_or_default();
         let requires = array_values.remove("requires").unwrap_or_default();
         let requires_one = array_values.remove("requires-one").unwrap_or_default();
-        let autostart = values
-            .remove("autostart")
-            .map_or(Ok(true), |autostart| {
-                match autostart.as_str() {
-                    "yes" => Ok(true),
-                    "no" => Ok(false),
-                    _ => Err(snafu::NoneError),
-                }
-            })
-            .with_context(|_| {
-                InvalidBooleanSnafu {
-                    key: "autostart".to_string(),
-                }
-            });
+        let after = array_values.remove("after").unwrap_or_default();
+        let before = array_values.remove("before").unwrap_or_default();
+        let autostart =
+            parse_boolean(values, "autostart").map(|autostart| autostart.unwrap_or(true));
         let runlevel = values
             .remove("runlevel")
             .map_or(Ok(RunLevel::default()), |s| RunLevel::from_str(&s))
             .with_context(|_| RunLevelParseSnafu);
+        let conditions = parse_conditions(values, array_values, code_values);
         self.options = Some(autostart.and_then(|autostart| {
-            runlevel.map(|runlevel| {
-                ServiceOptions {
-                    dependencies,
-                    requires,
-                    requires_one,
-                    autostart,
-                    runlevel,
-                }
+            runlevel.and_then(|runlevel| {
+                conditions.map(|conditions| {
+                    ServiceOptions {
+                        dependencies,
+                        requires,
+                        requires_one,
+                        after,
+                        before,
+                        autostart,
+                        runlevel,
+                        conditions,
+                    }
+                })
             })
         }));
     }
@@ -81,15 +146,23 @@ impl SectionBuilder for ServiceOptionsBuilder {
     }
 
     fn get_fields(&self) -> &'static [&'static str] {
-        &[]
+        &["autostart", "runlevel", "condition-container"]
     }
 
     fn get_array_fields(&self) -> &'static [&'static str] {
-        &["dependencies", "requires", "requires-one"]
+        &[
+            "dependencies",
+            "requires",
+            "requires-one",
+            "after",
+            "before",
+            "condition-path-exists",
+            "condition-kernel-cmdline",
+        ]
     }
 
     fn get_code_fields(&self) -> &'static [&'static str] {
-        &[]
+        &["condition-script"]
     }
 }
 
@@ -105,7 +178,9 @@ mod test {
                 .parse_until_next_section(&[
                     "dependencies = [ foo ]",
                     "requires = [ bar ]",
-                    "requires-one = [ foobar ]"
+                    "requires-one = [ foobar ]",
+                    "after = [ network ]",
+                    "before = [ login ]",
                 ])
                 .unwrap()
                 .is_empty()
@@ -115,5 +190,48 @@ mod test {
         assert_eq!(options.dependencies, vec!["foo".to_string()]);
         assert_eq!(options.requires, vec!["bar".to_string()]);
         assert_eq!(options.requires_one, vec!["foobar".to_string()]);
+        assert_eq!(options.after, vec!["network".to_string()]);
+        assert_eq!(options.before, vec!["login".to_string()]);
+    }
+
+    #[test]
+    fn parse_conditions() {
+        let mut builder = ServiceOptionsBuilder::new();
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "condition-path-exists = [ /etc/foo !/run/bar ]",
+                    "condition-kernel-cmdline = [ quiet ]",
+                    "condition-container = no",
+                    "condition-script = (",
+                    "    test -f /etc/foobar",
+                    ")",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let options = builder.options.unwrap().unwrap();
+        assert_eq!(
+            options.conditions[..4],
+            [
+                Condition::PathExists {
+                    path: PathBuf::from("/run/bar"),
+                    negate: true
+                },
+                Condition::PathExists {
+                    path: PathBuf::from("/etc/foo"),
+                    negate: false
+                },
+                Condition::KernelCommandLine {
+                    key: "quiet".to_string(),
+                    negate: false
+                },
+                Condition::Container(false),
+            ]
+        );
+        assert!(
+            matches!(&options.conditions[4], Condition::Script(script) if script.max_deaths == 1)
+        );
     }
 }
@@ -0,0 +1,243 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+};
+
+use rinit_service::types::{
+    Activation,
+    InvalidActivationError,
+    Socket,
+    SocketAddress,
+};
+use snafu::{
+    ensure,
+    OptionExt,
+    ResultExt,
+    Snafu,
+};
+
+use super::SectionBuilder;
+
+#[derive(Snafu, Debug)]
+pub enum SocketBuilderError {
+    #[snafu(display("no socket has been declared"))]
+    NoSockets,
+    #[snafu(display("{port} is not a valid port"))]
+    InvalidPort { port: String },
+    #[snafu(display("{mode} is not a valid octal mode"))]
+    InvalidMode { mode: String },
+    #[snafu(display("{path} is not an absolute path"))]
+    RelativePath { path: String },
+    #[snafu(display("{source}"))]
+    InvalidActivation { source: InvalidActivationError },
+    #[snafu(display("{key} must be either 'yes' or 'no'"))]
+    InvalidBoolean { key: String },
+    #[snafu(display("{key} must be a positive integer"))]
+    InvalidInteger { key: String },
+    #[snafu(display("accept mode only works with unix-stream and tcp sockets"))]
+    AcceptNeedsStreamSockets,
+}
+
+pub struct SocketBuilder {
+    pub socket: Option<Result<Socket, SocketBuilderError>>,
+}
+
+type Result<T, E = SocketBuilderError> = std::result::Result<T, E>;
+
+impl SocketBuilder {
+    pub fn new() -> Self {
+        Self { socket: None }
+    }
+}
+
+fn parse_paths(
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+    key: &'static str,
+    address: fn(PathBuf) -> SocketAddress,
+) -> Result<Vec<SocketAddress>> {
+    array_values
+        .remove(key)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|path| {
+            ensure!(path.starts_with('/'), RelativePathSnafu { path });
+            Ok(address(PathBuf::from(path)))
+        })
+        .collect()
+}
+
+fn parse_ports(
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+    key: &'static str,
+    address: fn(u16) -> SocketAddress,
+) -> Result<Vec<SocketAddress>> {
+    array_values
+        .remove(key)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|port| {
+            match port.parse::<u16>() {
+                Ok(port) if port != 0 => Ok(address(port)),
+                _ => InvalidPortSnafu { port }.fail(),
+            }
+        })
+        .collect()
+}
+
+fn parse_socket(
+    values: &mut HashMap<&'static str, String>,
+    array_values: &mut HashMap<&'static str, Vec<String>>,
+) -> Result<Socket> {
+    // The sockets are passed to the service in this order
+    let mut listen = parse_paths(array_values, "unix-stream", SocketAddress::UnixStream)?;
+    listen.extend(parse_paths(
+        array_values,
+        "unix-datagram",
+        SocketAddress::UnixDatagram,
+    )?);
+    listen.extend(parse_paths(array_values, "fifo", SocketAddress::Fifo)?);
+    listen.extend(parse_ports(array_values, "tcp", SocketAddress::Tcp)?);
+    listen.extend(parse_ports(array_values, "udp", SocketAddress::Udp)?);
+    ensure!(!listen.is_empty(), NoSocketsSnafu);
+    let mode = values
+        .remove("mode")
+        .map_or(Ok(Socket::DEFAULT_MODE), |mode| {
+            u32::from_str_radix(&mode, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .ok_or(SocketBuilderError::InvalidMode { mode })
+        })?;
+    let activation = values
+        .remove("activation")
+        .map_or(Ok(Activation::default()), Activation::try_from)
+        .with_context(|_| InvalidActivationSnafu)?;
+    let accept = values
+        .remove("accept")
+        .map_or(Ok(false), |accept| {
+            match accept.as_str() {
+                "yes" => Ok(true),
+                "no" => Ok(false),
+                _ => Err(snafu::NoneError),
+            }
+        })
+        .with_context(|_| InvalidBooleanSnafu { key: "accept" })?;
+    ensure!(
+        !accept || listen.iter().all(SocketAddress::is_stream),
+        AcceptNeedsStreamSocketsSnafu
+    );
+    let max_connections = match values.remove("max-connections") {
+        Some(max) => {
+            max.parse::<u32>()
+                .ok()
+                .filter(|max| *max > 0)
+                .with_context(|| {
+                    InvalidIntegerSnafu {
+                        key: "max-connections",
+                    }
+                })?
+        }
+        None => Socket::DEFAULT_MAX_CONNECTIONS,
+    };
+    Ok(Socket {
+        listen,
+        mode,
+        activation,
+        accept,
+        max_connections,
+    })
+}
+
+impl SectionBuilder for SocketBuilder {
+    fn build(
+        &mut self,
+        values: &mut HashMap<&'static str, String>,
+        array_values: &mut HashMap<&'static str, Vec<String>>,
+        _code_values: &mut HashMap<&'static str, String>,
+    ) {
+        self.socket = Some(parse_socket(values, array_values));
+    }
+
+    fn section_name(&self) -> &'static str {
+        "socket"
+    }
+
+    fn get_fields(&self) -> &'static [&'static str] {
+        &["mode", "activation", "accept", "max-connections"]
+    }
+
+    fn get_array_fields(&self) -> &'static [&'static str] {
+        &["unix-stream", "unix-datagram", "fifo", "tcp", "udp"]
+    }
+
+    fn get_code_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_section() {
+        let mut builder = SocketBuilder::new();
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "unix-stream = [ /run/foo.sock ]",
+                    "tcp = [ 8080 ]",
+                    "fifo = [ /run/foo.fifo ]",
+                    "mode = 0660",
+                    "activation = on-demand",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let socket = builder.socket.unwrap().unwrap();
+        assert_eq!(
+            socket.listen,
+            vec![
+                SocketAddress::UnixStream(PathBuf::from("/run/foo.sock")),
+                SocketAddress::Fifo(PathBuf::from("/run/foo.fifo")),
+                SocketAddress::Tcp(8080),
+            ]
+        );
+        assert_eq!(socket.mode, 0o660);
+        assert_eq!(socket.activation, Activation::OnDemand);
+    }
+
+    #[test]
+    fn parse_section_invalid_port() {
+        let mut builder = SocketBuilder::new();
+        builder
+            .parse_until_next_section(&["udp = [ 70000 ]"])
+            .unwrap();
+
+        assert!(matches!(
+            builder.socket.unwrap(),
+            Err(SocketBuilderError::InvalidPort { .. })
+        ));
+    }
+
+    #[test]
+    fn parse_section_accept() {
+        let mut builder = SocketBuilder::new();
+        builder
+            .parse_until_next_section(&["tcp = [ 2222 ]", "accept = yes", "max-connections = 4"])
+            .unwrap();
+
+        let socket = builder.socket.unwrap().unwrap();
+        assert!(socket.accept);
+        assert_eq!(socket.max_connections, 4);
+
+        let mut builder = SocketBuilder::new();
+        builder
+            .parse_until_next_section(&["udp = [ 2222 ]", "accept = yes"])
+            .unwrap();
+        assert!(matches!(
+            builder.socket.unwrap(),
+            Err(SocketBuilderError::AcceptNeedsStreamSockets)
+        ));
+    }
+}
@@ -0,0 +1,143 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+};
+
+use rinit_service::types::{
+    CalendarEvent,
+    CalendarEventParseError,
+    Timer,
+};
+use snafu::{
+    ensure,
+    ResultExt,
+    Snafu,
+};
+
+use super::SectionBuilder;
+
+#[derive(Snafu, Debug)]
+pub enum TimerBuilderError {
+    #[snafu(display("{key} must be a positive integer"))]
+    InvalidInteger {
+        key: String,
+        source: std::num::ParseIntError,
+    },
+    #[snafu(display("{source}"))]
+    CalendarEventParseError { source: CalendarEventParseError },
+    #[snafu(display("the timer must set on_boot_sec, on_active_sec or on_calendar"))]
+    NoTrigger,
+}
+
+pub struct TimerBuilder {
+    pub timer: Option<Result<Timer, TimerBuilderError>>,
+}
+
+type Result<T, E = TimerBuilderError> = std::result::Result<T, E>;
+
+impl TimerBuilder {
+    pub fn new() -> Self {
+        Self { timer: None }
+    }
+}
+
+fn parse_seconds(
+    values: &mut HashMap<&'static str, String>,
+    key: &'static str,
+) -> Result<Option<u64>> {
+    values
+        .remove(key)
+        .map_or(Ok(None), |value| value.parse::<u64>().map(Some))
+        .with_context(|_| InvalidIntegerSnafu { key })
+}
+
+fn parse_timer(values: &mut HashMap<&'static str, String>) -> Result<Timer> {
+    let timer = Timer {
+        on_boot_sec: parse_seconds(values, "on_boot_sec")?,
+        on_active_sec: parse_seconds(values, "on_active_sec")?,
+        on_calendar: values
+            .remove("on_calendar")
+            .map_or(Ok(None), |event| CalendarEvent::from_str(&event).map(Some))
+            .with_context(|_| CalendarEventParseSnafu)?,
+        randomized_delay_sec: parse_seconds(values, "randomized_delay_sec")?.unwrap_or_default(),
+    };
+    ensure!(
+        timer.on_boot_sec.is_some() || timer.on_active_sec.is_some() || timer.on_calendar.is_some(),
+        NoTriggerSnafu
+    );
+    Ok(timer)
+}
+
+impl SectionBuilder for TimerBuilder {
+    fn build(
+        &mut self,
+        values: &mut HashMap<&'static str, String>,
+        _array_values: &mut HashMap<&'static str, Vec<String>>,
+        _code_values: &mut HashMap<&'static str, String>,
+    ) {
+        self.timer = Some(parse_timer(values));
+    }
+
+    fn section_name(&self) -> &'static str {
+        "timer"
+    }
+
+    fn get_fields(&self) -> &'static [&'static str] {
+        &[
+            "on_boot_sec",
+            "on_active_sec",
+            "on_calendar",
+            "randomized_delay_sec",
+        ]
+    }
+
+    fn get_array_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn get_code_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_section() {
+        let mut builder = TimerBuilder::new();
+        assert!(
+            builder
+                .parse_until_next_section(&[
+                    "on_boot_sec = 300",
+                    "on_calendar = Mon *-*-* 03:00",
+                    "randomized_delay_sec = 60",
+                ])
+                .unwrap()
+                .is_empty()
+        );
+
+        let timer = builder.timer.unwrap().unwrap();
+        assert_eq!(timer.on_boot_sec, Some(300));
+        assert_eq!(timer.on_active_sec, None);
+        assert_eq!(
+            timer.on_calendar,
+            Some(CalendarEvent::from_str("Mon *-*-* 03:00").unwrap())
+        );
+        assert_eq!(timer.randomized_delay_sec, 60);
+    }
+
+    #[test]
+    fn parse_section_no_trigger() {
+        let mut builder = TimerBuilder::new();
+        builder
+            .parse_until_next_section(&["randomized_delay_sec = 60"])
+            .unwrap();
+
+        assert!(matches!(
+            builder.timer.unwrap(),
+            Err(TimerBuilderError::NoTrigger)
+        ));
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+};
+
+use rinit_service::types::{
+    RunLevel,
+    RunLevelParseError,
+    VirtualOptions,
+};
+use snafu::{
+    ResultExt,
+    Snafu,
+};
+
+use super::SectionBuilder;
+
+#[derive(Snafu, Debug)]
+pub enum VirtualOptionsBuilderError {
+    #[snafu(display("empty providers found"))]
+    EmptyProviders,
+    #[snafu(display("{source}"))]
+    RunLevelParseError { source: RunLevelParseError },
+}
+
+pub struct VirtualOptionsBuilder {
+    pub virtual_options: Option<Result<VirtualOptions, VirtualOptionsBuilderError>>,
+}
+
+type Result<T, E = VirtualOptionsBuilderError> = std::result::Result<T, E>;
+
+impl VirtualOptionsBuilder {
+    pub fn new() -> Self {
+        VirtualOptionsBuilder {
+            virtual_options: None,
+        }
+    }
+}
+
+impl SectionBuilder for VirtualOptionsBuilder {
+    fn build(
+        &mut self,
+        values: &mut HashMap<&'static str, String>,
+        array_values: &mut HashMap<&'static str, Vec<String>>,
+        _code_values: &mut HashMap<&'static str, String>,
+    ) {
+        let providers = array_values.remove("providers");
+        let runlevel = values
+            .remove("runlevel")
+            .map_or(Ok(RunLevel::default()), |s| RunLevel::from_str(&s))
+            .with_context(|_| RunLevelParseSnafu);
+        self.virtual_options = Some(providers.map_or(
+            Err(VirtualOptionsBuilderError::EmptyProviders),
+            |providers| {
+                runlevel.map(|runlevel| {
+                    VirtualOptions {
+                        providers,
+                        runlevel,
+                    }
+                })
+            },
+        ));
+    }
+
+    fn section_name(&self) -> &'static str {
+        "options"
+    }
+
+    fn get_fields(&self) -> &'static [&'static str] {
+        &["runlevel"]
+    }
+
+    fn get_array_fields(&self) -> &'static [&'static str] {
+        &["providers"]
+    }
+
+    fn get_code_fields(&self) -> &'static [&'static str] {
+        &[]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_section() {
+        let mut builder = VirtualOptionsBuilder::new();
+        assert!(
+            builder
+                .parse_until_next_section(&["providers = [ foo bar ]", "runlevel = boot"])
+                .unwrap()
+                .is_empty()
+        );
+
+        let virtual_options = builder.virtual_options.unwrap().unwrap();
+        assert_eq!(
+            virtual_options.providers,
+            vec!["bar".to_string(), "foo".to_string()]
+        );
+        assert_eq!(virtual_options.runlevel, RunLevel::Boot);
+    }
+
+    #[test]
+    fn parse_section_no_providers() {
+        let mut builder = VirtualOptionsBuilder::new();
+        builder
+            .parse_until_next_section(&["runlevel = boot"])
+            .unwrap();
+
+        assert!(matches!(
+            builder.virtual_options.unwrap(),
+            Err(VirtualOptionsBuilderError::EmptyProviders)
+        ));
+    }
+}
@@ -24,6 +24,11 @@ pub enum ParseServiceError {
     OpenFile { path: PathBuf, source: io::Error },
     #[snafu(display("unable to read line from file {:?}", path))]
     ReadFile { path: PathBuf, source: io::Error },
+    #[snafu(display(
+        "the name of the template service in file {:?} must end with '@'",
+        path
+    ))]
+    InvalidTemplateName { path: PathBuf },
     #[snafu(display("unable to read the name of service in file {:?} at line 1", path))]
     NameNotFound { path: PathBuf },
     #[snafu(display("while reading file {:?}", path))]
@@ -44,12 +49,35 @@ unsafe impl Send for ParseServiceError {}
 
 type Result<T, E = ParseServiceError> = std::result::Result<T, E>;
 
+/// Placeholder replaced by the instance name when parsing a template service
+pub const INSTANCE_PLACEHOLDER: &str = "%i";
+
 pub fn parse_service(path: &Path) -> Result<Service> {
-    let file = fs::read_to_string(path).with_context(|_| {
+    parse_service_impl(path, None)
+}
+
+/// Parse an instance of a template service, e.g. getty@tty1 using the template
+/// file of getty@. Every occurrence of INSTANCE_PLACEHOLDER is replaced by the
+/// instance name
+pub fn parse_template_service(
+    path: &Path,
+    instance: &str,
+) -> Result<Service> {
+    parse_service_impl(path, Some(instance))
+}
+
+fn parse_service_impl(
+    path: &Path,
+    instance: Option<&str>,
+) -> Result<Service> {
+    let mut file = fs::read_to_string(path).with_context(|_| {
         OpenFileSnafu {
             path: path.to_owned(),
         }
     })?;
+    if let Some(instance) = instance {
+        file = file.replace(INSTANCE_PLACEHOLDER, instance);
+    }
     let lines = file
         .split_inclusive('\n')
         .map(|line| line.trim_end())
@@ -67,7 +95,17 @@ pub fn parse_service(path: &Path) -> Result<Service> {
 
     read_key_value!("name", name, NameNotFoundSnafu, lines[0]);
     // Otherwise we can't borrow line as mutable again
-    let name = name.to_owned();
+    let name = if let Some(instance) = instance {
+        ensure!(
+            name.ends_with('@'),
+            InvalidTemplateNameSnafu {
+                path: path.to_owned()
+            }
+        );
+        format!("{name}{instance}")
+    } else {
+        name.to_owned()
+    };
 
     read_key_value!("type", service_type, TypeNotFoundSnafu, lines[1]);
     match service_type {
@@ -114,7 +152,20 @@ pub fn parse_service(path: &Path) -> Result<Service> {
                 }
             })
         }
-        // "virtual" => VirtualParser::parse(name, reader),
+        "virtual" => {
+            let mut builder = VirtualBuilder::new(name);
+            builder.parse(&lines[2..]).with_context(|_| {
+                ServiceParseSnafu {
+                    path: path.to_owned(),
+                }
+            })?;
+
+            builder.build().with_context(|_| {
+                ServiceBuildSnafu {
+                    path: path.to_owned(),
+                }
+            })
+        }
         _ => {
             TypeNotFoundSnafu {
                 path: path.to_owned(),
@@ -126,7 +177,10 @@ pub fn parse_service(path: &Path) -> Result<Service> {
 
 #[cfg(test)]
 mod test {
-    use std::path::PathBuf;
+    use std::{
+        path::PathBuf,
+        str::FromStr,
+    };
 
     use super::*;
 
@@ -168,6 +222,8 @@ mod test {
                 name: "foo".to_string(),
                 start: Script::new(ScriptPrefix::Bash, "    exit 0\n".to_string()),
                 stop: None,
+                timer: None,
+                path: None,
                 options: ServiceOptions::new(),
                 environment: ScriptEnvironment::new(),
             }),
@@ -181,6 +237,32 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn parse_oneshot_with_timer() -> Result<(), ParseServiceError> {
+        assert_eq!(
+            Service::Oneshot(Oneshot {
+                name: "foo".to_string(),
+                start: Script::new(ScriptPrefix::Bash, "    exit 0\n".to_string()),
+                stop: None,
+                timer: Some(Timer {
+                    on_calendar: Some(CalendarEvent::from_str("daily").unwrap()),
+                    randomized_delay_sec: 600,
+                    ..Timer::default()
+                }),
+                path: None,
+                options: ServiceOptions::new(),
+                environment: ScriptEnvironment::new(),
+            }),
+            parse_service(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("test/samples/oneshot_with_timer")
+                    .as_path()
+            )?
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn parse_oneshot_with_stop() -> Result<(), ParseServiceError> {
         assert_eq!(
@@ -188,6 +270,8 @@ mod test {
                 name: "foo".to_string(),
                 start: Script::new(ScriptPrefix::Bash, "    exit 0\n".to_string()),
                 stop: Some(Script::new(ScriptPrefix::Sh, "    exit 1\n".to_string())),
+                timer: None,
+                path: None,
                 options: ServiceOptions::new(),
                 environment: ScriptEnvironment::new(),
             }),
@@ -207,7 +291,12 @@ mod test {
             Service::Longrun(Longrun {
                 name: "foo".to_string(),
                 run: Script::new(ScriptPrefix::Bash, "    loop\n".to_string()),
+                stop: None,
                 finish: None,
+                socket: None,
+                path: None,
+                resources: None,
+                sandbox: None,
                 options: ServiceOptions::new(),
                 environment: ScriptEnvironment::new(),
             }),
@@ -221,6 +310,57 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn parse_virtual() -> Result<(), ParseServiceError> {
+        assert_eq!(
+            Service::Virtual(Virtual {
+                name: "foo".to_string(),
+                options: VirtualOptions {
+                    providers: vec!["bar".to_string(), "foobar".to_string()],
+                    runlevel: RunLevel::Default
+                }
+            }),
+            parse_service(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("test/samples/virtual")
+                    .as_path()
+            )?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_template() -> Result<(), ParseServiceError> {
+        let mut env = ScriptEnvironment::new();
+        env.add("TTY", "tty1".to_string());
+        assert_eq!(
+            Service::Longrun(Longrun {
+                name: "getty@tty1".to_string(),
+                run: Script::new(ScriptPrefix::Bash, "    agetty tty1\n".to_string()),
+                stop: None,
+                finish: None,
+                socket: None,
+                path: None,
+                resources: None,
+                sandbox: None,
+                options: ServiceOptions {
+                    dependencies: vec!["udev@tty1".to_string()],
+                    ..ServiceOptions::new()
+                },
+                environment: env,
+            }),
+            parse_template_service(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("test/samples/template")
+                    .as_path(),
+                "tty1"
+            )?
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn parse_longrun_no_run() {
         assert!(
@@ -232,4 +372,16 @@ mod test {
             .is_err()
         );
     }
+
+    #[test]
+    fn parse_longrun_notify_in_listen_fds() {
+        assert!(
+            parse_service(
+                PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                    .join("test/samples/longrun_notify_in_listen_fds")
+                    .as_path()
+            )
+            .is_err()
+        );
+    }
 }
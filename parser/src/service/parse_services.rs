@@ -18,6 +18,7 @@ use snafu::{
 
 use crate::{
     parse_service,
+    parse_template_service,
     ParseServiceError,
 };
 
@@ -27,6 +28,11 @@ pub enum ServicesParserError {
     ParsingServiceError { source: ParseServiceError },
     #[snafu(display("could not find service file for {:?}", service))]
     CouldNotFindService { service: String },
+    #[snafu(display(
+        "the instance name of {:?} can only contain letters, digits, '_', '.', ':' and '-'",
+        service
+    ))]
+    InvalidInstance { service: String },
     #[snafu(display(
         "the service name is different than the file name for {:?}",
         service_file
@@ -49,29 +55,36 @@ pub fn parse_services(
         .into_iter()
         .map(|service| {
             // If we don't find the services passed as args on the system, return an error
-            if let Some(file) = get_service_file(&service, &service_dirs, system) {
-                Ok((service, file))
+            if let Some((file, instance)) = find_service_file(&service, &service_dirs, system)? {
+                Ok((service, file, instance))
             } else {
                 Err(ServicesParserError::CouldNotFindService { service })
             }
         })
-        .collect::<Result<Vec<(String, PathBuf)>, ServicesParserError>>()?;
+        .collect::<Result<Vec<(String, PathBuf, Option<String>)>, ServicesParserError>>()?;
 
-    while let Some((name, file)) = to_parse.pop() {
-        let service = parse_service(&file).context(ParsingServiceSnafu {})?;
+    while let Some((name, file, instance)) = to_parse.pop() {
+        let service = if let Some(instance) = instance {
+            parse_template_service(&file, &instance)
+        } else {
+            parse_service(&file)
+        }
+        .context(ParsingServiceSnafu {})?;
         ensure!(
             service.name() == name,
             NameNotMatchingFileSnafu { service_file: file }
         );
         // Skip services that we can't found, the dependency graph will
         // handle the error
-        to_parse.extend(service.dependencies().iter().filter_map(|service| {
-            if services_already_parsed.insert(service.clone()) {
-                get_service_file(service, &service_dirs, system).map(|file| (service.clone(), file))
-            } else {
-                None
+        for dependency in service.dependencies() {
+            if services_already_parsed.insert(dependency.clone()) {
+                if let Some((file, instance)) =
+                    find_service_file(dependency, &service_dirs, system)?
+                {
+                    to_parse.push((dependency.clone(), file, instance));
+                }
             }
-        }));
+        }
 
         results.push(service);
     }
@@ -79,6 +92,41 @@ pub fn parse_services(
     Ok(results)
 }
 
+// The instance name is substituted in the service file and ends up in the
+// paths built from the service name, like the one of its log file
+fn is_valid_instance(instance: &str) -> bool {
+    instance != "."
+        && instance != ".."
+        && instance
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | ':' | '-'))
+}
+
+// An instance of a template service, e.g. getty@tty1, uses its own file when it
+// exists, otherwise the template file getty@ along with the instance name
+fn find_service_file(
+    service: &str,
+    paths: &[PathBuf],
+    system: bool,
+) -> Result<Option<(PathBuf, Option<String>)>, ServicesParserError> {
+    let template = service
+        .split_once('@')
+        .filter(|(_, instance)| !instance.is_empty());
+    if let Some((_, instance)) = template {
+        ensure!(
+            is_valid_instance(instance),
+            InvalidInstanceSnafu { service }
+        );
+    }
+    Ok(get_service_file(service, paths, system)
+        .map(|file| (file, None))
+        .or_else(|| {
+            let (template, instance) = template?;
+            get_service_file(&format!("{template}@"), paths, system)
+                .map(|file| (file, Some(instance.to_owned())))
+        }))
+}
+
 fn get_service_file(
     service: &str,
     paths: &[PathBuf],
@@ -94,3 +142,26 @@ fn get_service_file(
         }
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn instance_names() {
+        assert!(is_valid_instance("tty1"));
+        assert!(is_valid_instance("eth0.100"));
+        assert!(is_valid_instance("user:1000"));
+        for instance in ["..", ".", "../../x", "x\n[start]", "a b", "a/b"] {
+            assert!(!is_valid_instance(instance), "{instance:?}");
+        }
+        assert!(matches!(
+            find_service_file("getty@../../x", &[], true),
+            Err(ServicesParserError::InvalidInstance { .. })
+        ));
+        assert!(matches!(
+            find_service_file("getty@tty1", &[], true),
+            Ok(None)
+        ));
+    }
+}
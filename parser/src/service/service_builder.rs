@@ -5,6 +5,7 @@ use rinit_service::types::{
     ScriptEnvironment,
     Service,
     ServiceOptions,
+    Virtual,
 };
 use snafu::{
     ensure,
@@ -19,11 +20,17 @@ use crate::{
     parse_section::parse_section,
     section::{
         BundleOptionsBuilder,
+        PathBuilder,
+        ResourcesBuilder,
+        SandboxBuilder,
         ScriptBuilder,
         ScriptEnvironmentBuilder,
         SectionBuilder,
         SectionBuilderError,
         ServiceOptionsBuilder,
+        SocketBuilder,
+        TimerBuilder,
+        VirtualOptionsBuilder,
     },
 };
 
@@ -109,6 +116,8 @@ pub struct OneshotBuilder {
     name: String,
     start_builder: ScriptBuilder,
     stop_builder: ScriptBuilder,
+    timer_builder: TimerBuilder,
+    path_builder: PathBuilder,
     options_builder: ServiceOptionsBuilder,
     env_builder: ScriptEnvironmentBuilder,
 }
@@ -125,6 +134,8 @@ impl OneshotBuilder {
             name,
             start_builder: ScriptBuilder::new_for_section("start"),
             stop_builder: ScriptBuilder::new_for_section("stop"),
+            timer_builder: TimerBuilder::new(),
+            path_builder: PathBuilder::new(),
             options_builder: ServiceOptionsBuilder::new(),
             env_builder: ScriptEnvironmentBuilder::new(),
         }
@@ -134,7 +145,12 @@ impl OneshotBuilder {
 pub struct LongrunBuilder {
     name: String,
     run_builder: ScriptBuilder,
+    stop_builder: ScriptBuilder,
     finish_builder: ScriptBuilder,
+    socket_builder: SocketBuilder,
+    path_builder: PathBuilder,
+    resources_builder: ResourcesBuilder,
+    sandbox_builder: SandboxBuilder,
     options_builder: ServiceOptionsBuilder,
     env_builder: ScriptEnvironmentBuilder,
 }
@@ -143,6 +159,10 @@ pub struct LongrunBuilder {
 pub enum LongrunBuilderError {
     #[snafu(display("no start section found"))]
     NoRunSection,
+    #[snafu(display(
+        "notify fd {fd} is used by the listening sockets, passed as fds 3 to {last}"
+    ))]
+    NotifyFdInListenFds { fd: u8, last: usize },
 }
 
 impl LongrunBuilder {
@@ -150,13 +170,38 @@ impl LongrunBuilder {
         Self {
             name,
             run_builder: ScriptBuilder::new_for_section("run"),
+            stop_builder: ScriptBuilder::new_for_section("stop"),
             finish_builder: ScriptBuilder::new_for_section("finish"),
+            socket_builder: SocketBuilder::new(),
+            path_builder: PathBuilder::new(),
+            resources_builder: ResourcesBuilder::new(),
+            sandbox_builder: SandboxBuilder::new(),
             options_builder: ServiceOptionsBuilder::new(),
             env_builder: ScriptEnvironmentBuilder::new(),
         }
     }
 }
 
+pub struct VirtualBuilder {
+    name: String,
+    options_builder: VirtualOptionsBuilder,
+}
+
+#[derive(Snafu, Debug)]
+pub enum VirtualBuilderError {
+    #[snafu(display("no options section found"))]
+    NoVirtualOptionsSection,
+}
+
+impl VirtualBuilder {
+    pub fn new(name: String) -> Self {
+        Self {
+            name,
+            options_builder: VirtualOptionsBuilder::new(),
+        }
+    }
+}
+
 impl ServiceBuilder for BundleBuilder {
     fn build(self) -> Result<Service, Box<dyn Error>> {
         Ok(Service::Bundle(Bundle {
@@ -184,6 +229,16 @@ impl ServiceBuilder for OneshotBuilder {
             } else {
                 None
             },
+            timer: if let Some(timer) = self.timer_builder.timer {
+                Some(timer?)
+            } else {
+                None
+            },
+            path: if let Some(path) = self.path_builder.path {
+                Some(path?)
+            } else {
+                None
+            },
             options: self
                 .options_builder
                 .options
@@ -201,6 +256,10 @@ impl ServiceBuilder for OneshotBuilder {
         self.start_builder,
         "stop",
         self.stop_builder,
+        "timer",
+        self.timer_builder,
+        "path",
+        self.path_builder,
         "options",
         self.options_builder,
         "env",
@@ -210,17 +269,42 @@ impl ServiceBuilder for OneshotBuilder {
 
 impl ServiceBuilder for LongrunBuilder {
     fn build(self) -> Result<Service, Box<dyn Error>> {
-        Ok(Service::Longrun(Longrun {
+        let longrun = Longrun {
             name: self.name,
             run: self
                 .run_builder
                 .script
                 .with_context(|| NoRunSectionSnafu)??,
+            stop: if let Some(stop) = self.stop_builder.script {
+                Some(stop?)
+            } else {
+                None
+            },
             finish: if let Some(finish) = self.finish_builder.script {
                 Some(finish?)
             } else {
                 None
             },
+            socket: if let Some(socket) = self.socket_builder.socket {
+                Some(socket?)
+            } else {
+                None
+            },
+            path: if let Some(path) = self.path_builder.path {
+                Some(path?)
+            } else {
+                None
+            },
+            resources: if let Some(resources) = self.resources_builder.resources {
+                Some(resources?)
+            } else {
+                None
+            },
+            sandbox: if let Some(sandbox) = self.sandbox_builder.sandbox {
+                Some(sandbox?)
+            } else {
+                None
+            },
             options: self
                 .options_builder
                 .options
@@ -229,18 +313,52 @@ impl ServiceBuilder for LongrunBuilder {
                 .env_builder
                 .environment
                 .unwrap_or_else(|| Ok(ScriptEnvironment::new()))?,
-        }))
+        };
+        // The listening sockets are passed as fds 3, 4 and so on, the
+        // notify fd would overwrite one of them
+        if let (Some(fd), Some(socket)) = (longrun.run.notify, &longrun.socket) {
+            let last = 2 + socket.listen.len();
+            ensure!(
+                !(3..=last).contains(&(fd as usize)),
+                NotifyFdInListenFdsSnafu { fd, last }
+            );
+        }
+        Ok(Service::Longrun(longrun))
     }
 
     parse_sections!(
         self,
         "run",
         self.run_builder,
+        "stop",
+        self.stop_builder,
         "finish",
         self.finish_builder,
+        "socket",
+        self.socket_builder,
+        "path",
+        self.path_builder,
+        "resources",
+        self.resources_builder,
+        "sandbox",
+        self.sandbox_builder,
         "options",
         self.options_builder,
         "env",
         self.env_builder
     );
 }
+
+impl ServiceBuilder for VirtualBuilder {
+    fn build(self) -> Result<Service, Box<dyn Error>> {
+        Ok(Service::Virtual(Virtual {
+            name: self.name,
+            options: self
+                .options_builder
+                .virtual_options
+                .with_context(|| NoVirtualOptionsSectionSnafu)??,
+        }))
+    }
+
+    parse_sections!(self, "options", self.options_builder);
+}
@@ -1,9 +1,11 @@
 use std::{
+    collections::HashMap,
     env,
     path::{
         Path,
         PathBuf,
     },
+    str::FromStr,
 };
 
 use figment::{
@@ -23,9 +25,15 @@ use snafu::{
     Snafu,
 };
 
-use crate::dirs::{
-    Dirs,
-    DirsError,
+use crate::{
+    dirs::{
+        Dirs,
+        DirsError,
+    },
+    types::{
+        RunLevel,
+        RunLevelParseError,
+    },
 };
 
 const CONF_FILENAME: &str = "rinit.conf";
@@ -34,6 +42,14 @@ const CONF_FILENAME: &str = "rinit.conf";
 pub struct Config {
     #[serde(flatten)]
     pub dirs: Dirs,
+    /// The provider to select for each virtual service, when more than one is
+    /// enabled
+    #[serde(default)]
+    pub providers: HashMap<String, String>,
+    /// The runlevels in the order they are started, boot is always started
+    /// first. At startup rinit starts every runlevel up to default
+    #[serde(default)]
+    pub runlevels: Vec<String>,
 }
 
 #[derive(Debug, Snafu)]
@@ -42,6 +58,8 @@ pub enum ConfigError {
     DirectoriesError { source: DirsError },
     #[snafu(display("unable to find configuration file {:?}", config_file))]
     DirsFileNotFound { config_file: PathBuf },
+    #[snafu(display("{source}"))]
+    InvalidRunLevel { source: RunLevelParseError },
 }
 
 type Result<T, E = ConfigError> = std::result::Result<T, E>;
@@ -83,6 +101,24 @@ impl Config {
         // Read the configuration variables from the env
         conf = conf.merge(providers::Env::prefixed("RINIT_"));
 
-        Ok(conf.extract().unwrap())
+        let config: Config = conf.extract().unwrap();
+        config
+            .runlevels
+            .iter()
+            .try_for_each(|runlevel| RunLevel::from_str(runlevel).map(|_| ()))
+            .context(InvalidRunLevelSnafu {})?;
+
+        Ok(config)
+    }
+
+    /// Get all the runlevels in the order they are started
+    pub fn runlevels(&self) -> Vec<RunLevel> {
+        RunLevel::order(
+            &self
+                .runlevels
+                .iter()
+                .filter_map(|runlevel| RunLevel::from_str(runlevel).ok())
+                .collect::<Vec<RunLevel>>(),
+        )
     }
 }
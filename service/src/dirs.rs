@@ -1,6 +1,10 @@
 use std::{
     env,
-    path::PathBuf,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 
 use serde::{
@@ -13,6 +17,8 @@ use snafu::{
 };
 use xdg::BaseDirectories;
 
+const CGROUP2_MOUNT: &str = "/sys/fs/cgroup";
+
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct Dirs {
     #[serde(default)]
@@ -25,6 +31,13 @@ pub struct Dirs {
     pub datadir: PathBuf,
     #[serde(default)]
     pub logdir: PathBuf,
+    /// Where the state that must survive a reboot is stored
+    #[serde(default)]
+    pub libdir: PathBuf,
+    /// The cgroup v2 subtree delegated to rinit, every service gets its own
+    /// cgroup below it
+    #[serde(default)]
+    pub cgroupdir: PathBuf,
 }
 
 #[derive(Debug, Snafu)]
@@ -61,9 +74,22 @@ impl Dirs {
                 .join("rinit"),
             datadir: xdg.get_data_home(),
             logdir: xdg.get_state_home(),
+            libdir: xdg.get_state_home(),
+            cgroupdir: Self::own_cgroup()
+                .map(|cgroup| cgroup.join("rinit.slice"))
+                .unwrap_or(system_config.cgroupdir),
         })
     }
 
+    // The cgroup v2 of the current process, usually delegated to the user
+    fn own_cgroup() -> Option<PathBuf> {
+        fs::read_to_string("/proc/self/cgroup")
+            .ok()?
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .map(|cgroup| Path::new(CGROUP2_MOUNT).join(cgroup.trim_start_matches('/')))
+    }
+
     pub fn service_directories(&self) -> Vec<PathBuf> {
         let uid = unsafe { libc::getuid() };
         let service_type = if uid == 0 { "system" } else { "user" };
@@ -80,4 +106,12 @@ impl Dirs {
     pub fn graph_filename(&self) -> PathBuf {
         self.datadir.join("graph.data")
     }
+
+    /// The file storing when the timer of the service has last run
+    pub fn timer_filename(
+        &self,
+        service: &str,
+    ) -> PathBuf {
+        self.libdir.join("timers").join(service)
+    }
 }
@@ -16,29 +16,75 @@ use snafu::{
 
 use crate::{
     graph::Node,
-    types::Service,
+    types::{
+        Provider,
+        RunLevel,
+        Service,
+        Virtual,
+    },
 };
 
 #[derive(Debug, Snafu, PartialEq, Eq)]
 pub enum DependencyGraphError {
     #[snafu(display("found a cycle in the dependency graph"))]
     CycleFoundError,
-    #[snafu(display(
-        "service {service} does not have the same runlevel as its dependency {dependency}"
-    ))]
-    DependenciesMustHaveSameRunLevel { service: String, dependency: String },
+    #[snafu(display("service {service} depends on {dependency}, which is in a later runlevel"))]
+    DependencyInLaterRunLevel { service: String, dependency: String },
     #[snafu(display("the dependency {} of service {} is missing", dependency, service))]
     DependenciesUnfulfilledError { service: String, dependency: String },
+    #[snafu(display("{provider} is not a provider of the virtual service {service}"))]
+    InvalidProvider { service: String, provider: String },
+    #[snafu(display(
+        "multiple providers {providers:?} are enabled for the virtual service {service}, select \
+         one of them in the configuration"
+    ))]
+    MultipleProvidersEnabled {
+        service: String,
+        providers: Vec<String>,
+    },
+    #[snafu(display(
+        "none of the providers {providers:?} of the virtual service {service} is enabled"
+    ))]
+    NoProviderEnabled {
+        service: String,
+        providers: Vec<String>,
+    },
+    #[snafu(display(
+        "service {service} requires one of {alternatives:?}, but none of them is enabled"
+    ))]
+    NoRequiredServiceEnabled {
+        service: String,
+        alternatives: Vec<String>,
+    },
+    #[snafu(display("service {service} requires {required}, which is not enabled"))]
+    RequiredServiceNotEnabled { service: String, required: String },
     #[snafu(display("service {} is not enabled", service))]
     ServiceNotEnabled { service: String },
+    #[snafu(display("service {service} is required by {required_by}"))]
+    ServiceRequired {
+        service: String,
+        required_by: String,
+    },
     #[snafu(display("service {service} is already enabled"))]
     ServiceAlreadyEnabled { service: String },
+    #[snafu(display(
+        "runlevel {runlevel} of service {service} is not declared in the configuration"
+    ))]
+    UnknownRunLevel { service: String, runlevel: String },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DependencyGraph {
     pub enabled_services: HashSet<usize>,
     pub nodes: IndexMap<String, Node>,
+    /// The provider to select for a virtual service when more than one is
+    /// enabled. It comes from the configuration, so it is never serialized
+    #[serde(skip)]
+    pub preferred_providers: HashMap<String, String>,
+    /// The runlevels in the order they are started. A service can only depend
+    /// on services in the same or in an earlier runlevel
+    #[serde(skip, default = "DependencyGraph::default_runlevels")]
+    pub runlevels: Vec<RunLevel>,
 }
 
 enum Color {
@@ -52,8 +98,14 @@ impl DependencyGraph {
         DependencyGraph {
             enabled_services: HashSet::new(),
             nodes: IndexMap::new(),
+            preferred_providers: HashMap::new(),
+            runlevels: Self::default_runlevels(),
         }
     }
+
+    fn default_runlevels() -> Vec<RunLevel> {
+        RunLevel::order(&[])
+    }
 }
 
 impl Default for DependencyGraph {
@@ -71,6 +123,19 @@ impl DependencyGraph {
         &mut self,
         services_to_enable: Vec<String>,
         services: Vec<Service>,
+    ) -> Result<()> {
+        // The changes are validated on a copy, the graph is left untouched when
+        // any check fails
+        let mut graph = self.clone();
+        graph.add_services_unchecked(services_to_enable, services)?;
+        *self = graph;
+        Ok(())
+    }
+
+    fn add_services_unchecked(
+        &mut self,
+        services_to_enable: Vec<String>,
+        services: Vec<Service>,
     ) -> Result<()> {
         services_to_enable.iter().try_for_each(|service| {
             if let Some(index) = &self.nodes.get_index_of(service) {
@@ -98,27 +163,23 @@ impl DependencyGraph {
             index
         };
 
+        // Update enabled services set, virtual services can only select an
+        // enabled provider
+        let services_to_enable_index: Vec<usize> = services_to_enable
+            .iter()
+            .map(|service| self.nodes.get_index_of(service).unwrap())
+            .collect();
+        self.enabled_services
+            .extend(services_to_enable_index.iter().copied());
+        self.select_providers()?;
+
         self.check_dependencies(starting_index)?;
 
-        // Update enabled services set and populate dependents
-        services_to_enable.iter().for_each(|service| {
-            let index = self.nodes.get_index_of(service).unwrap();
-            self.enabled_services.insert(index);
-            let dependencies = self.nodes[index].service.dependencies().to_owned();
-            for dep in dependencies {
-                self.nodes
-                    .get_mut(&dep)
-                    .unwrap()
-                    .add_dependent(service.to_string());
-            }
-        });
+        // Populate dependents of the new and enabled nodes
+        self.populate_dependents(&(index..self.nodes.len()).collect::<Vec<usize>>());
+        self.populate_dependents(&services_to_enable_index);
 
-        self.check_cycles(
-            services_to_enable
-                .iter()
-                .map(|name| self.nodes.get_index_of(name).unwrap())
-                .collect(),
-        )?;
+        self.check_cycles(services_to_enable_index)?;
 
         Ok(())
     }
@@ -155,7 +216,7 @@ impl DependencyGraph {
 
                 let name = name.clone();
                 // Remove all instances of this service from Node::dependents
-                let dependencies = existing_service.dependencies().to_owned();
+                let dependencies = node.dependencies().to_owned();
                 for dep in dependencies {
                     self.nodes.get_mut(&dep).unwrap().remove_dependent(&name);
                 }
@@ -174,14 +235,103 @@ impl DependencyGraph {
         services_index.iter().for_each(|index| {
             let (name, node) = self.nodes.get_index(*index).unwrap();
             let name = name.clone();
-            node.service
-                .dependencies()
+            node.dependencies()
                 .to_owned()
                 .iter()
                 .for_each(|dep| self.nodes.get_mut(dep).unwrap().add_dependent(name.clone()));
         });
     }
 
+    // Select a provider for each virtual service in the graph, choosing between
+    // the enabled ones
+    fn select_providers(&mut self) -> Result<()> {
+        let selected_providers = self
+            .nodes
+            .values()
+            .filter_map(|node| {
+                match &node.service {
+                    Service::Virtual(virtual_service) => Some((virtual_service, &node.provider)),
+                    _ => None,
+                }
+            })
+            .map(|(virtual_service, current)| -> Result<(String, String)> {
+                Ok((
+                    virtual_service.name.clone(),
+                    self.select_provider(virtual_service, current.as_deref())?,
+                ))
+            })
+            .collect::<Result<Vec<(String, String)>>>()?;
+
+        for (name, provider) in selected_providers {
+            let node = self.nodes.get_mut(&name).unwrap();
+            if node.provider.as_ref() == Some(&provider) {
+                continue;
+            }
+            if let Some(previous) = node.provider.replace(provider.clone()) {
+                if let Some(previous) = self.nodes.get_mut(&previous) {
+                    previous.remove_dependent(&name);
+                }
+            }
+            self.nodes.get_mut(&provider).unwrap().add_dependent(name);
+        }
+
+        Ok(())
+    }
+
+    // The provider in the configuration always has the precedence. Then keep the
+    // current one, so that enabling another provider doesn't change it.
+    // Otherwise there must be exactly one provider enabled.
+    fn select_provider(
+        &self,
+        virtual_service: &Virtual,
+        current: Option<&str>,
+    ) -> Result<String> {
+        let name = &virtual_service.name;
+        let providers = &virtual_service.options.providers;
+        let is_enabled = |provider: &str| self.is_enabled(provider);
+
+        if let Some(preferred) = self.preferred_providers.get(name) {
+            ensure!(
+                providers.contains(preferred),
+                InvalidProviderSnafu {
+                    service: name,
+                    provider: preferred,
+                }
+            );
+            if is_enabled(preferred) {
+                return Ok(preferred.to_owned());
+            }
+        }
+
+        if let Some(current) = current.filter(|current| is_enabled(current)) {
+            return Ok(current.to_owned());
+        }
+
+        match Provider::from(
+            providers
+                .iter()
+                .filter(|provider| is_enabled(provider))
+                .cloned()
+                .collect::<Vec<String>>(),
+        ) {
+            Provider::Empty => {
+                NoProviderEnabledSnafu {
+                    service: name,
+                    providers: providers.to_owned(),
+                }
+                .fail()
+            }
+            Provider::Single(provider) => Ok(provider),
+            Provider::Multiple(providers) => {
+                MultipleProvidersEnabledSnafu {
+                    service: name,
+                    providers,
+                }
+                .fail()
+            }
+        }
+    }
+
     fn check_dependencies(
         &self,
         from: usize,
@@ -190,9 +340,8 @@ impl DependencyGraph {
             .values()
             .skip(from)
             .try_for_each(|node| -> Result<()> {
-                let runlevel = node.service.runlevel();
-                node.service
-                    .dependencies()
+                let runlevel = self.runlevel_position(node)?;
+                node.dependencies()
                     .iter()
                     .try_for_each(|dep| -> Result<()> {
                         ensure!(
@@ -203,18 +352,68 @@ impl DependencyGraph {
                             }
                         );
                         ensure!(
-                            self.nodes.get(dep).unwrap().service.runlevel() == runlevel,
-                            DependenciesMustHaveSameRunLevelSnafu {
+                            self.runlevel_position(self.nodes.get(dep).unwrap())? <= runlevel,
+                            DependencyInLaterRunLevelSnafu {
                                 service: node.name(),
                                 dependency: dep
                             }
                         );
                         Ok(())
-                    })
+                    })?;
+                self.check_requirements(node)
             })?;
         Ok(())
     }
 
+    fn runlevel_position(
+        &self,
+        node: &Node,
+    ) -> Result<usize> {
+        let runlevel = node.service.runlevel();
+        self.runlevels
+            .iter()
+            .position(|r| r == runlevel)
+            .with_context(|| {
+                UnknownRunLevelSnafu {
+                    service: node.name(),
+                    runlevel: runlevel.to_string(),
+                }
+            })
+    }
+
+    // Services in requires must be enabled, while at least one of the services in
+    // requires_one must be enabled
+    fn check_requirements(
+        &self,
+        node: &Node,
+    ) -> Result<()> {
+        node.service
+            .requires()
+            .iter()
+            .try_for_each(|required| -> Result<()> {
+                ensure!(
+                    self.is_enabled(required),
+                    RequiredServiceNotEnabledSnafu {
+                        service: node.name(),
+                        required,
+                    }
+                );
+                Ok(())
+            })?;
+        let alternatives = node.service.requires_one();
+        ensure!(
+            alternatives.is_empty()
+                || alternatives
+                    .iter()
+                    .any(|alternative| self.is_enabled(alternative)),
+            NoRequiredServiceEnabledSnafu {
+                service: node.name(),
+                alternatives: alternatives.to_owned(),
+            }
+        );
+        Ok(())
+    }
+
     fn check_cycles(
         &self,
         services_to_enable: Vec<usize>,
@@ -224,10 +423,19 @@ impl DependencyGraph {
             .iter()
             .map(|(name, _node)| (self.nodes.get_index_of(name).unwrap(), Color::White))
             .collect();
+        // A service X with Y in before is the same as Y having X in after
+        let mut ordered_before: HashMap<&str, Vec<usize>> = HashMap::new();
+        self.nodes.values().enumerate().for_each(|(index, node)| {
+            node.service.before().iter().for_each(|service| {
+                ordered_before.entry(service).or_default().push(index);
+            })
+        });
 
         services_to_enable
             .iter()
-            .try_for_each(|node| -> Result<()> { self.visit(&mut colors, *node) })?;
+            .try_for_each(|node| -> Result<()> {
+                self.visit(&mut colors, &ordered_before, *node)
+            })?;
 
         Ok(())
     }
@@ -235,21 +443,31 @@ impl DependencyGraph {
     fn visit(
         &self,
         colors: &mut HashMap<usize, Color>,
+        ordered_before: &HashMap<&str, Vec<usize>>,
         node: usize,
     ) -> Result<()> {
         colors.insert(node, Color::Gray);
 
-        self.nodes
-            .get_index(node)
-            .unwrap()
-            .1
-            .service
+        let current = &self.nodes[node];
+        // Requirements and ordering relations are started before the service too,
+        // so they must not create a cycle either
+        current
             .dependencies()
             .iter()
-            .map(|dep| self.nodes.get_index_of(dep).unwrap())
+            .chain(current.service.requires())
+            .chain(current.service.requires_one())
+            .chain(current.service.after())
+            .filter_map(|dep| self.nodes.get_index_of(dep))
+            .chain(
+                ordered_before
+                    .get(current.name())
+                    .into_iter()
+                    .flatten()
+                    .copied(),
+            )
             .try_for_each(|dep| -> Result<()> {
                 match colors.get(&dep).unwrap() {
-                    Color::White => self.visit(colors, dep),
+                    Color::White => self.visit(colors, ordered_before, dep),
                     Color::Gray => Err(DependencyGraphError::CycleFoundError {}),
                     Color::Black => Ok(()),
                 }
@@ -262,17 +480,68 @@ impl DependencyGraph {
     pub fn disable_services(
         &mut self,
         services: Vec<String>,
+    ) -> Result<()> {
+        let mut graph = self.clone();
+        graph.disable_services_unchecked(services)?;
+        *self = graph;
+        Ok(())
+    }
+
+    fn disable_services_unchecked(
+        &mut self,
+        services: Vec<String>,
     ) -> Result<()> {
         services.iter().try_for_each(|service| -> Result<()> {
             let node_index = self
                 .nodes
                 .get_index_of(service)
                 .context(ServiceNotEnabledSnafu { service })?;
+            self.check_not_required(service)?;
             self.enabled_services.remove(&node_index);
             if !self.is_node_required(node_index) {
                 self.remove_node(node_index);
             }
 
+            // If the service was the provider of a virtual service, select another
+            // one and remove it when nothing else requires it
+            self.select_providers()?;
+            if let Some(node_index) = self
+                .nodes
+                .get_index_of(service)
+                .filter(|index| !self.is_node_required(*index))
+            {
+                self.remove_node(node_index);
+            }
+
+            Ok(())
+        })
+    }
+
+    // Check that the service can be disabled without leaving another service
+    // without its requirements
+    fn check_not_required(
+        &self,
+        service: &str,
+    ) -> Result<()> {
+        self.nodes.values().try_for_each(|node| -> Result<()> {
+            let alternatives = node.service.requires_one();
+            ensure!(
+                !node
+                    .service
+                    .requires()
+                    .iter()
+                    .any(|required| required == service)
+                    && (!alternatives
+                        .iter()
+                        .any(|alternative| alternative == service)
+                        || alternatives.iter().any(|alternative| {
+                            alternative != service && self.is_enabled(alternative)
+                        })),
+                ServiceRequiredSnafu {
+                    service,
+                    required_by: node.name(),
+                }
+            );
             Ok(())
         })
     }
@@ -282,13 +551,7 @@ impl DependencyGraph {
         index: usize,
     ) {
         let name = self.nodes[index].name().to_owned();
-        // This node has already been removed from the graph
-        if !self.has_service(&name) {
-            return;
-        }
-
         self.nodes[index]
-            .service
             .dependencies()
             .to_owned()
             .iter()
@@ -300,8 +563,15 @@ impl DependencyGraph {
                 }
             });
 
+        // Removing the dependencies might have moved this node
+        let index = self.nodes.get_index_of(&name).unwrap();
         // Remove the node by swapping, let IndexMap handle it
         self.nodes.swap_remove_index(index);
+        // The last node has taken the place of the removed one, update its index
+        let last = self.nodes.len();
+        if index != last && self.enabled_services.remove(&last) {
+            self.enabled_services.insert(index);
+        }
     }
 
     fn is_node_required(
@@ -311,6 +581,15 @@ impl DependencyGraph {
         self.enabled_services.contains(&index) || self.nodes[index].has_dependents()
     }
 
+    fn is_enabled(
+        &self,
+        name: &str,
+    ) -> bool {
+        self.nodes
+            .get_index_of(name)
+            .is_some_and(|index| self.enabled_services.contains(&index))
+    }
+
     #[inline]
     fn has_service(
         &self,
@@ -333,11 +612,37 @@ mod test {
             name: name.to_string(),
             start: Script::new(ScriptPrefix::Bash, "exit 0".to_string()),
             stop: None,
+            timer: None,
+            path: None,
             options,
             environment: ScriptEnvironment::new(),
         })
     }
 
+    fn create_virtual_service(
+        name: &str,
+        providers: &[&str],
+    ) -> Service {
+        Service::Virtual(Virtual {
+            name: name.to_string(),
+            options: VirtualOptions {
+                providers: providers.iter().map(|p| p.to_string()).collect(),
+                runlevel: RunLevel::Default,
+            },
+        })
+    }
+
+    fn create_service_depending_on(
+        name: &str,
+        dependency: &str,
+    ) -> Service {
+        create_new_service(name, {
+            let mut options = ServiceOptions::new();
+            options.dependencies = vec![dependency.to_string()];
+            options
+        })
+    }
+
     #[test]
     fn add_services_to_empty_graph() {
         let mut graph = DependencyGraph::new();
@@ -419,6 +724,27 @@ mod test {
         assert_eq!(graph.nodes.len(), 2);
     }
 
+    #[test]
+    fn add_template_instances() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["getty@tty1".to_string(), "getty@tty2".to_string()],
+                vec![
+                    create_new_service("getty@tty1", ServiceOptions::new()),
+                    create_new_service("getty@tty2", ServiceOptions::new()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+
+        graph
+            .disable_services(vec!["getty@tty1".to_string()])
+            .unwrap();
+        assert!(graph.nodes.contains_key("getty@tty2"));
+    }
+
     #[test]
     fn add_service_with_unfulfilled_dependency() {
         let mut graph = DependencyGraph::new();
@@ -463,6 +789,52 @@ mod test {
 
         assert!(res.is_err());
         assert_eq!(res, Err(DependencyGraphError::CycleFoundError));
+        // Nothing has been added to the graph
+        assert!(graph.nodes.is_empty());
+        assert!(graph.enabled_services.is_empty());
+    }
+
+    #[test]
+    fn add_service_ordered_after_missing_service() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["foo".to_string()],
+                vec![create_new_service("foo", {
+                    let mut options = ServiceOptions::new();
+                    options.after = vec!["bar".to_string()];
+                    options
+                })],
+            )
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 1);
+    }
+
+    #[test]
+    fn add_services_with_ordering_cycle() {
+        let mut graph = DependencyGraph::new();
+
+        // foo -> bar -> foobar -> foo
+        let res = graph.add_services(
+            vec!["foo".to_string(), "foobar".to_string()],
+            vec![
+                create_new_service("foo", {
+                    let mut options = ServiceOptions::new();
+                    options.dependencies = vec!["bar".to_string()];
+                    options.before = vec!["foobar".to_string()];
+                    options
+                }),
+                create_new_service("bar", {
+                    let mut options = ServiceOptions::new();
+                    options.after = vec!["foobar".to_string()];
+                    options
+                }),
+                create_new_service("foobar", ServiceOptions::new()),
+            ],
+        );
+
+        assert_eq!(res, Err(DependencyGraphError::CycleFoundError));
     }
 
     #[test]
@@ -514,21 +886,330 @@ mod test {
                         create_new_service("foo", {
                             let mut options = ServiceOptions::new();
                             options.dependencies = vec!["bar".to_string()];
-                            options
-                        }),
-                        create_new_service("bar", {
-                            use crate::types::RunLevel;
-                            let mut options = ServiceOptions::new();
                             options.runlevel = RunLevel::Boot;
                             options
                         }),
+                        create_new_service("bar", ServiceOptions::new()),
                     ],
                 )
                 .unwrap_err(),
-            DependencyGraphError::DependenciesMustHaveSameRunLevel {
+            DependencyGraphError::DependencyInLaterRunLevel {
                 service: "foo".to_string(),
                 dependency: "bar".to_string()
             }
         );
     }
+
+    #[test]
+    fn service_depending_on_earlier_runlevel() {
+        let mut graph = DependencyGraph::new();
+        graph.runlevels = RunLevel::order(&[RunLevel::Named("rescue".to_string())]);
+
+        graph
+            .add_services(
+                vec!["foo".to_string()],
+                vec![
+                    create_new_service("foo", {
+                        let mut options = ServiceOptions::new();
+                        options.dependencies = vec!["bar".to_string()];
+                        options
+                    }),
+                    create_new_service("bar", {
+                        let mut options = ServiceOptions::new();
+                        options.runlevel = RunLevel::Named("rescue".to_string());
+                        options
+                    }),
+                ],
+            )
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+    }
+
+    #[test]
+    fn service_with_unknown_runlevel() {
+        let mut graph = DependencyGraph::new();
+
+        assert_eq!(
+            graph
+                .add_services(
+                    vec!["foo".to_string()],
+                    vec![create_new_service("foo", {
+                        let mut options = ServiceOptions::new();
+                        options.runlevel = RunLevel::Named("rescue".to_string());
+                        options
+                    })],
+                )
+                .unwrap_err(),
+            DependencyGraphError::UnknownRunLevel {
+                service: "foo".to_string(),
+                runlevel: "rescue".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn virtual_service_selects_enabled_provider() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["syslog-ng".to_string()],
+                vec![create_new_service("syslog-ng", ServiceOptions::new())],
+            )
+            .unwrap();
+        graph
+            .add_services(
+                vec!["foo".to_string()],
+                vec![
+                    create_service_depending_on("foo", "syslog"),
+                    create_virtual_service("syslog", &["rsyslog", "syslog-ng"]),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(
+            graph.nodes["syslog"].provider,
+            Some("syslog-ng".to_string())
+        );
+        assert!(graph.nodes["syslog-ng"].dependents.contains("syslog"));
+    }
+
+    #[test]
+    fn virtual_service_without_enabled_provider() {
+        let mut graph = DependencyGraph::new();
+
+        assert_eq!(
+            graph
+                .add_services(
+                    vec!["foo".to_string()],
+                    vec![
+                        create_service_depending_on("foo", "syslog"),
+                        create_virtual_service("syslog", &["rsyslog", "syslog-ng"]),
+                    ],
+                )
+                .unwrap_err(),
+            DependencyGraphError::NoProviderEnabled {
+                service: "syslog".to_string(),
+                providers: vec!["rsyslog".to_string(), "syslog-ng".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn virtual_service_with_multiple_enabled_providers() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["rsyslog".to_string(), "syslog-ng".to_string()],
+                vec![
+                    create_new_service("rsyslog", ServiceOptions::new()),
+                    create_new_service("syslog-ng", ServiceOptions::new()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            graph
+                .add_services(
+                    vec!["foo".to_string()],
+                    vec![
+                        create_service_depending_on("foo", "syslog"),
+                        create_virtual_service("syslog", &["rsyslog", "syslog-ng"]),
+                    ],
+                )
+                .unwrap_err(),
+            DependencyGraphError::MultipleProvidersEnabled {
+                service: "syslog".to_string(),
+                providers: vec!["rsyslog".to_string(), "syslog-ng".to_string()]
+            }
+        );
+    }
+
+    #[test]
+    fn virtual_service_with_preferred_provider() {
+        let mut graph = DependencyGraph::new();
+        graph
+            .preferred_providers
+            .insert("syslog".to_string(), "rsyslog".to_string());
+
+        graph
+            .add_services(
+                vec!["rsyslog".to_string(), "syslog-ng".to_string()],
+                vec![
+                    create_new_service("rsyslog", ServiceOptions::new()),
+                    create_new_service("syslog-ng", ServiceOptions::new()),
+                ],
+            )
+            .unwrap();
+        graph
+            .add_services(
+                vec!["foo".to_string()],
+                vec![
+                    create_service_depending_on("foo", "syslog"),
+                    create_virtual_service("syslog", &["rsyslog", "syslog-ng"]),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(graph.nodes["syslog"].provider, Some("rsyslog".to_string()));
+    }
+
+    #[test]
+    fn disable_selected_provider() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["foo".to_string(), "syslog-ng".to_string()],
+                vec![
+                    create_service_depending_on("foo", "syslog"),
+                    create_virtual_service("syslog", &["rsyslog", "syslog-ng"]),
+                    create_new_service("syslog-ng", ServiceOptions::new()),
+                ],
+            )
+            .unwrap();
+        graph
+            .add_services(
+                vec!["rsyslog".to_string()],
+                vec![create_new_service("rsyslog", ServiceOptions::new())],
+            )
+            .unwrap();
+        assert_eq!(
+            graph.nodes["syslog"].provider,
+            Some("syslog-ng".to_string())
+        );
+
+        graph
+            .disable_services(vec!["syslog-ng".to_string()])
+            .unwrap();
+        assert_eq!(graph.nodes["syslog"].provider, Some("rsyslog".to_string()));
+        assert!(!graph.nodes.contains_key("syslog-ng"));
+    }
+
+    #[test]
+    fn disable_last_enabled_provider() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["foo".to_string(), "syslog-ng".to_string()],
+                vec![
+                    create_service_depending_on("foo", "syslog"),
+                    create_virtual_service("syslog", &["rsyslog", "syslog-ng"]),
+                    create_new_service("syslog-ng", ServiceOptions::new()),
+                ],
+            )
+            .unwrap();
+
+        assert!(matches!(
+            graph.disable_services(vec!["syslog-ng".to_string()]),
+            Err(DependencyGraphError::NoProviderEnabled { .. })
+        ));
+        // The provider is still enabled and selected
+        assert!(graph.is_enabled("syslog-ng"));
+        assert_eq!(
+            graph.nodes["syslog"].provider,
+            Some("syslog-ng".to_string())
+        );
+    }
+
+    #[test]
+    fn add_service_requiring_disabled_service() {
+        let mut graph = DependencyGraph::new();
+
+        let res = graph.add_services(
+            vec!["foo".to_string()],
+            vec![
+                create_new_service("foo", {
+                    let mut options = ServiceOptions::new();
+                    options.requires = vec!["bar".to_string()];
+                    options
+                }),
+                create_new_service("bar", ServiceOptions::new()),
+            ],
+        );
+        assert_eq!(
+            res,
+            Err(DependencyGraphError::RequiredServiceNotEnabled {
+                service: "foo".to_string(),
+                required: "bar".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn add_service_requiring_enabled_service() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["foo".to_string(), "bar".to_string()],
+                vec![
+                    create_new_service("foo", {
+                        let mut options = ServiceOptions::new();
+                        options.requires = vec!["bar".to_string()];
+                        options
+                    }),
+                    create_new_service("bar", ServiceOptions::new()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+    }
+
+    #[test]
+    fn add_service_requiring_one_service() {
+        let mut graph = DependencyGraph::new();
+        let foo = || {
+            create_new_service("foo", {
+                let mut options = ServiceOptions::new();
+                options.requires_one = vec!["bar".to_string(), "foobar".to_string()];
+                options
+            })
+        };
+
+        assert!(matches!(
+            graph.add_services(vec!["foo".to_string()], vec![foo()]),
+            Err(DependencyGraphError::NoRequiredServiceEnabled { .. })
+        ));
+
+        let mut graph = DependencyGraph::new();
+        graph
+            .add_services(
+                vec!["foo".to_string(), "foobar".to_string()],
+                vec![foo(), create_new_service("foobar", ServiceOptions::new())],
+            )
+            .unwrap();
+        assert_eq!(graph.nodes.len(), 2);
+    }
+
+    #[test]
+    fn disable_required_service() {
+        let mut graph = DependencyGraph::new();
+
+        graph
+            .add_services(
+                vec!["foo".to_string(), "bar".to_string()],
+                vec![
+                    create_new_service("foo", {
+                        let mut options = ServiceOptions::new();
+                        options.requires_one = vec!["bar".to_string()];
+                        options
+                    }),
+                    create_new_service("bar", ServiceOptions::new()),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(
+            graph.disable_services(vec!["bar".to_string()]),
+            Err(DependencyGraphError::ServiceRequired {
+                service: "bar".to_string(),
+                required_by: "foo".to_string()
+            })
+        );
+        graph.disable_services(vec!["foo".to_string()]).unwrap();
+        graph.disable_services(vec!["bar".to_string()]).unwrap();
+        assert_eq!(graph.nodes.len(), 0);
+    }
 }
@@ -1,6 +1,6 @@
-use std::collections::{
-    HashMap,
-    HashSet,
+use std::{
+    collections::HashSet,
+    slice,
 };
 
 use serde::{
@@ -8,19 +8,18 @@ use serde::{
     Serialize,
 };
 
-use crate::types::{
-    Provider,
-    Service,
-};
+use crate::types::Service;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Node {
     #[serde(flatten)]
     pub service: Service,
     #[serde(default, skip_serializing_if = "HashSet::is_empty")]
     pub dependents: HashSet<String>,
-    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
-    providers: HashMap<String, Provider>,
+    /// The concrete service selected by the dependency graph when this node is
+    /// a virtual service
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
 }
 
 impl Node {
@@ -28,7 +27,7 @@ impl Node {
         Node {
             service,
             dependents: HashSet::new(),
-            providers: HashMap::new(),
+            provider: None,
         }
     }
 
@@ -36,6 +35,19 @@ impl Node {
         self.service.name()
     }
 
+    /// Get the dependencies of the service. A virtual service depends on its
+    /// selected provider
+    pub fn dependencies(&self) -> &[String] {
+        match &self.service {
+            Service::Virtual(_) => {
+                self.provider
+                    .as_ref()
+                    .map_or(&[], |provider| slice::from_ref(provider))
+            }
+            _ => self.service.dependencies(),
+        }
+    }
+
     pub fn add_dependent(
         &mut self,
         dependent: String,
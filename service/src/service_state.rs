@@ -21,6 +21,21 @@ pub enum ServiceState {
 pub enum IdleServiceState {
     Up,
     Down,
+    /// Only some of the members of a bundle are up. This state is derived from
+    /// the members and is never sent by the supervisor
+    PartiallyUp,
+    /// The conditions of the service are false, so it has not been started
+    Skipped,
+    /// The service has died too many times in a short period of time. It won't
+    /// be started again until it is reset
+    Failed,
+}
+
+impl IdleServiceState {
+    /// Whether the dependents of a service in this state can be started
+    pub fn is_satisfied(&self) -> bool {
+        matches!(self, IdleServiceState::Up | IdleServiceState::Skipped)
+    }
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
@@ -29,6 +44,36 @@ pub enum TransitioningServiceState {
     Stopping,
 }
 
+/// The state that a service reported through its notify socket
+#[derive(PartialEq, Eq, Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NotifyState {
+    /// Free-form status sent with STATUS=
+    pub status: Option<String>,
+    /// The main process of the service, sent with MAINPID=
+    pub main_pid: Option<i32>,
+    /// The service is reloading its configuration, set by RELOADING=1 until the
+    /// next READY=1
+    pub reloading: bool,
+    /// The service is shutting down, set by STOPPING=1
+    pub stopping: bool,
+}
+
+/// The result of the liveness probe of a running service
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum Health {
+    Healthy,
+    /// The probe has failed this many times in a row
+    Failing(u32),
+}
+
+/// When the timer of a oneshot has run and will run next, in seconds since the
+/// epoch
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct TimerState {
+    pub last: Option<i64>,
+    pub next: Option<i64>,
+}
+
 impl fmt::Display for ServiceState {
     fn fmt(
         &self,
@@ -52,6 +97,9 @@ impl fmt::Display for IdleServiceState {
             match self {
                 IdleServiceState::Up => "up",
                 IdleServiceState::Down => "down",
+                IdleServiceState::PartiallyUp => "partially up",
+                IdleServiceState::Skipped => "skipped",
+                IdleServiceState::Failed => "failed",
             }
         )
     }
@@ -73,5 +121,17 @@ impl fmt::Display for TransitioningServiceState {
     }
 }
 
+impl fmt::Display for Health {
+    fn fmt(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+    ) -> fmt::Result {
+        match self {
+            Health::Healthy => write!(f, "healthy"),
+            Health::Failing(failures) => write!(f, "liveness probe failed {failures} times"),
+        }
+    }
+}
+
 unsafe impl Send for ServiceState {}
 unsafe impl Sync for ServiceState {}
@@ -1,25 +1,49 @@
 mod bundle;
 mod bundle_options;
+mod calendar_event;
+mod condition;
+mod exec_options;
+mod kill_mode;
 mod longrun;
 mod oneshot;
+mod path_triggers;
+mod probe;
 mod provider;
+mod resources;
+mod restart_policy;
 mod runlevel;
+mod sandbox;
 mod script;
 mod script_environment;
 mod service;
 mod service_options;
+mod socket;
+mod timer;
+mod virtual_options;
 mod virtual_service;
 
 pub use self::{
     bundle::*,
     bundle_options::*,
+    calendar_event::*,
+    condition::*,
+    exec_options::*,
+    kill_mode::*,
     longrun::*,
     oneshot::*,
+    path_triggers::*,
+    probe::*,
     provider::*,
+    resources::*,
+    restart_policy::*,
     runlevel::*,
+    sandbox::*,
     script::*,
     script_environment::*,
     service::*,
     service_options::*,
+    socket::*,
+    timer::*,
+    virtual_options::*,
     virtual_service::*,
 };
@@ -0,0 +1,392 @@
+use std::{
+    mem::MaybeUninit,
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use snafu::{
+    ensure,
+    OptionExt,
+    Snafu,
+};
+
+const WEEKDAYS: [&str; 7] = ["mon", "tue", "wed", "thu", "fri", "sat", "sun"];
+const YEARS: RangeInclusive<u32> = 1970..=2199;
+
+/// A recurring point in local time, e.g. "daily" or "Mon *-*-* 03:00". Every
+/// component is a list of the allowed values, where an empty list matches
+/// any value
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct CalendarEvent {
+    /// 0 is Monday
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub weekdays: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub years: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub months: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hours: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub minutes: Vec<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub seconds: Vec<u32>,
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(display("{event:?} is not a valid calendar event"))]
+pub struct CalendarEventParseError {
+    event: String,
+}
+
+// Parse a component like "*", "1,15", "1..5" or "*/10"
+fn parse_component(
+    component: &str,
+    range: RangeInclusive<u32>,
+) -> Option<Vec<u32>> {
+    if component == "*" {
+        return Some(Vec::new());
+    }
+    let mut values = Vec::new();
+    for item in component.split(',') {
+        let (item, step) = match item.split_once('/') {
+            Some((item, step)) => {
+                (
+                    item,
+                    Some(step.parse::<u32>().ok().filter(|step| *step > 0)?),
+                )
+            }
+            None => (item, None),
+        };
+        let (start, end) = match item.split_once("..") {
+            Some((start, end)) => (start.parse().ok()?, end.parse().ok()?),
+            None if item == "*" => (*range.start(), *range.end()),
+            // "5/10" repeats from 5 until the end of the range
+            None if step.is_some() => (item.parse().ok()?, *range.end()),
+            None => {
+                let value = item.parse().ok()?;
+                (value, value)
+            }
+        };
+        if start > end || !range.contains(&start) || !range.contains(&end) {
+            return None;
+        }
+        values.extend((start..=end).step_by(step.unwrap_or(1) as usize));
+    }
+    values.sort_unstable();
+    values.dedup();
+    Some(values)
+}
+
+fn parse_weekday(weekday: &str) -> Option<u32> {
+    let weekday = weekday.to_lowercase();
+    WEEKDAYS
+        .iter()
+        .position(|name| weekday.starts_with(name))
+        .map(|position| position as u32)
+}
+
+fn parse_weekdays(weekdays: &str) -> Option<Vec<u32>> {
+    let mut values = Vec::new();
+    for item in weekdays.split(',') {
+        match item.split_once("..") {
+            Some((start, end)) => {
+                let (start, end) = (parse_weekday(start)?, parse_weekday(end)?);
+                if start > end {
+                    return None;
+                }
+                values.extend(start..=end);
+            }
+            None => values.push(parse_weekday(item)?),
+        }
+    }
+    values.sort_unstable();
+    values.dedup();
+    Some(values)
+}
+
+impl FromStr for CalendarEvent {
+    type Err = CalendarEventParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expanded = match s.trim().to_lowercase().as_str() {
+            "minutely" => "*-*-* *:*:00",
+            "hourly" => "*-*-* *:00:00",
+            "daily" => "*-*-* 00:00:00",
+            "weekly" => "Mon *-*-* 00:00:00",
+            "monthly" => "*-*-01 00:00:00",
+            "yearly" | "annually" => "*-01-01 00:00:00",
+            _ => s.trim(),
+        };
+        let error = || CalendarEventParseSnafu { event: s };
+        let mut event = CalendarEvent::default();
+        let mut tokens = expanded.split_whitespace().peekable();
+        if let Some(weekdays) = tokens.next_if(|token| token.starts_with(char::is_alphabetic)) {
+            event.weekdays = parse_weekdays(weekdays).with_context(error)?;
+        }
+        if let Some(date) = tokens.next_if(|token| token.contains('-')) {
+            let date: Vec<&str> = date.split('-').collect();
+            // The year can be omitted
+            let (years, months, days) = match date[..] {
+                [years, months, days] => (years, months, days),
+                [months, days] => ("*", months, days),
+                _ => return error().fail(),
+            };
+            event.years = parse_component(years, YEARS).with_context(error)?;
+            event.months = parse_component(months, 1..=12).with_context(error)?;
+            event.days = parse_component(days, 1..=31).with_context(error)?;
+        }
+        match tokens.next() {
+            Some(time) => {
+                let time: Vec<&str> = time.split(':').collect();
+                let (hours, minutes, seconds) = match time[..] {
+                    [hours, minutes, seconds] => (hours, minutes, seconds),
+                    [hours, minutes] => (hours, minutes, "00"),
+                    _ => return error().fail(),
+                };
+                event.hours = parse_component(hours, 0..=23).with_context(error)?;
+                event.minutes = parse_component(minutes, 0..=59).with_context(error)?;
+                event.seconds = parse_component(seconds, 0..=59).with_context(error)?;
+            }
+            // Midnight
+            None => {
+                event.hours = vec![0];
+                event.minutes = vec![0];
+                event.seconds = vec![0];
+            }
+        }
+        ensure!(tokens.next().is_none() && !expanded.is_empty(), error());
+        Ok(event)
+    }
+}
+
+fn matches(
+    values: &[u32],
+    value: u32,
+) -> bool {
+    values.is_empty() || values.contains(&value)
+}
+
+// Iterate the allowed values from start
+fn candidates(
+    values: &[u32],
+    start: u32,
+    end: u32,
+) -> impl Iterator<Item = u32> + '_ {
+    (start..=end).filter(move |value| matches(values, *value))
+}
+
+// Convert days since the epoch to (year, month, day), see
+// http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+fn days_from_civil(
+    year: i64,
+    month: u32,
+    day: u32,
+) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month_index = if month > 2 { month - 3 } else { month + 9 } as i64;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn local_time(timestamp: i64) -> libc::tm {
+    let mut tm = MaybeUninit::<libc::tm>::zeroed();
+    unsafe {
+        libc::localtime_r(&(timestamp as libc::time_t), tm.as_mut_ptr());
+        tm.assume_init()
+    }
+}
+
+fn local_timestamp(
+    (year, month, day): (i64, u32, u32),
+    (hour, minute, second): (u32, u32, u32),
+) -> i64 {
+    let mut tm: libc::tm = unsafe { MaybeUninit::zeroed().assume_init() };
+    tm.tm_year = (year - 1900) as i32;
+    tm.tm_mon = month as i32 - 1;
+    tm.tm_mday = day as i32;
+    tm.tm_hour = hour as i32;
+    tm.tm_min = minute as i32;
+    tm.tm_sec = second as i32;
+    // Let mktime figure out whether DST is in effect
+    tm.tm_isdst = -1;
+    unsafe { libc::mktime(&mut tm) as i64 }
+}
+
+/// Format a timestamp in seconds since the epoch as a local date and time
+pub fn format_local_time(timestamp: i64) -> String {
+    let tm = local_time(timestamp);
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900,
+        tm.tm_mon + 1,
+        tm.tm_mday,
+        tm.tm_hour,
+        tm.tm_min,
+        tm.tm_sec
+    )
+}
+
+impl CalendarEvent {
+    // The first time of the day matching the event, starting from the one passed
+    fn first_time(
+        &self,
+        (hour, minute, second): (u32, u32, u32),
+    ) -> Option<(u32, u32, u32)> {
+        for h in candidates(&self.hours, hour, 23) {
+            let minute = if h == hour { minute } else { 0 };
+            for m in candidates(&self.minutes, minute, 59) {
+                let second = if h == hour && m == minute { second } else { 0 };
+                if let Some(s) = candidates(&self.seconds, second, 59).next() {
+                    return Some((h, m, s));
+                }
+            }
+        }
+        None
+    }
+
+    /// The first time matching this event after the timestamp passed, both
+    /// expressed in seconds since the epoch
+    pub fn next_after(
+        &self,
+        after: i64,
+    ) -> Option<i64> {
+        let start = local_time(after + 1);
+        let mut days = days_from_civil(
+            start.tm_year as i64 + 1900,
+            start.tm_mon as u32 + 1,
+            start.tm_mday as u32,
+        );
+        let mut time = (
+            start.tm_hour as u32,
+            start.tm_min as u32,
+            // Leap seconds
+            (start.tm_sec as u32).min(59),
+        );
+        loop {
+            let date = civil_from_days(days);
+            let (year, month, day) = date;
+            if year > *YEARS.end() as i64 {
+                return None;
+            }
+            let weekday = (days + 3).rem_euclid(7) as u32;
+            if matches(&self.years, year as u32)
+                && matches(&self.months, month)
+                && matches(&self.days, day)
+                && matches(&self.weekdays, weekday)
+            {
+                if let Some(time) = self.first_time(time) {
+                    let timestamp = local_timestamp(date, time);
+                    // The time might not exist or be repeated because of DST
+                    if timestamp > after {
+                        return Some(timestamp);
+                    }
+                }
+            }
+            days += 1;
+            time = (0, 0, 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn local(
+        date: (i64, u32, u32),
+        time: (u32, u32, u32),
+    ) -> i64 {
+        local_timestamp(date, time)
+    }
+
+    #[test]
+    fn parse_calendar_event() {
+        assert_eq!(
+            CalendarEvent::from_str("daily").unwrap(),
+            CalendarEvent {
+                hours: vec![0],
+                minutes: vec![0],
+                seconds: vec![0],
+                ..CalendarEvent::default()
+            }
+        );
+        assert_eq!(
+            CalendarEvent::from_str("Mon..Wed,Fri *-*-1,15 03:00").unwrap(),
+            CalendarEvent {
+                weekdays: vec![0, 1, 2, 4],
+                days: vec![1, 15],
+                hours: vec![3],
+                minutes: vec![0],
+                seconds: vec![0],
+                ..CalendarEvent::default()
+            }
+        );
+        assert_eq!(
+            CalendarEvent::from_str("*:0/20").unwrap().minutes,
+            vec![0, 20, 40]
+        );
+        assert!(CalendarEvent::from_str("Foo 03:00").is_err());
+        assert!(CalendarEvent::from_str("25:00").is_err());
+        assert!(CalendarEvent::from_str("*-13-01").is_err());
+    }
+
+    #[test]
+    fn next_after() {
+        // 2023-05-10 is a Wednesday
+        let now = local((2023, 5, 10), (12, 7, 0));
+        assert_eq!(
+            CalendarEvent::from_str("daily").unwrap().next_after(now),
+            Some(local((2023, 5, 11), (0, 0, 0)))
+        );
+        assert_eq!(
+            CalendarEvent::from_str("Mon *-*-* 03:00")
+                .unwrap()
+                .next_after(now),
+            Some(local((2023, 5, 15), (3, 0, 0)))
+        );
+        assert_eq!(
+            CalendarEvent::from_str("*:0/15").unwrap().next_after(now),
+            Some(local((2023, 5, 10), (12, 15, 0)))
+        );
+        assert_eq!(
+            CalendarEvent::from_str("*-02-29 00:00")
+                .unwrap()
+                .next_after(now),
+            Some(local((2024, 2, 29), (0, 0, 0)))
+        );
+        assert_eq!(
+            CalendarEvent::from_str("2020-01-01")
+                .unwrap()
+                .next_after(now),
+            None
+        );
+    }
+}
@@ -0,0 +1,23 @@
+use std::path::PathBuf;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::Script;
+
+/// A condition that must be true for a service to be started. When a condition
+/// is false, the service is skipped instead of failing
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum Condition {
+    /// The path must exist, or must not exist if negated
+    PathExists { path: PathBuf, negate: bool },
+    /// The kernel command line must contain the key, either alone or as
+    /// key=value, or must not contain it if negated
+    KernelCommandLine { key: String, negate: bool },
+    /// Whether rinit must be running inside a container or not
+    Container(bool),
+    /// The script must exit successfully
+    Script(Box<Script>),
+}
@@ -0,0 +1,191 @@
+use std::{
+    convert::TryFrom,
+    path::PathBuf,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_with::skip_serializing_none;
+use snafu::Snafu;
+
+/// The resources that can be limited with setrlimit
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RLimitResource {
+    As,
+    Core,
+    Cpu,
+    Data,
+    Fsize,
+    Memlock,
+    Msgqueue,
+    Nice,
+    Nofile,
+    Nproc,
+    Rtprio,
+    Rttime,
+    Sigpending,
+    Stack,
+}
+
+impl RLimitResource {
+    pub const ALL: [RLimitResource; 14] = [
+        RLimitResource::As,
+        RLimitResource::Core,
+        RLimitResource::Cpu,
+        RLimitResource::Data,
+        RLimitResource::Fsize,
+        RLimitResource::Memlock,
+        RLimitResource::Msgqueue,
+        RLimitResource::Nice,
+        RLimitResource::Nofile,
+        RLimitResource::Nproc,
+        RLimitResource::Rtprio,
+        RLimitResource::Rttime,
+        RLimitResource::Sigpending,
+        RLimitResource::Stack,
+    ];
+
+    /// The key of the limit in a script section
+    pub fn key(&self) -> &'static str {
+        match self {
+            RLimitResource::As => "limit_as",
+            RLimitResource::Core => "limit_core",
+            RLimitResource::Cpu => "limit_cpu",
+            RLimitResource::Data => "limit_data",
+            RLimitResource::Fsize => "limit_fsize",
+            RLimitResource::Memlock => "limit_memlock",
+            RLimitResource::Msgqueue => "limit_msgqueue",
+            RLimitResource::Nice => "limit_nice",
+            RLimitResource::Nofile => "limit_nofile",
+            RLimitResource::Nproc => "limit_nproc",
+            RLimitResource::Rtprio => "limit_rtprio",
+            RLimitResource::Rttime => "limit_rttime",
+            RLimitResource::Sigpending => "limit_sigpending",
+            RLimitResource::Stack => "limit_stack",
+        }
+    }
+}
+
+/// A resource limit of the process. None means unlimited
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RLimit {
+    pub resource: RLimitResource,
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IoPrioClass {
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+#[derive(Snafu, Debug)]
+#[snafu(display("{class} is not a valid IO scheduling class, use realtime, best-effort or idle"))]
+pub struct InvalidIoPrioClassError {
+    class: String,
+}
+
+impl TryFrom<String> for IoPrioClass {
+    type Error = InvalidIoPrioClassError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(match value.as_str() {
+            "realtime" => IoPrioClass::Realtime,
+            "best-effort" => IoPrioClass::BestEffort,
+            "idle" => IoPrioClass::Idle,
+            _ => InvalidIoPrioClassSnafu { class: value }.fail()?,
+        })
+    }
+}
+
+/// The IO scheduling class and priority of the process, as set by ionice
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct IoPriority {
+    pub class: IoPrioClass,
+    /// From 0 (highest) to 7 (lowest), ignored by the idle class
+    pub level: u8,
+}
+
+impl IoPriority {
+    pub const DEFAULT_LEVEL: u8 = 4;
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SchedPolicy {
+    Other,
+    Batch,
+    Idle,
+    Fifo,
+    Rr,
+}
+
+#[derive(Snafu, Debug)]
+#[snafu(display("{policy} is not a valid scheduling policy, use other, batch, idle, fifo or rr"))]
+pub struct InvalidSchedPolicyError {
+    policy: String,
+}
+
+impl TryFrom<String> for SchedPolicy {
+    type Error = InvalidSchedPolicyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(match value.as_str() {
+            "other" => SchedPolicy::Other,
+            "batch" => SchedPolicy::Batch,
+            "idle" => SchedPolicy::Idle,
+            "fifo" => SchedPolicy::Fifo,
+            "rr" => SchedPolicy::Rr,
+            _ => InvalidSchedPolicySnafu { policy: value }.fail()?,
+        })
+    }
+}
+
+impl SchedPolicy {
+    /// Whether the policy is a real-time one, which needs a priority
+    pub fn is_realtime(&self) -> bool {
+        matches!(self, SchedPolicy::Fifo | SchedPolicy::Rr)
+    }
+}
+
+/// The scheduling policy of the process
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Scheduling {
+    pub policy: SchedPolicy,
+    /// From 1 to 99 for the real-time policies, 0 for the others
+    pub priority: u8,
+}
+
+/// The attributes of the process that are set before executing a script
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct ExecOptions {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rlimits: Vec<RLimit>,
+    /// From -20 (highest priority) to 19 (lowest priority)
+    pub nice: Option<i32>,
+    pub io_priority: Option<IoPriority>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// The CPUs the process can run on
+    pub cpu_affinity: Vec<usize>,
+    pub scheduling: Option<Scheduling>,
+    /// From -1000 (never killed) to 1000 (killed first by the OOM killer)
+    pub oom_score_adj: Option<i32>,
+    /// The directory the process runs in, inside root_directory if set
+    pub working_directory: Option<PathBuf>,
+    pub umask: Option<u32>,
+    /// The process is chrooted into this directory
+    pub root_directory: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// Groups added to the ones of user
+    pub supplementary_groups: Vec<String>,
+}
+
+impl ExecOptions {
+    pub fn is_default(&self) -> bool {
+        *self == ExecOptions::default()
+    }
+}
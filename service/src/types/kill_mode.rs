@@ -0,0 +1,48 @@
+use std::convert::TryFrom;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use snafu::Snafu;
+
+/// Which processes are signalled when a long running process is stopped, and
+/// which ones are killed once the main process has exited
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum KillMode {
+    /// Only signal the main process, the processes it spawned are left alone
+    Process,
+    /// Signal the process group of the main process
+    Group,
+    /// Signal every process in the cgroup of the service
+    Cgroup,
+    /// Signal the main process, then kill every process left in the cgroup
+    #[default]
+    Mixed,
+}
+
+#[derive(Snafu, Debug)]
+#[snafu(display("{mode} is not a valid kill mode, use process, group, cgroup or mixed"))]
+pub struct InvalidKillModeError {
+    mode: String,
+}
+
+impl TryFrom<String> for KillMode {
+    type Error = InvalidKillModeError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(match value.as_str() {
+            "process" => KillMode::Process,
+            "group" => KillMode::Group,
+            "cgroup" => KillMode::Cgroup,
+            "mixed" => KillMode::Mixed,
+            _ => InvalidKillModeSnafu { mode: value }.fail()?,
+        })
+    }
+}
+
+impl KillMode {
+    pub fn is_default(&self) -> bool {
+        *self == KillMode::default()
+    }
+}
@@ -11,9 +11,37 @@ use super::*;
 pub struct Longrun {
     pub name: String,
     pub run: Script,
+    /// Run to ask the process to exit, before sending any signal
+    pub stop: Option<Script>,
     pub finish: Option<Script>,
+    pub socket: Option<Socket>,
+    pub path: Option<PathTriggers>,
+    pub resources: Option<Resources>,
+    pub sandbox: Option<Sandbox>,
     #[serde(flatten)]
     pub options: ServiceOptions,
     #[serde(flatten, default, skip_serializing_if = "ScriptEnvironment::is_empty")]
     pub environment: ScriptEnvironment,
 }
+
+impl Longrun {
+    /// A longrun without any optional section, the other fields are set to
+    /// their defaults
+    pub fn new(
+        name: String,
+        run: Script,
+    ) -> Self {
+        Self {
+            name,
+            run,
+            stop: None,
+            finish: None,
+            socket: None,
+            path: None,
+            resources: None,
+            sandbox: None,
+            options: ServiceOptions::new(),
+            environment: ScriptEnvironment::new(),
+        }
+    }
+}
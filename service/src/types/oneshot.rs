@@ -7,11 +7,13 @@ use serde_with::skip_serializing_none;
 use super::*;
 
 #[skip_serializing_none]
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Oneshot {
     pub name: String,
     pub start: Script,
     pub stop: Option<Script>,
+    pub timer: Option<Timer>,
+    pub path: Option<PathTriggers>,
     #[serde(flatten)]
     pub options: ServiceOptions,
     #[serde(flatten, default, skip_serializing_if = "ScriptEnvironment::is_empty")]
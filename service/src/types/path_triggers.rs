@@ -0,0 +1,82 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// A condition on a path that starts the service when it becomes true
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum PathTrigger {
+    /// The path exists
+    Exists(PathBuf),
+    /// The file has been closed after being written, or the path has been
+    /// created, removed, moved or had its attributes changed
+    Changed(PathBuf),
+    /// Like Changed, but every write to the file is a trigger
+    Modified(PathBuf),
+    /// The directory contains at least one entry
+    DirectoryNotEmpty(PathBuf),
+}
+
+impl PathTrigger {
+    pub fn path(&self) -> &Path {
+        match self {
+            PathTrigger::Exists(path)
+            | PathTrigger::Changed(path)
+            | PathTrigger::Modified(path)
+            | PathTrigger::DirectoryNotEmpty(path) => path,
+        }
+    }
+}
+
+/// The paths watched by rsvc, the service is started every time one of the
+/// triggers fires
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct PathTriggers {
+    pub triggers: Vec<PathTrigger>,
+    /// The time window, in seconds, in which at most trigger_limit_burst
+    /// triggers start the service
+    #[serde(
+        default = "PathTriggers::default_trigger_limit_interval_sec",
+        skip_serializing_if = "PathTriggers::is_default_trigger_limit_interval_sec"
+    )]
+    pub trigger_limit_interval_sec: u64,
+    #[serde(
+        default = "PathTriggers::default_trigger_limit_burst",
+        skip_serializing_if = "PathTriggers::is_default_trigger_limit_burst"
+    )]
+    pub trigger_limit_burst: u32,
+}
+
+impl PathTriggers {
+    pub const DEFAULT_TRIGGER_LIMIT_INTERVAL_SEC: u64 = 2;
+    pub const DEFAULT_TRIGGER_LIMIT_BURST: u32 = 200;
+
+    pub fn new(triggers: Vec<PathTrigger>) -> Self {
+        Self {
+            triggers,
+            trigger_limit_interval_sec: Self::default_trigger_limit_interval_sec(),
+            trigger_limit_burst: Self::default_trigger_limit_burst(),
+        }
+    }
+
+    const fn default_trigger_limit_interval_sec() -> u64 {
+        Self::DEFAULT_TRIGGER_LIMIT_INTERVAL_SEC
+    }
+
+    const fn is_default_trigger_limit_interval_sec(interval: &u64) -> bool {
+        *interval == Self::DEFAULT_TRIGGER_LIMIT_INTERVAL_SEC
+    }
+
+    const fn default_trigger_limit_burst() -> u32 {
+        Self::DEFAULT_TRIGGER_LIMIT_BURST
+    }
+
+    const fn is_default_trigger_limit_burst(burst: &u32) -> bool {
+        *burst == Self::DEFAULT_TRIGGER_LIMIT_BURST
+    }
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::Script;
+
+/// A check on a running process, used to know when it is ready or whether it
+/// is still healthy
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum Probe {
+    /// Connecting to the unix socket must succeed
+    UnixSocket(PathBuf),
+    /// Connecting to the TCP port on localhost must succeed
+    TcpPort(u16),
+    /// The path must exist
+    PathExists(PathBuf),
+    /// The script must exit successfully
+    Script(Box<Script>),
+}
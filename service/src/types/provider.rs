@@ -3,9 +3,20 @@ use serde::{
     Serialize,
 };
 
+/// The enabled providers found for a virtual service
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
 pub enum Provider {
     Empty,
     Single(String),
     Multiple(Vec<String>),
 }
+
+impl From<Vec<String>> for Provider {
+    fn from(mut providers: Vec<String>) -> Self {
+        match providers.len() {
+            0 => Provider::Empty,
+            1 => Provider::Single(providers.pop().unwrap()),
+            _ => Provider::Multiple(providers),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_with::skip_serializing_none;
+
+/// The limits of a longrun, enforced by the controllers of its cgroup. The
+/// limits not set are left to the kernel defaults
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct Resources {
+    /// Hard limit of the memory usage in bytes, the processes are killed by
+    /// the OOM killer when it is reached
+    pub memory_max: Option<u64>,
+    /// Memory usage in bytes above which the processes are throttled and
+    /// their memory reclaimed
+    pub memory_high: Option<u64>,
+    /// Relative share of CPU time, from 1 to 10000
+    pub cpu_weight: Option<u32>,
+    /// Maximum CPU time as a percentage of one CPU, it can be higher than 100
+    /// on multi-core systems
+    pub cpu_max: Option<u32>,
+    /// Relative share of IO, from 1 to 10000
+    pub io_weight: Option<u32>,
+    /// Maximum number of processes and threads
+    pub pids_max: Option<u64>,
+}
+
+impl Resources {
+    pub const DEFAULT_WEIGHT: u32 = 100;
+    /// The period of cpu.max, in microseconds
+    pub const CPU_MAX_PERIOD: u64 = 100000;
+
+    /// The contents of the controller files, in the order they are written.
+    /// The limits not set are reset to the default
+    pub fn controller_files(&self) -> Vec<(&'static str, String)> {
+        fn max(limit: Option<u64>) -> String {
+            limit.map_or_else(|| "max".to_string(), |limit| limit.to_string())
+        }
+        vec![
+            // memory.high must not be higher than memory.max, lower it first
+            ("memory.high", max(self.memory_high)),
+            ("memory.max", max(self.memory_max)),
+            (
+                "cpu.weight",
+                self.cpu_weight.unwrap_or(Self::DEFAULT_WEIGHT).to_string(),
+            ),
+            (
+                "cpu.max",
+                format!(
+                    "{} {}",
+                    max(self
+                        .cpu_max
+                        .map(|percent| percent as u64 * Self::CPU_MAX_PERIOD / 100)),
+                    Self::CPU_MAX_PERIOD
+                ),
+            ),
+            (
+                "io.weight",
+                format!("default {}", self.io_weight.unwrap_or(Self::DEFAULT_WEIGHT)),
+            ),
+            ("pids.max", max(self.pids_max)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn controller_files() {
+        let resources = Resources {
+            memory_max: Some(1 << 30),
+            cpu_max: Some(50),
+            io_weight: Some(500),
+            ..Resources::default()
+        };
+        assert_eq!(
+            resources.controller_files(),
+            vec![
+                ("memory.high", "max".to_string()),
+                ("memory.max", "1073741824".to_string()),
+                ("cpu.weight", "100".to_string()),
+                ("cpu.max", "50000 100000".to_string()),
+                ("io.weight", "default 500".to_string()),
+                ("pids.max", "max".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    convert::TryFrom,
+    os::unix::process::ExitStatusExt,
+    process::ExitStatus,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use snafu::Snafu;
+
+/// When a long running process should be restarted after it has exited
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum RestartPolicy {
+    /// Restart the process regardless of how it exited
+    #[default]
+    Always,
+    /// Restart the process if it exited with a non-zero code or if it was
+    /// killed by a signal
+    OnFailure,
+    /// Restart the process only if it was killed by a signal
+    OnAbnormal,
+    /// Never restart the process
+    Never,
+}
+
+#[derive(Snafu, Debug)]
+#[snafu(display(
+    "{policy} is not a valid restart policy, use always, on-failure, on-abnormal or never"
+))]
+pub struct InvalidRestartPolicyError {
+    policy: String,
+}
+
+impl TryFrom<String> for RestartPolicy {
+    type Error = InvalidRestartPolicyError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(match value.as_str() {
+            "always" => RestartPolicy::Always,
+            "on-failure" => RestartPolicy::OnFailure,
+            "on-abnormal" => RestartPolicy::OnAbnormal,
+            "never" => RestartPolicy::Never,
+            _ => InvalidRestartPolicySnafu { policy: value }.fail()?,
+        })
+    }
+}
+
+impl RestartPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == RestartPolicy::default()
+    }
+
+    pub fn should_restart(
+        &self,
+        status: &ExitStatus,
+    ) -> bool {
+        match self {
+            RestartPolicy::Always => true,
+            RestartPolicy::OnFailure => !status.success(),
+            RestartPolicy::OnAbnormal => status.signal().is_some(),
+            RestartPolicy::Never => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn should_restart() {
+        let success = ExitStatus::from_raw(0);
+        // Exited with code 1
+        let failure = ExitStatus::from_raw(1 << 8);
+        let killed = ExitStatus::from_raw(libc::SIGKILL);
+
+        assert!(RestartPolicy::Always.should_restart(&success));
+        assert!(!RestartPolicy::OnFailure.should_restart(&success));
+        assert!(RestartPolicy::OnFailure.should_restart(&failure));
+        assert!(RestartPolicy::OnFailure.should_restart(&killed));
+        assert!(!RestartPolicy::OnAbnormal.should_restart(&failure));
+        assert!(RestartPolicy::OnAbnormal.should_restart(&killed));
+        assert!(!RestartPolicy::Never.should_restart(&killed));
+    }
+}
@@ -4,21 +4,27 @@ use serde::{
     Deserialize,
     Serialize,
 };
-use snafu::Snafu;
+use snafu::{
+    ensure,
+    Snafu,
+};
 
 // Define the runlevel for the service. Boot is for all the services that needs
 // to be started before the others (Default runlevel). It is more obvious for
 // root mode but it also makes sense in user mode, where for example you need
-// dbus before all the other services
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone, Copy)]
+// dbus before all the other services.
+// Other runlevels can be declared in the configuration, where their order is
+// defined. Boot always comes first
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Default, Clone)]
 pub enum RunLevel {
     Boot,
     #[default]
     Default,
+    Named(String),
 }
 
 #[derive(Debug, Snafu)]
-#[snafu(display(""))]
+#[snafu(display("{runlevel:?} is not a valid runlevel name"))]
 pub struct RunLevelParseError {
     runlevel: String,
 }
@@ -27,22 +33,42 @@ impl RunLevel {
     pub fn is_default(&self) -> bool {
         matches!(self, RunLevel::Default)
     }
+
+    /// Get the runlevels in the order they are started, from the ones
+    /// declared in the configuration. Boot is always the first one, while
+    /// default is added at the end if it hasn't been declared
+    pub fn order(declared: &[RunLevel]) -> Vec<RunLevel> {
+        let mut runlevels = vec![RunLevel::Boot];
+        runlevels.extend(
+            declared
+                .iter()
+                .filter(|runlevel| **runlevel != RunLevel::Boot)
+                .cloned(),
+        );
+        if !runlevels.contains(&RunLevel::Default) {
+            runlevels.push(RunLevel::Default);
+        }
+        runlevels
+    }
 }
 
 impl FromStr for RunLevel {
     type Err = RunLevelParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "boot" => Ok(RunLevel::Boot),
-            "default" => Ok(RunLevel::Default),
+        Ok(match s {
+            "boot" => RunLevel::Boot,
+            "default" => RunLevel::Default,
             _ => {
-                RunLevelParseSnafu {
-                    runlevel: s.to_string(),
-                }
-                .fail()
+                ensure!(
+                    !s.is_empty()
+                        && s.chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                    RunLevelParseSnafu { runlevel: s }
+                );
+                RunLevel::Named(s.to_owned())
             }
-        }
+        })
     }
 }
 
@@ -51,7 +77,46 @@ impl ToString for RunLevel {
         match self {
             RunLevel::Boot => "boot",
             RunLevel::Default => "default",
+            RunLevel::Named(name) => name,
         }
         .to_string()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_runlevel() {
+        assert_eq!(RunLevel::from_str("boot").unwrap(), RunLevel::Boot);
+        assert_eq!(
+            RunLevel::from_str("multi-user").unwrap(),
+            RunLevel::Named("multi-user".to_string())
+        );
+        assert!(RunLevel::from_str("multi user").is_err());
+    }
+
+    #[test]
+    fn runlevels_order() {
+        assert_eq!(
+            RunLevel::order(&[RunLevel::Named("rescue".to_string())]),
+            vec![
+                RunLevel::Boot,
+                RunLevel::Named("rescue".to_string()),
+                RunLevel::Default
+            ]
+        );
+        assert_eq!(
+            RunLevel::order(&[
+                RunLevel::Default,
+                RunLevel::Named("maintenance".to_string())
+            ]),
+            vec![
+                RunLevel::Boot,
+                RunLevel::Default,
+                RunLevel::Named("maintenance".to_string())
+            ]
+        );
+    }
+}
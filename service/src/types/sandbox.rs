@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+/// The hardening of a longrun. Each script of the service runs in its own
+/// namespaces, so the run and stop scripts don't share /tmp or the loopback
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct Sandbox {
+    /// Mount a new tmpfs on /tmp and /var/tmp
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub private_tmp: bool,
+    /// Make /usr, /boot and /etc read-only
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub protect_system: bool,
+    /// Make /home, /root and /run/user inaccessible
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub protect_home: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub read_only_paths: Vec<PathBuf>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub inaccessible_paths: Vec<PathBuf>,
+    /// Run in a new network namespace, where only the loopback is available
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub private_network: bool,
+    /// Mount a new /dev containing only the pseudo devices, like /dev/null
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub private_devices: bool,
+    /// The process and its children can't gain privileges, e.g. through
+    /// setuid binaries
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub no_new_privileges: bool,
+}
+
+impl Sandbox {
+    pub const PROTECT_SYSTEM_PATHS: [&'static str; 3] = ["/usr", "/boot", "/etc"];
+    pub const PROTECT_HOME_PATHS: [&'static str; 3] = ["/home", "/root", "/run/user"];
+
+    /// Whether the service needs its own mount namespace
+    pub fn needs_mount_namespace(&self) -> bool {
+        self.private_tmp
+            || self.protect_system
+            || self.protect_home
+            || !self.read_only_paths.is_empty()
+            || !self.inaccessible_paths.is_empty()
+            || self.private_devices
+    }
+
+    /// The paths that are made read-only, the ones of protect_system included
+    pub fn all_read_only_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = if self.protect_system {
+            Self::PROTECT_SYSTEM_PATHS
+                .iter()
+                .map(PathBuf::from)
+                .collect()
+        } else {
+            Vec::new()
+        };
+        paths.extend(self.read_only_paths.iter().cloned());
+        paths
+    }
+
+    /// The paths that are made inaccessible, the ones of protect_home included
+    pub fn all_inaccessible_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = if self.protect_home {
+            Self::PROTECT_HOME_PATHS.iter().map(PathBuf::from).collect()
+        } else {
+            Vec::new()
+        };
+        paths.extend(self.inaccessible_paths.iter().cloned());
+        paths
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn mount_namespace() {
+        assert!(!Sandbox::default().needs_mount_namespace());
+        assert!(
+            !Sandbox {
+                private_network: true,
+                no_new_privileges: true,
+                ..Sandbox::default()
+            }
+            .needs_mount_namespace()
+        );
+        let sandbox = Sandbox {
+            protect_home: true,
+            inaccessible_paths: vec![PathBuf::from("/srv/secrets")],
+            ..Sandbox::default()
+        };
+        assert!(sandbox.needs_mount_namespace());
+        assert_eq!(
+            sandbox.all_inaccessible_paths(),
+            vec![
+                PathBuf::from("/home"),
+                PathBuf::from("/root"),
+                PathBuf::from("/run/user"),
+                PathBuf::from("/srv/secrets"),
+            ]
+        );
+    }
+}
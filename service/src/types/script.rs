@@ -7,6 +7,13 @@ use serde::{
 use serde_with::skip_serializing_none;
 use snafu::Snafu;
 
+use super::{
+    ExecOptions,
+    KillMode,
+    Probe,
+    RestartPolicy,
+};
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum ScriptPrefix {
     Bash,
@@ -37,6 +44,15 @@ impl TryFrom<String> for ScriptPrefix {
     }
 }
 
+/// A signal sent to stop a process, and how long to wait for it to exit
+/// before moving on to the next step
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SignalStep {
+    pub signal: i32,
+    /// In milliseconds
+    pub wait: u32,
+}
+
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Script {
@@ -72,11 +88,90 @@ pub struct Script {
     )]
     /// The signal to send when we want to stop/close a script/process
     pub down_signal: i32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    /// The signals to send in order when stopping the process, before
+    /// SIGKILL. When set, it replaces down_signal and timeout_kill
+    pub down_signals: Vec<SignalStep>,
+    #[serde(default, skip_serializing_if = "KillMode::is_default")]
+    /// Which processes receive down_signal, and which ones are killed after
+    /// the main process has exited
+    pub kill_mode: KillMode,
+    #[serde(default, skip_serializing_if = "RestartPolicy::is_default")]
+    /// When the process should be restarted after it has exited
+    pub restart: RestartPolicy,
+    #[serde(
+        default = "Script::default_restart_delay",
+        skip_serializing_if = "Script::is_default_restart_delay"
+    )]
+    /// The time to wait before restarting the process, in milliseconds. It
+    /// doubles after each consecutive restart
+    pub restart_delay: u32,
+    #[serde(
+        default = "Script::default_restart_delay_max",
+        skip_serializing_if = "Script::is_default_restart_delay_max"
+    )]
+    /// The maximum time to wait before restarting the process, in milliseconds
+    pub restart_delay_max: u32,
+    #[serde(
+        default = "Script::default_restart_limit_burst",
+        skip_serializing_if = "Script::is_default_restart_limit_burst"
+    )]
+    /// How many times the process can die within restart_limit_interval before
+    /// the service is considered failed
+    pub restart_limit_burst: u32,
+    #[serde(
+        default = "Script::default_restart_limit_interval",
+        skip_serializing_if = "Script::is_default_restart_limit_interval"
+    )]
+    /// The time window used by restart_limit_burst, in milliseconds
+    pub restart_limit_interval: u32,
     pub user: Option<String>,
     pub group: Option<String>,
     // The fd that will receive input as soon as the program start
     // This is only for programs that implement readiness
     pub notify: Option<u8>,
+    #[serde(default, skip_serializing_if = "Script::is_default_notify_socket")]
+    /// Export NOTIFY_SOCKET to the process and wait for READY=1 before
+    /// considering it up, like sd_notify
+    pub notify_socket: bool,
+    /// Poll this probe until it succeeds before considering the process up,
+    /// instead of waiting for timeout milliseconds
+    pub readiness: Option<Probe>,
+    /// Check periodically that the running process is still healthy
+    pub liveness: Option<Probe>,
+    #[serde(
+        default = "Script::default_liveness_interval",
+        skip_serializing_if = "Script::is_default_liveness_interval"
+    )]
+    /// How often the liveness probe is checked, in milliseconds
+    pub liveness_interval: u32,
+    #[serde(
+        default = "Script::default_liveness_timeout",
+        skip_serializing_if = "Script::is_default_liveness_timeout"
+    )]
+    /// How long the liveness probe can take before it is considered failed, in
+    /// milliseconds
+    pub liveness_timeout: u32,
+    #[serde(
+        default = "Script::default_liveness_threshold",
+        skip_serializing_if = "Script::is_default_liveness_threshold"
+    )]
+    /// How many times in a row the liveness probe can fail before the process
+    /// is considered hung and it is killed
+    pub liveness_threshold: u32,
+    /// The process must send a keepalive within this interval, in
+    /// milliseconds, or it is considered hung
+    pub watchdog: Option<u32>,
+    #[serde(
+        default = "Script::default_watchdog_signal",
+        skip_serializing_if = "Script::is_default_watchdog_signal"
+    )]
+    /// The signal to send to a hung process
+    pub watchdog_signal: i32,
+    #[serde(flatten, default, skip_serializing_if = "ExecOptions::is_default")]
+    /// The limits, scheduling and environment of the process. Boxed, since
+    /// most scripts leave it empty
+    pub exec: Box<ExecOptions>,
 }
 
 impl Script {
@@ -87,6 +182,15 @@ impl Script {
     // children as well. Sending SIGTERM would only kill the shell and leave the
     // children runnning
     pub const DEFAULT_DOWN_SIGNAL: i32 = libc::SIGHUP;
+    pub const DEFAULT_RESTART_DELAY: u32 = 100;
+    pub const DEFAULT_RESTART_DELAY_MAX: u32 = 10000;
+    pub const DEFAULT_RESTART_LIMIT_BURST: u32 = 5;
+    pub const DEFAULT_RESTART_LIMIT_INTERVAL: u32 = 10000;
+    pub const DEFAULT_LIVENESS_INTERVAL: u32 = 10000;
+    pub const DEFAULT_LIVENESS_TIMEOUT: u32 = 1000;
+    pub const DEFAULT_LIVENESS_THRESHOLD: u32 = 3;
+    // Make the process dump its core, so that it can be inspected
+    pub const DEFAULT_WATCHDOG_SIGNAL: i32 = libc::SIGABRT;
 
     const fn default_timeout() -> u32 {
         Self::DEFAULT_TIMEOUT
@@ -120,6 +224,74 @@ impl Script {
         *signal == Self::DEFAULT_DOWN_SIGNAL
     }
 
+    const fn default_restart_delay() -> u32 {
+        Self::DEFAULT_RESTART_DELAY
+    }
+
+    fn is_default_restart_delay(restart_delay: &u32) -> bool {
+        *restart_delay == Self::DEFAULT_RESTART_DELAY
+    }
+
+    const fn default_restart_delay_max() -> u32 {
+        Self::DEFAULT_RESTART_DELAY_MAX
+    }
+
+    fn is_default_restart_delay_max(restart_delay_max: &u32) -> bool {
+        *restart_delay_max == Self::DEFAULT_RESTART_DELAY_MAX
+    }
+
+    const fn default_restart_limit_burst() -> u32 {
+        Self::DEFAULT_RESTART_LIMIT_BURST
+    }
+
+    fn is_default_restart_limit_burst(restart_limit_burst: &u32) -> bool {
+        *restart_limit_burst == Self::DEFAULT_RESTART_LIMIT_BURST
+    }
+
+    const fn default_restart_limit_interval() -> u32 {
+        Self::DEFAULT_RESTART_LIMIT_INTERVAL
+    }
+
+    fn is_default_restart_limit_interval(restart_limit_interval: &u32) -> bool {
+        *restart_limit_interval == Self::DEFAULT_RESTART_LIMIT_INTERVAL
+    }
+
+    const fn default_liveness_interval() -> u32 {
+        Self::DEFAULT_LIVENESS_INTERVAL
+    }
+
+    fn is_default_liveness_interval(liveness_interval: &u32) -> bool {
+        *liveness_interval == Self::DEFAULT_LIVENESS_INTERVAL
+    }
+
+    const fn default_liveness_timeout() -> u32 {
+        Self::DEFAULT_LIVENESS_TIMEOUT
+    }
+
+    fn is_default_liveness_timeout(liveness_timeout: &u32) -> bool {
+        *liveness_timeout == Self::DEFAULT_LIVENESS_TIMEOUT
+    }
+
+    const fn default_liveness_threshold() -> u32 {
+        Self::DEFAULT_LIVENESS_THRESHOLD
+    }
+
+    fn is_default_liveness_threshold(liveness_threshold: &u32) -> bool {
+        *liveness_threshold == Self::DEFAULT_LIVENESS_THRESHOLD
+    }
+
+    const fn default_watchdog_signal() -> i32 {
+        Self::DEFAULT_WATCHDOG_SIGNAL
+    }
+
+    const fn is_default_watchdog_signal(signal: &i32) -> bool {
+        *signal == Self::DEFAULT_WATCHDOG_SIGNAL
+    }
+
+    fn is_default_notify_socket(notify_socket: &bool) -> bool {
+        !*notify_socket
+    }
+
     // This function always set the default values instead of leaving None
     // Use it everywhere the script will be read and executed
     pub fn new(
@@ -133,9 +305,25 @@ impl Script {
             timeout_kill: Self::default_timeout_kill(),
             max_deaths: Self::default_max_deaths(),
             down_signal: Self::default_down_signal(),
+            down_signals: Vec::new(),
+            kill_mode: KillMode::default(),
+            restart: RestartPolicy::default(),
+            restart_delay: Self::default_restart_delay(),
+            restart_delay_max: Self::default_restart_delay_max(),
+            restart_limit_burst: Self::default_restart_limit_burst(),
+            restart_limit_interval: Self::default_restart_limit_interval(),
             user: None,
             group: None,
             notify: None,
+            notify_socket: false,
+            readiness: None,
+            liveness: None,
+            liveness_interval: Self::default_liveness_interval(),
+            liveness_timeout: Self::default_liveness_timeout(),
+            liveness_threshold: Self::default_liveness_threshold(),
+            watchdog: None,
+            watchdog_signal: Self::default_watchdog_signal(),
+            exec: Box::default(),
         }
     }
 
@@ -146,4 +334,84 @@ impl Script {
         // killed
         (self.timeout + self.timeout_kill) * self.max_deaths as u32 - self.timeout_kill
     }
+
+    /// Get the signals to send in order to stop the process, SIGKILL is sent
+    /// after the last one
+    pub fn get_down_steps(&self) -> Vec<SignalStep> {
+        if self.down_signals.is_empty() {
+            vec![SignalStep {
+                signal: self.down_signal,
+                wait: self.timeout_kill,
+            }]
+        } else {
+            self.down_signals.clone()
+        }
+    }
+
+    /// Get the maximum time that stopping the process with signals might take
+    pub fn get_stop_time(&self) -> u32 {
+        self.get_down_steps().iter().map(|step| step.wait).sum()
+    }
+
+    /// Get the time to wait before restarting the process, given how many
+    /// times it has been restarted in a row
+    pub fn get_restart_delay(
+        &self,
+        restarts: u32,
+    ) -> u32 {
+        self.restart_delay
+            .saturating_mul(2u32.saturating_pow(restarts))
+            .min(self.restart_delay_max)
+    }
+
+    /// Get the total time spent waiting between the deaths happening while
+    /// starting the process
+    pub fn get_startup_restart_delay(&self) -> u32 {
+        (0..self.max_deaths.saturating_sub(1) as u32)
+            .map(|restarts| self.get_restart_delay(restarts))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn restart_delay_backoff() {
+        let mut script = Script::new(ScriptPrefix::Bash, "exit 1".to_string());
+        script.restart_delay = 100;
+        script.restart_delay_max = 1000;
+
+        assert_eq!(script.get_restart_delay(0), 100);
+        assert_eq!(script.get_restart_delay(1), 200);
+        assert_eq!(script.get_restart_delay(3), 800);
+        assert_eq!(script.get_restart_delay(4), 1000);
+        assert_eq!(script.get_restart_delay(40), 1000);
+        // max_deaths is 3, there are 2 restarts while starting
+        assert_eq!(script.get_startup_restart_delay(), 300);
+    }
+
+    #[test]
+    fn down_steps() {
+        let mut script = Script::new(ScriptPrefix::Bash, "loop".to_string());
+        assert_eq!(
+            script.get_down_steps(),
+            vec![SignalStep {
+                signal: libc::SIGHUP,
+                wait: 3000
+            }]
+        );
+        script.down_signals = vec![
+            SignalStep {
+                signal: libc::SIGCONT,
+                wait: 0,
+            },
+            SignalStep {
+                signal: libc::SIGTERM,
+                wait: 5000,
+            },
+        ];
+        assert_eq!(script.get_stop_time(), 5000);
+    }
 }
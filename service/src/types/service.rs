@@ -5,7 +5,7 @@ use serde::{
 
 use super::*;
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum Service {
     Bundle(Bundle),
     Longrun(Longrun),
@@ -28,26 +28,97 @@ impl Service {
             Self::Bundle(bundle) => &bundle.options.contents,
             Self::Longrun(longrun) => &longrun.options.dependencies,
             Self::Oneshot(oneshot) => &oneshot.options.dependencies,
-            // TODO: What should be done here?
+            // The provider is selected by the dependency graph, see
+            // Node::dependencies
             Self::Virtual(_virtual_service) => &[],
         }
     }
 
+    /// Services that must be enabled and up for this service to run
+    pub fn requires(&self) -> &[String] {
+        match &self {
+            Self::Longrun(longrun) => &longrun.options.requires,
+            Self::Oneshot(oneshot) => &oneshot.options.requires,
+            Self::Bundle(_) | Self::Virtual(_) => &[],
+        }
+    }
+
+    /// Alternatives where at least one must be enabled and up for this service
+    /// to run
+    pub fn requires_one(&self) -> &[String] {
+        match &self {
+            Self::Longrun(longrun) => &longrun.options.requires_one,
+            Self::Oneshot(oneshot) => &oneshot.options.requires_one,
+            Self::Bundle(_) | Self::Virtual(_) => &[],
+        }
+    }
+
+    pub fn after(&self) -> &[String] {
+        match &self {
+            Self::Longrun(longrun) => &longrun.options.after,
+            Self::Oneshot(oneshot) => &oneshot.options.after,
+            Self::Bundle(_) | Self::Virtual(_) => &[],
+        }
+    }
+
+    pub fn before(&self) -> &[String] {
+        match &self {
+            Self::Longrun(longrun) => &longrun.options.before,
+            Self::Oneshot(oneshot) => &oneshot.options.before,
+            Self::Bundle(_) | Self::Virtual(_) => &[],
+        }
+    }
+
     pub fn should_start(&self) -> bool {
         match &self {
             Service::Bundle(_) => false,
-            Service::Longrun(longrun) => longrun.options.autostart,
-            Service::Oneshot(oneshot) => oneshot.options.autostart,
+            // On-demand services are started by their sockets and the services
+            // with path triggers when one of them fires
+            Service::Longrun(longrun) => {
+                longrun.options.autostart && !self.is_on_demand() && longrun.path.is_none()
+            }
+            // Timers run the start script on their own
+            Service::Oneshot(oneshot) => {
+                oneshot.options.autostart && oneshot.timer.is_none() && oneshot.path.is_none()
+            }
             Service::Virtual(_) => false,
         }
     }
 
-    pub fn runlevel(&self) -> RunLevel {
+    pub fn runlevel(&self) -> &RunLevel {
+        match &self {
+            Service::Bundle(bundle) => &bundle.options.runlevel,
+            Service::Longrun(longrun) => &longrun.options.runlevel,
+            Service::Oneshot(oneshot) => &oneshot.options.runlevel,
+            Service::Virtual(virtual_service) => &virtual_service.options.runlevel,
+        }
+    }
+
+    /// The sockets bound by rsvc for this service
+    pub fn socket(&self) -> Option<&Socket> {
+        match &self {
+            Service::Longrun(longrun) => longrun.socket.as_ref(),
+            Service::Bundle(_) | Service::Oneshot(_) | Service::Virtual(_) => None,
+        }
+    }
+
+    pub fn is_on_demand(&self) -> bool {
+        matches!(self.socket(), Some(socket) if socket.activation == Activation::OnDemand)
+    }
+
+    pub fn timer(&self) -> Option<&Timer> {
+        match &self {
+            Service::Oneshot(oneshot) => oneshot.timer.as_ref(),
+            Service::Bundle(_) | Service::Longrun(_) | Service::Virtual(_) => None,
+        }
+    }
+
+    /// The paths watched by rsvc to start this service
+    pub fn path_triggers(&self) -> Option<&PathTriggers> {
         match &self {
-            Service::Bundle(bundle) => bundle.options.runlevel,
-            Service::Longrun(longrun) => longrun.options.runlevel,
-            Service::Oneshot(oneshot) => oneshot.options.runlevel,
-            Service::Virtual(_) => unimplemented!(),
+            Service::Longrun(longrun) => longrun.path.as_ref(),
+            Service::Oneshot(oneshot) => oneshot.path.as_ref(),
+            Service::Bundle(_) | Service::Virtual(_) => None,
         }
     }
 }
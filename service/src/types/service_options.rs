@@ -3,7 +3,10 @@ use serde::{
     Serialize,
 };
 
-use super::RunLevel;
+use super::{
+    Condition,
+    RunLevel,
+};
 
 /// Store options for Longrun and Oneshot
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
@@ -14,6 +17,14 @@ pub struct ServiceOptions {
     pub requires: Vec<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub requires_one: Vec<String>,
+    /// Start after these services when they are in the graph, without
+    /// depending on them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+    /// Start before these services when they are in the graph, without them
+    /// depending on this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<String>,
     #[serde(
         default = "ServiceOptions::default_autostart",
         skip_serializing_if = "ServiceOptions::is_default_autostart"
@@ -21,6 +32,8 @@ pub struct ServiceOptions {
     pub autostart: bool,
     #[serde(default, skip_serializing_if = "RunLevel::is_default")]
     pub runlevel: RunLevel,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conditions: Vec<Condition>,
 }
 
 impl ServiceOptions {
@@ -29,8 +42,11 @@ impl ServiceOptions {
             dependencies: Vec::new(),
             requires: Vec::new(),
             requires_one: Vec::new(),
+            after: Vec::new(),
+            before: Vec::new(),
             autostart: Self::default_autostart(),
             runlevel: RunLevel::Default,
+            conditions: Vec::new(),
         }
     }
 
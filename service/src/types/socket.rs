@@ -0,0 +1,139 @@
+use std::{
+    convert::TryFrom,
+    path::PathBuf,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use snafu::Snafu;
+
+/// A listening socket owned by rsvc and passed to the service
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub enum SocketAddress {
+    UnixStream(PathBuf),
+    UnixDatagram(PathBuf),
+    Fifo(PathBuf),
+    /// TCP port bound on the loopback interface
+    Tcp(u16),
+    /// UDP port bound on the loopback interface
+    Udp(u16),
+}
+
+impl SocketAddress {
+    /// Whether connections can be accepted on this socket
+    pub fn is_stream(&self) -> bool {
+        matches!(self, SocketAddress::UnixStream(_) | SocketAddress::Tcp(_))
+    }
+
+    /// The name exported in LISTEN_FDNAMES
+    pub fn name(&self) -> String {
+        match self {
+            SocketAddress::UnixStream(path)
+            | SocketAddress::UnixDatagram(path)
+            | SocketAddress::Fifo(path) => {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().replace(':', "_"))
+                    .unwrap_or_default()
+            }
+            SocketAddress::Tcp(port) => format!("tcp-{port}"),
+            SocketAddress::Udp(port) => format!("udp-{port}"),
+        }
+    }
+}
+
+/// When a longrun with sockets is started
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub enum Activation {
+    /// Start the service with the other services of its runlevel
+    #[default]
+    Boot,
+    /// Only watch the sockets and start the service on the first connection
+    /// or datagram received
+    OnDemand,
+}
+
+#[derive(Snafu, Debug)]
+#[snafu(display("{activation} is not a valid activation, use boot or on-demand"))]
+pub struct InvalidActivationError {
+    activation: String,
+}
+
+impl TryFrom<String> for Activation {
+    type Error = InvalidActivationError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Ok(match value.as_str() {
+            "boot" => Activation::Boot,
+            "on-demand" => Activation::OnDemand,
+            _ => InvalidActivationSnafu { activation: value }.fail()?,
+        })
+    }
+}
+
+impl Activation {
+    pub fn is_default(&self) -> bool {
+        *self == Activation::default()
+    }
+}
+
+/// The sockets of a longrun, they are bound before the service is started and
+/// stay open while it is restarted
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct Socket {
+    pub listen: Vec<SocketAddress>,
+    /// The permissions of the unix sockets and FIFOs
+    #[serde(
+        default = "Socket::default_mode",
+        skip_serializing_if = "Socket::is_default_mode"
+    )]
+    pub mode: u32,
+    #[serde(default, skip_serializing_if = "Activation::is_default")]
+    pub activation: Activation,
+    /// Accept the connections in rsvc and run a new instance of the run
+    /// script for each one, like inetd
+    #[serde(default, skip_serializing_if = "Socket::is_default_accept")]
+    pub accept: bool,
+    /// How many instances can run at the same time in accept mode
+    #[serde(
+        default = "Socket::default_max_connections",
+        skip_serializing_if = "Socket::is_default_max_connections"
+    )]
+    pub max_connections: u32,
+}
+
+impl Socket {
+    pub const DEFAULT_MODE: u32 = 0o666;
+    pub const DEFAULT_MAX_CONNECTIONS: u32 = 64;
+
+    pub fn new(listen: Vec<SocketAddress>) -> Self {
+        Self {
+            listen,
+            mode: Self::default_mode(),
+            activation: Activation::default(),
+            accept: false,
+            max_connections: Self::default_max_connections(),
+        }
+    }
+
+    const fn default_mode() -> u32 {
+        Self::DEFAULT_MODE
+    }
+
+    const fn is_default_mode(mode: &u32) -> bool {
+        *mode == Self::DEFAULT_MODE
+    }
+
+    fn is_default_accept(accept: &bool) -> bool {
+        !*accept
+    }
+
+    const fn default_max_connections() -> u32 {
+        Self::DEFAULT_MAX_CONNECTIONS
+    }
+
+    const fn is_default_max_connections(max_connections: &u32) -> bool {
+        *max_connections == Self::DEFAULT_MAX_CONNECTIONS
+    }
+}
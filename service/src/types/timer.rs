@@ -0,0 +1,32 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::CalendarEvent;
+
+/// When a oneshot should run. It is not started with its runlevel, rsvc runs
+/// its start script every time the timer elapses instead
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Default)]
+pub struct Timer {
+    /// Run once, this many seconds after the system has booted
+    pub on_boot_sec: Option<u64>,
+    /// Run once, this many seconds after the timer has been activated with
+    /// its runlevel
+    pub on_active_sec: Option<u64>,
+    /// Run every time the calendar event elapses. The missed runs are caught
+    /// up after downtime
+    pub on_calendar: Option<CalendarEvent>,
+    /// Delay every run by a random time between 0 and this many seconds
+    #[serde(
+        default,
+        skip_serializing_if = "Timer::is_default_randomized_delay_sec"
+    )]
+    pub randomized_delay_sec: u64,
+}
+
+impl Timer {
+    fn is_default_randomized_delay_sec(randomized_delay_sec: &u64) -> bool {
+        *randomized_delay_sec == 0
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::runlevel::RunLevel;
+
+/// Store options for Virtual
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
+pub struct VirtualOptions {
+    /// The services that can provide this virtual service. Only one of them
+    /// will be selected by the dependency graph
+    pub providers: Vec<String>,
+    #[serde(default, skip_serializing_if = "RunLevel::is_default")]
+    pub runlevel: RunLevel,
+}
@@ -3,8 +3,10 @@ use serde::{
     Serialize,
 };
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+use super::virtual_options::VirtualOptions;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct Virtual {
     pub name: String,
-    pub providers: Vec<String>,
+    pub options: VirtualOptions,
 }
@@ -0,0 +1,73 @@
+use std::{
+    env,
+    fs,
+    path::Path,
+};
+
+use rinit_service::types::{
+    Condition,
+    ScriptEnvironment,
+};
+use tracing::{
+    info,
+    warn,
+};
+
+use crate::supervision::run_short_lived_script;
+
+const KERNEL_CMDLINE: &str = "/proc/cmdline";
+
+/// Check all the conditions of a service, stopping at the first one that is
+/// false
+pub async fn check_conditions(
+    conditions: &[Condition],
+    env: &ScriptEnvironment,
+) -> bool {
+    for condition in conditions {
+        let res = match condition {
+            Condition::PathExists { path, negate } => path.exists() != *negate,
+            Condition::KernelCommandLine { key, negate } => kernel_cmdline_contains(key) != *negate,
+            Condition::Container(container) => is_container() == *container,
+            Condition::Script(script) => {
+                match run_short_lived_script(script, env, None).await {
+                    Ok(res) => res,
+                    Err(err) => {
+                        warn!("unable to run the condition script: {err}");
+                        false
+                    }
+                }
+            }
+        };
+        if !res {
+            info!("condition {condition:?} is false");
+            return false;
+        }
+    }
+
+    true
+}
+
+fn kernel_cmdline_contains(key: &str) -> bool {
+    match fs::read_to_string(KERNEL_CMDLINE) {
+        Ok(cmdline) => {
+            cmdline.split_whitespace().any(|arg| {
+                arg == key
+                    || arg
+                        .split_once('=')
+                        .is_some_and(|(arg_key, _)| arg_key == key)
+            })
+        }
+        Err(err) => {
+            warn!("unable to read {KERNEL_CMDLINE}: {err}");
+            false
+        }
+    }
+}
+
+// Container managers set the container environment variable for the init
+// process, docker and podman create these files instead
+fn is_container() -> bool {
+    env::var_os("container").is_some()
+        || Path::new("/.dockerenv").exists()
+        || Path::new("/run/.containerenv").exists()
+}
@@ -1,6 +1,7 @@
 use std::{
     cell::RefCell,
     path::Path,
+    rc::Rc,
     time::Duration,
 };
 
@@ -15,13 +16,23 @@ use flexi_logger::{
     Naming,
     WriteMode,
 };
-use futures::future::BoxFuture;
-use rinit_ipc::Request;
+use futures::{
+    future::BoxFuture,
+    FutureExt,
+};
+use rinit_ipc::{
+    Request,
+    ServiceStatus,
+};
 use rinit_service::{
+    dirs::Dirs,
     graph::Node,
     service_state::{
+        Health,
         IdleServiceState,
+        NotifyState,
         ServiceState,
+        TimerState,
         TransitioningServiceState,
     },
     types::Service,
@@ -32,7 +43,10 @@ use tokio::{
         mpsc,
         watch,
     },
-    task,
+    task::{
+        self,
+        JoinHandle,
+    },
     time::timeout,
 };
 use tracing::{
@@ -43,9 +57,18 @@ use tracing::{
 };
 use tracing_subscriber::FmtSubscriber;
 
-use crate::supervision::{
-    run_short_lived_script,
-    Supervisor,
+use crate::{
+    conditions::check_conditions,
+    supervision::{
+        accept_connections,
+        find_credentials,
+        run_short_lived_script,
+        set_resources,
+        Cgroup,
+        ListenSockets,
+        NotifySocket,
+        Supervisor,
+    },
 };
 
 // This data will be changed frequently
@@ -58,6 +81,23 @@ pub struct LiveService {
     _rx: broadcast::Receiver<IdleServiceState>,
     pub state: RefCell<ServiceState>,
     pub terminate: RefCell<Option<watch::Sender<()>>>,
+    /// What the service reported through its notify socket
+    pub notify: RefCell<NotifyState>,
+    /// The result of the liveness probe of the running process
+    pub health: RefCell<Option<Health>>,
+    /// The sockets declared by the service, bound until it is removed
+    pub sockets: Option<Rc<ListenSockets>>,
+    /// The task watching the sockets of an on-demand service
+    pub activation: RefCell<Option<JoinHandle<()>>>,
+    /// When the timer of the service has run and will run next
+    pub timer: RefCell<Option<TimerState>>,
+    /// The task running a oneshot on its timer. It keeps running when the
+    /// oneshot is stopped, until its runlevel is stopped or it is removed
+    pub timer_task: RefCell<Option<JoinHandle<()>>>,
+    /// The task watching the path triggers of the service
+    pub path_watch: RefCell<Option<JoinHandle<()>>>,
+    /// Why the process of the service exited last, when it hit a limit
+    pub failure_reason: RefCell<Option<String>>,
     pub remove: bool,
     pub new: Option<Box<LiveService>>,
 }
@@ -73,29 +113,76 @@ impl LiveService {
             tx,
             _rx: rx,
             terminate: RefCell::new(None),
+            notify: RefCell::new(NotifyState::default()),
+            health: RefCell::new(None),
+            sockets: None,
+            activation: RefCell::new(None),
+            timer: RefCell::new(None),
+            timer_task: RefCell::new(None),
+            path_watch: RefCell::new(None),
+            failure_reason: RefCell::new(None),
         }
     }
 
-    pub fn get_timeout(&self) -> Duration {
-        Duration::from_millis(match *self.state.borrow() {
+    /// Bind the sockets of a longrun, so that its dependents can connect to
+    /// them before it is up
+    pub fn bind_sockets(&mut self) {
+        if let Some(socket) = self.node.service.socket() {
+            match ListenSockets::bind(socket) {
+                Ok(sockets) => self.sockets = Some(Rc::new(sockets)),
+                Err(err) => {
+                    error!(
+                        "unable to bind the sockets of {}: {err:?}",
+                        self.node.name()
+                    )
+                }
+            }
+        }
+    }
+
+    /// Take the sockets of the old instance of this service, if they are
+    /// declared in the same way; bind new ones otherwise
+    pub fn inherit_sockets(
+        &mut self,
+        old: &mut LiveService,
+    ) {
+        if self.node.service.socket() == old.node.service.socket() {
+            self.sockets = old.sockets.take();
+        } else {
+            old.sockets = None;
+            self.bind_sockets();
+        }
+    }
+
+    /// Get the maximum time that the current transition might take. Bundles and
+    /// virtual services are transitioning while their members or provider are,
+    /// so they don't have a timeout on their own
+    pub fn get_timeout(&self) -> Option<Duration> {
+        Some(Duration::from_millis(match *self.state.borrow() {
             ServiceState::Idle(_) => unreachable!(),
             ServiceState::Transitioning(state) => {
                 match state {
                     TransitioningServiceState::Starting => {
                         match &self.node.service {
-                            Service::Bundle(_) => unreachable!(),
                             Service::Longrun(longrun) => {
                                 longrun.run.timeout * longrun.run.max_deaths as u32
+                                    + longrun.run.get_startup_restart_delay()
                             }
                             Service::Oneshot(oneshot) => oneshot.start.get_maximum_time(),
-                            Service::Virtual(_) => todo!(),
+                            Service::Bundle(_) | Service::Virtual(_) => return None,
                         }
                     }
                     TransitioningServiceState::Stopping => {
                         match &self.node.service {
-                            Service::Bundle(_) => unreachable!(),
                             Service::Longrun(longrun) => {
-                                longrun.run.timeout_kill
+                                longrun.run.get_stop_time()
+                                    + if let Some(stop) = &longrun.stop {
+                                        // The process has timeout_kill to exit after the
+                                        // stop script
+                                        stop.get_maximum_time() + longrun.run.timeout_kill
+                                    } else {
+                                        0
+                                    }
                                     + if let Some(finish) = &longrun.finish {
                                         finish.get_maximum_time()
                                     } else {
@@ -109,12 +196,12 @@ impl LiveService {
                                     0
                                 }
                             }
-                            Service::Virtual(_) => todo!(),
+                            Service::Bundle(_) | Service::Virtual(_) => return None,
                         }
                     }
                 }
             }
-        } as u64)
+        } as u64))
     }
 
     /// Wait until we have an idle service state, i.e. non transitioning
@@ -126,14 +213,17 @@ impl LiveService {
                 let mut rx = self.tx.subscribe();
                 let service_timeout = self.get_timeout();
                 Box::pin(async move {
-                    match timeout(service_timeout, rx.recv()).await {
-                        Ok(res) => {
-                            match res {
-                                Ok(state) => state,
-                                Err(_) => IdleServiceState::Down,
-                            }
+                    let res = if let Some(service_timeout) = service_timeout {
+                        match timeout(service_timeout, rx.recv()).await {
+                            Ok(res) => res,
+                            // the wait timed out
+                            Err(_) => return IdleServiceState::Down,
                         }
-                        // the wait timed out
+                    } else {
+                        rx.recv().await
+                    };
+                    match res {
+                        Ok(state) => state,
                         Err(_) => IdleServiceState::Down,
                     }
                 })
@@ -142,6 +232,23 @@ impl LiveService {
         }
     }
 
+    /// Stop watching the sockets of an on-demand service and the path triggers
+    pub fn stop_activation(&self) {
+        if let Some(activation) = self.activation.take() {
+            activation.abort();
+        }
+        if let Some(path_watch) = self.path_watch.take() {
+            path_watch.abort();
+        }
+    }
+
+    /// Stop running the oneshot on its timer
+    pub fn stop_timer(&self) {
+        if let Some(timer_task) = self.timer_task.take() {
+            timer_task.abort();
+        }
+    }
+
     pub fn update_state(
         &self,
         new: ServiceState,
@@ -149,9 +256,49 @@ impl LiveService {
         self.state.replace(new);
     }
 
-    pub async fn start_service(
+    pub fn is_up(&self) -> bool {
+        *self.state.borrow() == ServiceState::Idle(IdleServiceState::Up)
+    }
+
+    /// Whether the service is up or has been skipped
+    pub fn is_satisfied(&self) -> bool {
+        matches!(*self.state.borrow(), ServiceState::Idle(state) if state.is_satisfied())
+    }
+
+    /// Check the conditions of the service, if any is false the service should
+    /// be skipped
+    pub async fn check_conditions(
         &self,
         logdir: &Path,
+    ) -> bool {
+        let (conditions, env) = match &self.node.service {
+            Service::Longrun(longrun) => (&longrun.options.conditions, &longrun.environment),
+            Service::Oneshot(oneshot) => (&oneshot.options.conditions, &oneshot.environment),
+            Service::Bundle(_) | Service::Virtual(_) => return true,
+        };
+        if conditions.is_empty() {
+            return true;
+        }
+        check_conditions(conditions, env)
+            .with_subscriber(self.logger_subscriber(logdir).1)
+            .await
+    }
+
+    pub fn status(&self) -> ServiceStatus {
+        ServiceStatus {
+            name: self.node.name().to_owned(),
+            state: *self.state.borrow(),
+            provider: self.node.provider.clone(),
+            notify: self.notify.borrow().clone(),
+            health: *self.health.borrow(),
+            timer: *self.timer.borrow(),
+            failure_reason: self.failure_reason.borrow().clone(),
+        }
+    }
+
+    pub async fn start_service(
+        &self,
+        dirs: &Dirs,
         send: mpsc::Sender<Request>,
     ) -> bool {
         match &self.node.service {
@@ -159,9 +306,60 @@ impl LiveService {
                 let (tx, rx) = watch::channel(());
                 // terminate is our channel to ask the supervisor to close the process
                 self.terminate.replace(Some(tx));
-                let (fw_handle, logger) = self.logger_subscriber(logdir);
-                let mut supervisor = Supervisor::new(longrun.clone(), rx, fw_handle);
+                let (fw_handle, logger) = self.logger_subscriber(&dirs.logdir);
+                self.failure_reason.replace(None);
+                let cgroup = self.create_cgroup(&dirs.cgroupdir);
+                if let (Some(cgroup), Some(resources)) = (&cgroup, &longrun.resources) {
+                    cgroup.set_resources(resources);
+                }
+                if matches!(&longrun.socket, Some(socket) if socket.accept) {
+                    // There is no process to supervise, every connection runs a new instance
+                    let sockets = match &self.sockets {
+                        Some(sockets) => sockets.clone(),
+                        None => {
+                            error!("the sockets of {} are not bound", self.node.name());
+                            return false;
+                        }
+                    };
+                    task::spawn_local(
+                        accept_connections(longrun.clone(), sockets, rx, cgroup)
+                            .map(move |res| {
+                                // Keep the log file open until the last instance has exited
+                                drop(fw_handle);
+                                if let Err(err) = res {
+                                    error!("{err:?}");
+                                }
+                            })
+                            .with_subscriber(logger),
+                    );
+                    return true;
+                }
                 async {
+                    let notify_socket = if longrun.run.notify_socket {
+                        match find_credentials(&longrun.run).and_then(|(user, gid)| {
+                            NotifySocket::bind(
+                                dirs.rundir.join("notify").join(self.node.name()),
+                                user.map(|user| user.uid),
+                                gid,
+                            )
+                        }) {
+                            Ok(notify_socket) => Some(notify_socket),
+                            Err(err) => {
+                                error!("{err}");
+                                return false;
+                            }
+                        }
+                    } else {
+                        None
+                    };
+                    let mut supervisor = Supervisor::new(
+                        longrun.clone(),
+                        rx,
+                        fw_handle,
+                        notify_socket,
+                        self.sockets.clone(),
+                        cgroup,
+                    );
                     match supervisor.start().await {
                         Ok(res) => {
                             if res {
@@ -171,6 +369,9 @@ impl LiveService {
                                         error!("{err}");
                                     }
                                 });
+                            } else {
+                                self.failure_reason
+                                    .replace(supervisor.failure_reason.take());
                             }
                             res
                         }
@@ -184,12 +385,13 @@ impl LiveService {
                 .await
             }
             Service::Oneshot(oneshot) => {
-                run_short_lived_script(&oneshot.start, &oneshot.environment)
-                    .with_subscriber(self.logger_subscriber(logdir).1)
+                run_short_lived_script(&oneshot.start, &oneshot.environment, None)
+                    .with_subscriber(self.logger_subscriber(&dirs.logdir).1)
                     .await
                     .unwrap()
             }
-            Service::Bundle(_) | Service::Virtual(_) => todo!(),
+            // The members and the provider have already been started as dependencies
+            Service::Bundle(_) | Service::Virtual(_) => true,
         }
     }
 
@@ -208,7 +410,7 @@ impl LiveService {
             }
             Service::Oneshot(oneshot) => {
                 if let Some(stop_script) = &oneshot.stop {
-                    let res = run_short_lived_script(stop_script, &oneshot.environment)
+                    let res = run_short_lived_script(stop_script, &oneshot.environment, None)
                         .with_subscriber(self.logger_subscriber(logdir).1)
                         .await;
                     if let Err(err) = res {
@@ -216,7 +418,48 @@ impl LiveService {
                     }
                 }
             }
-            Service::Bundle(_) | Service::Virtual(_) => todo!(),
+            // The members of a bundle are stopped by LiveServiceGraph, while stopping
+            // a virtual service doesn't stop its provider
+            Service::Bundle(_) | Service::Virtual(_) => {}
+        }
+    }
+
+    /// Apply the new limits of the service to the cgroup of the running
+    /// process. The other changes only take effect when it is restarted
+    pub fn update_resources(
+        &self,
+        old: &LiveService,
+        cgroupdir: &Path,
+    ) {
+        if let (Service::Longrun(new), Service::Longrun(old)) =
+            (&self.node.service, &old.node.service)
+        {
+            let path = cgroupdir.join(self.node.name());
+            if new.resources != old.resources && !cgroupdir.as_os_str().is_empty() && path.exists()
+            {
+                set_resources(&path, &new.resources.clone().unwrap_or_default());
+            }
+        }
+    }
+
+    /// Create the cgroup of the service. The processes are tracked by their
+    /// process group when cgroups v2 are not available
+    fn create_cgroup(
+        &self,
+        cgroupdir: &Path,
+    ) -> Option<Cgroup> {
+        if cgroupdir.as_os_str().is_empty() {
+            return None;
+        }
+        match Cgroup::create(cgroupdir, self.node.name()) {
+            Ok(cgroup) => Some(cgroup),
+            Err(err) => {
+                warn!(
+                    "{err:?}, the processes of {} are tracked by their process group",
+                    self.node.name()
+                );
+                None
+            }
         }
     }
 
@@ -257,3 +500,12 @@ impl LiveService {
         )
     }
 }
+
+// The tasks hold the sockets and would keep starting a service that has been
+// removed or replaced
+impl Drop for LiveService {
+    fn drop(&mut self) {
+        self.stop_activation();
+        self.stop_timer();
+    }
+}
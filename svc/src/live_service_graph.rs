@@ -1,7 +1,9 @@
 use std::{
     self,
+    cell::RefCell,
     collections::{
         HashMap,
+        HashSet,
         TryReserveError,
     },
     io,
@@ -9,6 +11,7 @@ use std::{
 
 use async_recursion::async_recursion;
 use async_scoped_local::TokioScope;
+use futures::future::join_all;
 use indexmap::IndexMap;
 use rinit_ipc::{
     request_error::{
@@ -16,42 +19,68 @@ use rinit_ipc::{
         DependencyGraphNotFoundSnafu,
         DependentsStillRunningSnafu,
         LogicError,
+        NoRequiredServiceUpSnafu,
         RequestError,
+        RequiredServiceFailedToStartSnafu,
         RunLevelMustMatchSnafu,
+        ServiceFailedSnafu,
         ServiceFailedToStartSnafu,
         ServiceNotFoundSnafu,
+        UnknownRunLevelSnafu,
     },
     Request,
+    ServiceStatus,
 };
 use rinit_service::{
     config::Config,
     graph::DependencyGraph,
     service_state::{
+        Health,
         IdleServiceState,
+        NotifyState,
         ServiceState,
+        TimerState,
         TransitioningServiceState,
     },
-    types::RunLevel,
+    types::{
+        Bundle,
+        RunLevel,
+        Service,
+    },
 };
 use snafu::{
     ensure,
+    OptionExt,
     ResultExt,
     Snafu,
 };
-use tokio::sync::mpsc;
+use tokio::{
+    sync::mpsc,
+    task,
+};
 use tokio_stream::StreamExt;
 use tracing::{
+    error,
     info,
+    instrument::WithSubscriber,
     trace,
     warn,
 };
 
-use crate::live_service::LiveService;
+use crate::{
+    live_service::LiveService,
+    path_watcher::watch_paths,
+    supervision::enable_controllers,
+    timer::run_timer,
+};
 
 pub struct LiveServiceGraph {
     pub live_services: IndexMap<String, LiveService>,
     config: Config,
     send: mpsc::Sender<Request>,
+    // The runlevels that have been started, the services added to them on
+    // reload are watched like the ones started at boot
+    started_runlevels: RefCell<HashSet<RunLevel>>,
 }
 
 #[derive(Snafu, Debug)]
@@ -117,6 +146,11 @@ impl LiveServiceGraph {
         config: Config,
         send: mpsc::Sender<Request>,
     ) -> Result<Self> {
+        if !config.dirs.cgroupdir.as_os_str().is_empty() {
+            if let Err(err) = enable_controllers(&config.dirs.cgroupdir) {
+                warn!("{err:?}, the limits in [resources] won't be enforced");
+            }
+        }
         let graph_file = config.dirs.graph_filename();
         let graph: DependencyGraph = if graph_file.exists() {
             serde_json::from_slice(&std::fs::read(graph_file).with_context(|_| ReadGraphSnafu)?)
@@ -128,35 +162,45 @@ impl LiveServiceGraph {
             live_services: graph
                 .nodes
                 .into_iter()
-                .map(|(name, node)| (name, LiveService::new(node)))
+                .map(|(name, node)| {
+                    let mut live_service = LiveService::new(node);
+                    live_service.bind_sockets();
+                    (name, live_service)
+                })
                 .collect(),
             config,
             send,
+            started_runlevels: RefCell::new(HashSet::new()),
         })
     }
 
     pub async fn start_all_services(
         &self,
-        runlevel: RunLevel,
+        runlevel: &RunLevel,
     ) -> Vec<Result<()>> {
+        let services = self
+            .live_services
+            .values()
+            .filter(|live_service| {
+                live_service.node.service.should_start()
+                    && live_service.node.service.runlevel() == runlevel
+            })
+            .collect();
+        self.started_runlevels.borrow_mut().insert(runlevel.clone());
+        self.live_services
+            .values()
+            .filter(|live_service| live_service.node.service.runlevel() == runlevel)
+            .for_each(|live_service| self.watch_service(live_service));
         // This is unsafe because the futures may outlive the current scope
         // We wait on them afterwards and we know that self will outlive them
         // so it's safe to use it
         let (_, futures) = unsafe {
             TokioScope::scope_and_collect(|s| {
-                self.live_services.iter().for_each(|(_, live_service)| {
-                    s.spawn(async move {
-                        if live_service.node.service.should_start()
-                            && live_service.node.service.runlevel() == runlevel
-                        {
-                            // TODO: Generate an order of the services to start and use
-                            // start_service_impl
-                            self.start_service(live_service).await
-                        } else {
-                            Ok(())
-                        }
+                self.start_order(services)
+                    .into_iter()
+                    .for_each(|live_service| {
+                        s.spawn(self.start_service(live_service));
                     });
-                });
             })
         }
         .await;
@@ -171,49 +215,134 @@ impl LiveServiceGraph {
             .collect()
     }
 
+    /// Switch to the runlevel passed, like an isolate operation: the services
+    /// in the runlevels coming after it are stopped, in reverse order, while
+    /// the ones in the runlevels up to it are started. Returns whether all the
+    /// services have been started successfully
+    pub async fn switch_runlevel(
+        &self,
+        target: &RunLevel,
+    ) -> Result<bool> {
+        let runlevels = self.config.runlevels();
+        let position = runlevels
+            .iter()
+            .position(|runlevel| runlevel == target)
+            .with_context(|| {
+                UnknownRunLevelSnafu {
+                    runlevel: target.to_string(),
+                }
+            })?;
+        for runlevel in runlevels[position + 1..].iter().rev() {
+            self.stop_all_services(runlevel).await;
+        }
+        let mut success = true;
+        for runlevel in &runlevels[..=position] {
+            success &= self
+                .start_all_services(runlevel)
+                .await
+                .iter()
+                .all(Result::is_ok);
+        }
+        Ok(success)
+    }
+
+    /// Stop the services of all the runlevels, in reverse order
+    pub async fn stop_all_runlevels(&self) {
+        for runlevel in self.config.runlevels().iter().rev() {
+            self.stop_all_services(runlevel).await;
+        }
+    }
+
+    // Spawn the services ordered before the others first, so that they are
+    // already starting when the services ordered after them check their state
+    fn start_order<'a>(
+        &'a self,
+        services: Vec<&'a LiveService>,
+    ) -> Vec<&'a LiveService> {
+        fn visit<'a>(
+            graph: &'a LiveServiceGraph,
+            live_service: &'a LiveService,
+            services: &HashSet<&str>,
+            visited: &mut HashSet<&'a str>,
+            order: &mut Vec<&'a LiveService>,
+        ) {
+            if !visited.insert(live_service.node.name()) {
+                return;
+            }
+            for service in graph.get_ordered_before(live_service) {
+                if services.contains(service.node.name()) {
+                    visit(graph, service, services, visited, order);
+                }
+            }
+            order.push(live_service);
+        }
+
+        let names = services
+            .iter()
+            .map(|live_service| live_service.node.name())
+            .collect();
+        let mut visited = HashSet::new();
+        let mut order = Vec::with_capacity(services.len());
+        for live_service in services {
+            visit(self, live_service, &names, &mut visited, &mut order);
+        }
+        order
+    }
+
     #[async_recursion(?Send)]
     pub async fn start_service(
         &self,
         live_service: &LiveService,
     ) -> Result<()> {
-        let mut state = *live_service.state.borrow();
-        if state == ServiceState::Idle(IdleServiceState::Up) {
+        if live_service.is_satisfied() {
             return Ok(());
         }
+        let mut state = *live_service.state.borrow();
         if matches!(state, ServiceState::Transitioning(_)) {
             state = ServiceState::Idle(live_service.wait_idle_state().await);
         }
+        ensure!(
+            state != ServiceState::Idle(IdleServiceState::Failed),
+            ServiceFailedSnafu {
+                service: live_service.node.name(),
+            }
+        );
         // If the service is down
         if state == ServiceState::Idle(IdleServiceState::Down) {
             trace!("starting service {}", live_service.node.name());
             live_service.state.replace(ServiceState::Transitioning(
                 TransitioningServiceState::Starting,
             ));
-            self.start_dependencies(live_service).await?;
-            self.wait_on_deps_starting(live_service).await?;
+            if let Err(err) = self.start_dependencies(live_service).await {
+                // Do not leave the service in the starting state
+                self.send_state_update(live_service, IdleServiceState::Down)
+                    .await;
+                return Err(err);
+            }
+            self.wait_on_ordered_before(live_service).await;
 
-            // Call the closure and let the new subscriber collect all the tracings
-            let success = live_service
-                .start_service(&self.config.dirs.logdir, self.send.clone())
-                .await;
-            if let Err(err) = self
-                .send
-                .send(Request::UpdateServiceStatus(
-                    live_service.node.name().to_string(),
-                    if success {
-                        IdleServiceState::Up
-                    } else {
-                        IdleServiceState::Down
-                    },
-                ))
+            let new_state = if live_service
+                .check_conditions(&self.config.dirs.logdir)
                 .await
             {
-                warn!("Could not update service status: {err}");
-            }
+                // Call the closure and let the new subscriber collect all the tracings
+                if live_service
+                    .start_service(&self.config.dirs, self.send.clone())
+                    .await
+                {
+                    IdleServiceState::Up
+                } else {
+                    IdleServiceState::Down
+                }
+            } else {
+                info!("skipping service {}", live_service.node.name());
+                IdleServiceState::Skipped
+            };
+            self.send_state_update(live_service, new_state).await;
         }
         let state = live_service.wait_idle_state().await;
         ensure!(
-            state == IdleServiceState::Up,
+            state.is_satisfied(),
             ServiceFailedToStartSnafu {
                 service: live_service.node.name().to_string(),
             },
@@ -222,13 +351,14 @@ impl LiveServiceGraph {
         Ok(())
     }
 
+    /// Start all the dependencies in parallel, this includes the members of a
+    /// bundle
     async fn start_dependencies(
         &self,
         live_service: &LiveService,
     ) -> Result<()> {
         let futures: Vec<_> = live_service
             .node
-            .service
             .dependencies()
             .iter()
             .map(async move |dep| -> Result<()> {
@@ -241,22 +371,95 @@ impl LiveServiceGraph {
                 }
             })
             .collect();
-        for future in futures {
-            future.await?;
+        join_all(futures)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        self.wait_on_deps_starting(live_service).await?;
+        self.start_requirements(live_service).await
+    }
+
+    /// Start all the services in requires and the first of the services in
+    /// requires_one that can be started, unless one of them is already up
+    async fn start_requirements(
+        &self,
+        live_service: &LiveService,
+    ) -> Result<()> {
+        let futures: Vec<_> = live_service
+            .node
+            .service
+            .requires()
+            .iter()
+            .map(async move |required| -> Result<()> {
+                let res = match self.get_service(required) {
+                    Ok(required_service) => self.start_service(required_service).await,
+                    Err(err) => Err(err),
+                };
+                if res.is_err() {
+                    RequiredServiceFailedToStartSnafu {
+                        service: live_service.node.name(),
+                        required,
+                    }
+                    .fail()?;
+                }
+                Ok(())
+            })
+            .collect();
+        join_all(futures)
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        let alternatives: Vec<&LiveService> = live_service
+            .node
+            .service
+            .requires_one()
+            .iter()
+            .filter_map(|alternative| self.live_services.get(alternative))
+            .collect();
+        if live_service.node.service.requires_one().is_empty()
+            || alternatives
+                .iter()
+                .any(|alternative| alternative.is_satisfied())
+        {
+            return Ok(());
         }
+        for alternative in alternatives {
+            if self.start_service(alternative).await.is_ok() {
+                return Ok(());
+            }
+        }
+        NoRequiredServiceUpSnafu {
+            service: live_service.node.name(),
+            alternatives: live_service.node.service.requires_one().to_owned(),
+        }
+        .fail()?
+    }
 
-        Ok(())
+    // Services ordered before this one are not started, only waited on when
+    // they are already starting
+    async fn wait_on_ordered_before(
+        &self,
+        live_service: &LiveService,
+    ) {
+        for service in self.get_ordered_before(live_service) {
+            let state = *service.state.borrow();
+            if state == ServiceState::Transitioning(TransitioningServiceState::Starting) {
+                service.wait_idle_state().await;
+            }
+        }
     }
 
     async fn wait_on_deps_starting(
         &self,
         live_service: &LiveService,
     ) -> Result<()> {
-        for dep in live_service.node.service.dependencies() {
+        for dep in live_service.node.dependencies() {
             let dep_service = &self.live_services[dep];
             let state = dep_service.wait_idle_state().await;
             ensure!(
-                state == IdleServiceState::Up,
+                state.is_satisfied(),
                 DependencyFailedToStartSnafu {
                     service: live_service.node.name().to_string(),
                     dependency: dep.to_string(),
@@ -267,33 +470,267 @@ impl LiveServiceGraph {
         Ok(())
     }
 
+    #[async_recursion(?Send)]
     pub async fn stop_service(
         &self,
         live_service: &LiveService,
     ) -> Result<()> {
         let dependents = self.get_dependents(live_service);
         Self::wait_on_dependents_stopping(live_service.node.name(), &dependents).await?;
+        // The services requiring this one can't run without it, stop them first.
+        // get_requirers skips the ones that are already stopping or idle
+        for requirer in self.get_requirers(live_service) {
+            self.stop_service(requirer).await?;
+        }
+        self.stop_service_impl(live_service).await
+    }
+
+    /// Stop a service without stopping its requirers, which are stopped by
+    /// stop_all_services on their own
+    async fn stop_service_in_runlevel(
+        &self,
+        live_service: &LiveService,
+    ) -> Result<()> {
+        let dependents = self.get_dependents(live_service);
+        Self::wait_on_dependents_stopping(live_service.node.name(), &dependents).await?;
+        self.stop_service_impl(live_service).await
+    }
+
+    /// Watch the sockets, the timer and the path triggers of a service, which
+    /// start it on their own
+    fn watch_service(
+        &self,
+        live_service: &LiveService,
+    ) {
+        let service = &live_service.node.service;
+        if service.is_on_demand() {
+            self.watch_activation(live_service);
+        }
+        if service.timer().is_some() {
+            self.watch_timer(live_service);
+        }
+        if service.path_triggers().is_some() {
+            self.watch_path_triggers(live_service);
+        }
+    }
+
+    /// Watch a service added or replaced on reload, when its runlevel has
+    /// already been started
+    fn rewatch_service(
+        &self,
+        live_service: &LiveService,
+    ) {
+        if self
+            .started_runlevels
+            .borrow()
+            .contains(live_service.node.service.runlevel())
+        {
+            self.watch_service(live_service);
+        }
+    }
+
+    /// Start an on-demand service once there is activity on its sockets
+    fn watch_activation(
+        &self,
+        live_service: &LiveService,
+    ) {
+        let sockets = match &live_service.sockets {
+            Some(sockets) => sockets.clone(),
+            None => return,
+        };
+        if matches!(&*live_service.activation.borrow(), Some(handle) if !handle.is_finished()) {
+            return;
+        }
+        let service = live_service.node.name().to_owned();
+        let runlevel = live_service.node.service.runlevel().clone();
+        let send = self.send.clone();
+        live_service
+            .activation
+            .replace(Some(task::spawn_local(async move {
+                if let Err(err) = sockets.wait_activity().await {
+                    warn!("{err:?}");
+                    return;
+                }
+                info!("Activating service {service}");
+                if let Err(err) = send.send(Request::StartService { service, runlevel }).await {
+                    warn!("Could not start service: {err}");
+                }
+            })));
+    }
+
+    /// Run the start script of a oneshot every time its timer elapses
+    fn watch_timer(
+        &self,
+        live_service: &LiveService,
+    ) {
+        let oneshot = match &live_service.node.service {
+            Service::Oneshot(oneshot) => oneshot.clone(),
+            _ => return,
+        };
+        if matches!(&*live_service.timer_task.borrow(), Some(handle) if !handle.is_finished()) {
+            return;
+        }
+        let timer_file = self.config.dirs.timer_filename(live_service.node.name());
+        let (fw_handle, logger) = live_service.logger_subscriber(&self.config.dirs.logdir);
+        let send = self.send.clone();
+        live_service.timer_task.replace(Some(task::spawn_local(
+            async move {
+                if let Err(err) = run_timer(oneshot, timer_file, send).await {
+                    error!("{err:?}");
+                }
+                drop(fw_handle);
+            }
+            .with_subscriber(logger.finish()),
+        )));
+    }
+
+    /// Start the service every time one of its path triggers fires
+    fn watch_path_triggers(
+        &self,
+        live_service: &LiveService,
+    ) {
+        let path = match live_service.node.service.path_triggers() {
+            Some(path) => path.clone(),
+            None => return,
+        };
+        if matches!(&*live_service.path_watch.borrow(), Some(handle) if !handle.is_finished()) {
+            return;
+        }
+        let service = live_service.node.name().to_owned();
+        let send = self.send.clone();
+        live_service
+            .path_watch
+            .replace(Some(task::spawn_local(async move {
+                if let Err(err) = watch_paths(service, path, send).await {
+                    error!("{err:?}");
+                }
+            })));
+    }
+
+    /// Start a service because one of its path triggers fired. A oneshot that
+    /// has already run is run again
+    pub async fn trigger_service(
+        &self,
+        name: &str,
+    ) -> Result<()> {
+        let live_service = self.get_service(name)?;
+        if matches!(live_service.node.service, Service::Oneshot(_)) && live_service.is_up() {
+            live_service.update_state(ServiceState::Idle(IdleServiceState::Down));
+        }
+        self.start_service(live_service).await
+    }
+
+    #[async_recursion(?Send)]
+    async fn stop_service_impl(
+        &self,
+        live_service: &LiveService,
+    ) -> Result<()> {
+        // A stopped service must not be activated again
+        live_service.stop_activation();
+        let state = *live_service.state.borrow();
+        match state {
+            // The process of a failed service is not running anymore
+            ServiceState::Idle(IdleServiceState::Down | IdleServiceState::Failed) => return Ok(()),
+            // There is nothing running to stop
+            ServiceState::Idle(IdleServiceState::Skipped) => {
+                self.send_state_update(live_service, IdleServiceState::Down)
+                    .await;
+                return Ok(());
+            }
+            _ => {}
+        }
         live_service.state.replace(ServiceState::Transitioning(
             TransitioningServiceState::Stopping,
         ));
+        if let Service::Bundle(bundle) = &live_service.node.service {
+            if let Err(err) = self.stop_bundle_members(bundle).await {
+                // Some of the members are still running
+                self.send_state_update(live_service, IdleServiceState::Up)
+                    .await;
+                return Err(err);
+            }
+        }
         live_service.stop_service(&self.config.dirs.logdir).await;
+        self.send_state_update(live_service, IdleServiceState::Down)
+            .await;
+        Ok(())
+    }
+
+    /// Stop the members of a bundle in reverse dependency order: a member is
+    /// only stopped after all the members depending on it are down
+    async fn stop_bundle_members(
+        &self,
+        bundle: &Bundle,
+    ) -> Result<()> {
+        let mut members: Vec<&LiveService> = bundle
+            .options
+            .contents
+            .iter()
+            .map(|member| &self.live_services[member])
+            .collect();
+        while !members.is_empty() {
+            let (to_stop, remaining): (Vec<_>, Vec<_>) =
+                members.iter().copied().partition(|member| {
+                    !members.iter().any(|other| {
+                        other
+                            .node
+                            .dependencies()
+                            .iter()
+                            .any(|dep| dep == member.node.name())
+                    })
+                });
+            let futures: Vec<_> = to_stop
+                .iter()
+                .map(async move |member| -> Result<()> {
+                    // The bundle is a dependent of its members, skip it
+                    let dependents: Vec<_> = self
+                        .get_dependents(member)
+                        .into_iter()
+                        .filter(|dependent| dependent.node.name() != bundle.name)
+                        .collect();
+                    // This also waits on the members stopped in the previous iteration
+                    Self::wait_on_dependents_stopping(member.node.name(), &dependents).await?;
+                    self.stop_service_impl(member).await
+                })
+                .collect();
+            join_all(futures)
+                .await
+                .into_iter()
+                .collect::<Result<Vec<_>>>()?;
+            members = remaining;
+        }
+
+        Ok(())
+    }
+
+    async fn send_state_update(
+        &self,
+        live_service: &LiveService,
+        state: IdleServiceState,
+    ) {
         if let Err(err) = self
             .send
             .send(Request::UpdateServiceStatus(
                 live_service.node.name().to_string(),
-                IdleServiceState::Down,
+                state,
             ))
             .await
         {
             warn!("Could not update service status: {err}");
         }
-        Ok(())
     }
 
     pub async fn stop_all_services(
         &self,
-        runlevel: RunLevel,
+        runlevel: &RunLevel,
     ) {
+        self.started_runlevels.borrow_mut().remove(runlevel);
+        // The timers keep running when a oneshot is stopped on its own, not when
+        // its runlevel is stopped
+        self.live_services
+            .values()
+            .filter(|live_service| live_service.node.service.runlevel() == runlevel)
+            .for_each(LiveService::stop_timer);
         // This is unsafe because the futures may outlive the current scope
         // We wait on them afterwards and we know that self will outlive them
         // so it's safe to use it
@@ -302,16 +739,23 @@ impl LiveServiceGraph {
                 for (service, live_service) in &self.live_services {
                     s.spawn(async move {
                         if live_service.node.service.runlevel() == runlevel {
-                            let dependents = self.get_dependents(live_service);
-                            for dependent in dependents {
-                                // Wait until the dependent is down
-                                // TODO: Log
-                                while let Ok(IdleServiceState::Up) =
-                                    dependent.tx.subscribe().recv().await
-                                {
-                                }
+                            // Wait until the dependents, the requirers and the services
+                            // ordered after this one are down. The requirers in the other
+                            // runlevels are not part of this sweep
+                            for service in self
+                                .get_dependents(live_service)
+                                .into_iter()
+                                .chain(self.get_ordered_after(live_service))
+                                .chain(self.get_requirers(live_service).into_iter().filter(
+                                    |requirer| requirer.node.service.runlevel() == runlevel,
+                                ))
+                            {
+                                Self::wait_until_down(service).await;
+                            }
+                            if let Err(err) = self.stop_service_in_runlevel(live_service).await {
+                                error!("unable to stop service {service}: {err}");
+                                return;
                             }
-                            self.stop_service(live_service).await.unwrap();
 
                             // Self::stop_service only spawn the supervisor, we don't know if the
                             // service has stopped yet. Get the state of each one
@@ -349,6 +793,132 @@ impl LiveServiceGraph {
         }
     }
 
+    /// Get the status of a service. The state of an idle bundle is derived
+    /// from the state of its members
+    pub fn get_status(
+        &self,
+        live_service: &LiveService,
+    ) -> ServiceStatus {
+        let mut status = live_service.status();
+        if let (Service::Bundle(bundle), ServiceState::Idle(_)) =
+            (&live_service.node.service, status.state)
+        {
+            let members_up = bundle
+                .options
+                .contents
+                .iter()
+                .filter(|member| {
+                    matches!(
+                        self.get_status(&self.live_services[*member]).state,
+                        ServiceState::Idle(state) if state.is_satisfied()
+                    )
+                })
+                .count();
+            status.state = ServiceState::Idle(
+                if members_up == 0 {
+                    IdleServiceState::Down
+                } else if members_up == bundle.options.contents.len() {
+                    IdleServiceState::Up
+                } else {
+                    IdleServiceState::PartiallyUp
+                },
+            );
+        }
+        status
+    }
+
+    /// Get the services that are up and would lose their requirements if this
+    /// service went down
+    fn get_requirers(
+        &self,
+        live_service: &LiveService,
+    ) -> Vec<&LiveService> {
+        let name = live_service.node.name();
+        self.live_services
+            .values()
+            .filter(|requirer| requirer.is_up())
+            .filter(|requirer| {
+                let service = &requirer.node.service;
+                service.requires().iter().any(|required| required == name)
+                    || (service
+                        .requires_one()
+                        .iter()
+                        .any(|alternative| alternative == name)
+                        && !service.requires_one().iter().any(|alternative| {
+                            alternative != name
+                                && self
+                                    .live_services
+                                    .get(alternative)
+                                    .is_some_and(LiveService::is_satisfied)
+                        }))
+            })
+            .collect()
+    }
+
+    /// Get the services that are started before this one because of after and
+    /// before, without being its dependencies
+    fn get_ordered_before(
+        &self,
+        live_service: &LiveService,
+    ) -> Vec<&LiveService> {
+        let name = live_service.node.name();
+        live_service
+            .node
+            .service
+            .after()
+            .iter()
+            .filter_map(|service| self.live_services.get(service))
+            .chain(self.live_services.values().filter(|other| {
+                other
+                    .node
+                    .service
+                    .before()
+                    .iter()
+                    .any(|service| service == name)
+            }))
+            .collect()
+    }
+
+    /// Get the services that are started after this one because of after and
+    /// before, without depending on it
+    fn get_ordered_after(
+        &self,
+        live_service: &LiveService,
+    ) -> Vec<&LiveService> {
+        let name = live_service.node.name();
+        live_service
+            .node
+            .service
+            .before()
+            .iter()
+            .filter_map(|service| self.live_services.get(service))
+            .chain(self.live_services.values().filter(|other| {
+                other
+                    .node
+                    .service
+                    .after()
+                    .iter()
+                    .any(|service| service == name)
+            }))
+            .collect()
+    }
+
+    async fn wait_until_down(live_service: &LiveService) {
+        let mut rx = live_service.tx.subscribe();
+        loop {
+            let state = *live_service.state.borrow();
+            if matches!(
+                state,
+                ServiceState::Idle(
+                    IdleServiceState::Down | IdleServiceState::Skipped | IdleServiceState::Failed
+                )
+            ) || rx.recv().await.is_err()
+            {
+                break;
+            }
+        }
+    }
+
     fn get_dependents(
         &self,
         live_service: &LiveService,
@@ -373,8 +943,8 @@ impl LiveServiceGraph {
             })
             .filter_map(|(dependent, state)|
                 match state {
-                    IdleServiceState::Down => None,
-                    IdleServiceState::Up => Some(dependent),
+                    IdleServiceState::Down | IdleServiceState::Skipped | IdleServiceState::Failed => None,
+                    IdleServiceState::Up | IdleServiceState::PartiallyUp => Some(dependent),
                 })
             .map(|live_service| live_service.node.name().to_owned())
             .collect::<Vec<String>>()
@@ -433,8 +1003,10 @@ impl LiveServiceGraph {
             match service.1 {
                 // There is a new service, add it to the graph without starting it
                 (false, true) => {
-                    let new = LiveService::new(dep_graph.nodes.swap_remove(&name).unwrap());
-                    self.live_services.insert(name, new);
+                    let mut new = LiveService::new(dep_graph.nodes.swap_remove(&name).unwrap());
+                    new.bind_sockets();
+                    self.live_services.insert(name.clone(), new);
+                    self.rewatch_service(&self.live_services[&name]);
                     index += index;
                 }
                 // This service is only the live state and not in the new dependency graph
@@ -450,14 +1022,25 @@ impl LiveServiceGraph {
                 }
                 // This service is in both graph, update it now/later
                 (true, true) => {
-                    let new_live_service =
-                        LiveService::new(dep_graph.nodes.swap_remove(&name).unwrap());
                     let state = *self.live_services[&name].state.borrow();
+                    let down = state == ServiceState::Idle(IdleServiceState::Down);
+                    if down {
+                        // The tasks of the old service hold its sockets, they
+                        // must be released before the new ones are bound
+                        self.live_services[&name].stop_activation();
+                        self.live_services[&name].stop_timer();
+                    }
+                    let mut new_live_service =
+                        LiveService::new(dep_graph.nodes.swap_remove(&name).unwrap());
+                    new_live_service.inherit_sockets(&mut self.live_services[&name]);
+                    new_live_service
+                        .update_resources(&self.live_services[&name], &self.config.dirs.cgroupdir);
                     // If a service is already down, just update it with
                     // the new one
-                    if state == ServiceState::Idle(IdleServiceState::Down) {
+                    if down {
                         new_live_service.update_state(state);
                         self.live_services[&name] = new_live_service;
+                        self.rewatch_service(&self.live_services[&name]);
                         // Keep the current state
                     } else {
                         // otherwise, mark it for update. It will be updated by
@@ -479,11 +1062,86 @@ impl LiveServiceGraph {
     ) -> Result<()> {
         info!("Service {name} is {state}");
         let live_service = self.get_service(name)?;
+        let previous = *live_service.state.borrow();
         live_service.update_state(ServiceState::Idle(state));
         live_service.tx.send(state).unwrap();
+        // The process exited on its own, wait for the next connection
+        if previous == ServiceState::Idle(IdleServiceState::Up)
+            && state == IdleServiceState::Down
+            && live_service.node.service.is_on_demand()
+        {
+            self.watch_activation(live_service);
+        }
+        if matches!(state, IdleServiceState::Down | IdleServiceState::Failed) {
+            // Bring down the services that required this one. When the service has
+            // been stopped on request, they have already been stopped
+            for requirer in self.get_requirers(live_service) {
+                let request = Request::StopService {
+                    service: requirer.node.name().to_owned(),
+                    runlevel: requirer.node.service.runlevel().clone(),
+                };
+                let send = self.send.clone();
+                task::spawn_local(async move {
+                    if let Err(err) = send.send(request).await {
+                        warn!("Could not stop service: {err}");
+                    }
+                });
+            }
+        }
+        Ok(())
+    }
+
+    pub fn update_notify_state(
+        &self,
+        name: &str,
+        state: NotifyState,
+    ) -> Result<()> {
+        self.get_service(name)?.notify.replace(state);
+        Ok(())
+    }
+
+    pub fn update_health(
+        &self,
+        name: &str,
+        health: Option<Health>,
+    ) -> Result<()> {
+        self.get_service(name)?.health.replace(health);
+        Ok(())
+    }
+
+    pub fn update_timer(
+        &self,
+        name: &str,
+        state: TimerState,
+    ) -> Result<()> {
+        self.get_service(name)?.timer.replace(Some(state));
+        Ok(())
+    }
+
+    pub fn update_failure_reason(
+        &self,
+        name: &str,
+        reason: Option<String>,
+    ) -> Result<()> {
+        self.get_service(name)?.failure_reason.replace(reason);
         Ok(())
     }
 
+    /// Reset a failed service, so that it can be started again. Return whether
+    /// the service had failed
+    pub fn reset_failed(
+        &self,
+        name: &str,
+    ) -> Result<bool> {
+        let live_service = self.get_service(name)?;
+        let failed = *live_service.state.borrow() == ServiceState::Idle(IdleServiceState::Failed);
+        if failed {
+            info!("Service {name} has been reset");
+            live_service.update_state(ServiceState::Idle(IdleServiceState::Down));
+        }
+        Ok(failed)
+    }
+
     pub fn update_service(
         &mut self,
         name: &str,
@@ -502,6 +1160,7 @@ impl LiveServiceGraph {
                 new_live_service.update_state(ServiceState::Idle(IdleServiceState::Down));
                 *live_service = *new_live_service;
             });
+            self.rewatch_service(self.get_service(name)?);
         }
         Ok(())
     }
@@ -509,7 +1168,7 @@ impl LiveServiceGraph {
     pub fn check_runlevel(
         &self,
         name: &str,
-        runlevel: RunLevel,
+        runlevel: &RunLevel,
     ) -> Result<()> {
         ensure!(
             self.get_service(name)?.node.service.runlevel() == runlevel,
@@ -519,3 +1178,225 @@ impl LiveServiceGraph {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::{
+            Path,
+            PathBuf,
+        },
+        time::Duration,
+    };
+
+    use rinit_service::{
+        dirs::Dirs,
+        types::{
+            Activation,
+            Longrun,
+            Oneshot,
+            PathTrigger,
+            PathTriggers,
+            Script,
+            ScriptEnvironment,
+            ScriptPrefix,
+            ServiceOptions,
+            Socket,
+            SocketAddress,
+            Timer,
+        },
+    };
+    use tokio::time::timeout;
+
+    use super::*;
+
+    fn test_config(dir: &Path) -> Config {
+        Config {
+            dirs: Dirs {
+                datadir: dir.join("data"),
+                libdir: dir.join("lib"),
+                logdir: dir.join("log"),
+                ..Dirs::default()
+            },
+            ..Config::default()
+        }
+    }
+
+    // Write the graph containing the services passed, then reload it
+    async fn reload_with(
+        graph: &mut LiveServiceGraph,
+        services: Vec<Service>,
+    ) {
+        let mut dep_graph = DependencyGraph::new();
+        dep_graph
+            .add_services(
+                services
+                    .iter()
+                    .map(|service| service.name().to_string())
+                    .collect(),
+                services,
+            )
+            .unwrap();
+        let graph_file = graph.config.dirs.graph_filename();
+        std::fs::create_dir_all(graph_file.parent().unwrap()).unwrap();
+        std::fs::write(graph_file, serde_json::to_vec(&dep_graph).unwrap()).unwrap();
+        graph.reload_dependency_graph().await.unwrap();
+    }
+
+    fn timer_oneshot(on_active_sec: u64) -> Service {
+        Service::Oneshot(Oneshot {
+            name: "foo".to_string(),
+            start: Script::new(ScriptPrefix::Sh, "true".to_string()),
+            stop: None,
+            timer: Some(Timer {
+                on_active_sec: Some(on_active_sec),
+                ..Timer::default()
+            }),
+            path: None,
+            options: ServiceOptions::new(),
+            environment: ScriptEnvironment::new(),
+        })
+    }
+
+    // The timer sends its next elapse as soon as it is running
+    async fn next_elapse(recv: &mut mpsc::Receiver<Request>) -> i64 {
+        match timeout(Duration::from_secs(1), recv.recv()).await.unwrap() {
+            Some(Request::UpdateTimer(
+                name,
+                TimerState {
+                    next: Some(next), ..
+                },
+            )) if name == "foo" => next,
+            request => panic!("unexpected request {request:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_with_timer() {
+        let dir = std::env::temp_dir().join(format!("rinit-reload-test-{}", std::process::id()));
+        let (send, mut recv) = mpsc::channel(10);
+        let mut graph = LiveServiceGraph::new(test_config(&dir), send).unwrap();
+        let local_set = task::LocalSet::new();
+        local_set
+            .run_until(async {
+                // The runlevel has already been started when the timer is enabled
+                assert!(graph.switch_runlevel(&RunLevel::Default).await.unwrap());
+                reload_with(&mut graph, vec![timer_oneshot(60)]).await;
+                let next = next_elapse(&mut recv).await;
+
+                // Stopping the oneshot does not stop its timer
+                graph
+                    .stop_service(graph.get_service("foo").unwrap())
+                    .await
+                    .unwrap();
+                assert!(
+                    graph.live_services["foo"]
+                        .timer_task
+                        .borrow()
+                        .as_ref()
+                        .is_some_and(|handle| !handle.is_finished())
+                );
+
+                // The edited timer replaces the old one
+                reload_with(&mut graph, vec![timer_oneshot(120)]).await;
+                assert!(next_elapse(&mut recv).await >= next + 60);
+
+                reload_with(&mut graph, Vec::new()).await;
+                assert!(graph.live_services.is_empty());
+            })
+            .await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn reload_removes_sockets() {
+        let dir =
+            std::env::temp_dir().join(format!("rinit-reload-socket-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("bar.sock");
+        let (send, _recv) = mpsc::channel(10);
+        let mut graph = LiveServiceGraph::new(test_config(&dir), send).unwrap();
+        let local_set = task::LocalSet::new();
+        local_set
+            .run_until(async {
+                assert!(graph.switch_runlevel(&RunLevel::Default).await.unwrap());
+                let mut socket = Socket::new(vec![SocketAddress::UnixStream(path.clone())]);
+                socket.activation = Activation::OnDemand;
+                reload_with(
+                    &mut graph,
+                    vec![Service::Longrun(Longrun {
+                        socket: Some(socket),
+                        ..Longrun::new(
+                            "bar".to_string(),
+                            Script::new(ScriptPrefix::Sh, "true".to_string()),
+                        )
+                    })],
+                )
+                .await;
+                assert!(std::os::unix::net::UnixStream::connect(&path).is_ok());
+
+                // The activation task holds the sockets until it is aborted
+                reload_with(&mut graph, Vec::new()).await;
+                task::yield_now().await;
+                assert!(std::os::unix::net::UnixStream::connect(&path).is_err());
+                assert!(!path.exists());
+            })
+            .await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn path_oneshot(path: PathBuf) -> Service {
+        Service::Oneshot(Oneshot {
+            name: "baz".to_string(),
+            start: Script::new(ScriptPrefix::Sh, "true".to_string()),
+            stop: None,
+            timer: None,
+            path: Some(PathTriggers::new(vec![PathTrigger::Exists(path)])),
+            options: ServiceOptions::new(),
+            environment: ScriptEnvironment::new(),
+        })
+    }
+
+    // Whether the service has been triggered by one of its paths
+    async fn triggered(recv: &mut mpsc::Receiver<Request>) -> bool {
+        match timeout(Duration::from_millis(200), recv.recv()).await {
+            Ok(Some(Request::TriggerService(name))) => name == "baz",
+            Ok(request) => panic!("unexpected request {request:?}"),
+            Err(_) => false,
+        }
+    }
+
+    #[tokio::test]
+    async fn reload_with_path_triggers() {
+        let dir =
+            std::env::temp_dir().join(format!("rinit-reload-path-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (first, second) = (dir.join("first"), dir.join("second"));
+        let (send, mut recv) = mpsc::channel(10);
+        let mut graph = LiveServiceGraph::new(test_config(&dir), send).unwrap();
+        let local_set = task::LocalSet::new();
+        local_set
+            .run_until(async {
+                assert!(graph.switch_runlevel(&RunLevel::Default).await.unwrap());
+                reload_with(&mut graph, vec![path_oneshot(first.clone())]).await;
+                std::fs::write(&first, "").unwrap();
+                assert!(triggered(&mut recv).await);
+                std::fs::remove_file(&first).unwrap();
+
+                // Only the paths of the new [path] section are watched
+                reload_with(&mut graph, vec![path_oneshot(second.clone())]).await;
+                std::fs::write(&first, "").unwrap();
+                assert!(!triggered(&mut recv).await);
+                std::fs::write(&second, "").unwrap();
+                assert!(triggered(&mut recv).await);
+                std::fs::remove_file(&second).unwrap();
+
+                // A removed service is not triggered anymore
+                reload_with(&mut graph, Vec::new()).await;
+                std::fs::write(&second, "").unwrap();
+                assert!(!triggered(&mut recv).await);
+            })
+            .await;
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
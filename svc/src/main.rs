@@ -1,9 +1,12 @@
 #![feature(async_closure)]
 
+pub mod conditions;
 pub mod live_service;
 pub mod live_service_graph;
+pub mod path_watcher;
 pub mod request_handler;
 pub mod supervision;
+pub mod timer;
 
 use std::{
     cell::RefCell,
@@ -0,0 +1,316 @@
+use std::{
+    collections::{
+        HashMap,
+        VecDeque,
+    },
+    fs,
+    os::fd::{
+        AsRawFd,
+        FromRawFd,
+        OwnedFd,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::Duration,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use nix::sys::inotify::{
+    AddWatchFlags,
+    InitFlags,
+    Inotify,
+    InotifyEvent,
+    WatchDescriptor,
+};
+use rinit_ipc::Request;
+use rinit_service::types::{
+    PathTrigger,
+    PathTriggers,
+};
+use tokio::{
+    io::unix::AsyncFd,
+    sync::mpsc,
+    time::{
+        sleep_until,
+        Instant,
+    },
+};
+use tracing::{
+    info,
+    warn,
+};
+
+// The events that change a file or the entries of a directory
+const CHANGED_MASK: AddWatchFlags = AddWatchFlags::IN_CLOSE_WRITE
+    .union(AddWatchFlags::IN_CREATE)
+    .union(AddWatchFlags::IN_DELETE)
+    .union(AddWatchFlags::IN_MOVED_FROM)
+    .union(AddWatchFlags::IN_MOVED_TO)
+    .union(AddWatchFlags::IN_ATTRIB)
+    .union(AddWatchFlags::IN_DELETE_SELF)
+    .union(AddWatchFlags::IN_MOVE_SELF);
+
+fn is_not_empty(path: &Path) -> bool {
+    fs::read_dir(path).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+/// Whether the trigger fires because of an event on the path passed. The
+/// level triggers, i.e. PathExists and DirectoryNotEmpty, are checked when
+/// there is no event
+fn fires(
+    trigger: &PathTrigger,
+    event: Option<(&Path, AddWatchFlags)>,
+) -> bool {
+    let path = trigger.path();
+    // The event happened on the path, on one of its entries or on one of its
+    // ancestors
+    let (related, ancestor) = match event {
+        Some((event_path, _)) => {
+            (
+                event_path == path || event_path.parent() == Some(path),
+                path.starts_with(event_path),
+            )
+        }
+        None => (true, true),
+    };
+    match trigger {
+        PathTrigger::Exists(path) => (related || ancestor) && path.exists(),
+        PathTrigger::DirectoryNotEmpty(path) => (related || ancestor) && is_not_empty(path),
+        PathTrigger::Changed(_) => {
+            related && event.is_some_and(|(_, mask)| mask.intersects(CHANGED_MASK))
+        }
+        PathTrigger::Modified(_) => {
+            related
+                && event.is_some_and(|(_, mask)| {
+                    mask.intersects(CHANGED_MASK | AddWatchFlags::IN_MODIFY)
+                })
+        }
+    }
+}
+
+/// Watch the paths of a service with inotify
+pub struct PathWatcher {
+    inotify: AsyncFd<Inotify>,
+    // Inotify does not close its file descriptor, do it once it has been
+    // deregistered from the runtime
+    _fd: OwnedFd,
+    triggers: Vec<PathTrigger>,
+    watches: HashMap<WatchDescriptor, PathBuf>,
+}
+
+impl PathWatcher {
+    pub fn new(triggers: Vec<PathTrigger>) -> Result<Self> {
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .context("unable to initialize inotify")?;
+        let fd = unsafe { OwnedFd::from_raw_fd(inotify.as_raw_fd()) };
+        let mut watcher = Self {
+            inotify: AsyncFd::new(inotify).context("unable to watch the inotify descriptor")?,
+            _fd: fd,
+            triggers,
+            watches: HashMap::new(),
+        };
+        watcher.add_watches()?;
+        Ok(watcher)
+    }
+
+    // Watch the paths that exist and the nearest existing ancestor of the ones
+    // that do not. Called again after every event, since paths may have been
+    // created or removed
+    fn add_watches(&mut self) -> Result<()> {
+        let mut masks: HashMap<&Path, AddWatchFlags> = HashMap::new();
+        for trigger in &self.triggers {
+            let path = trigger.path();
+            let mask = match trigger {
+                PathTrigger::Modified(_) => CHANGED_MASK | AddWatchFlags::IN_MODIFY,
+                PathTrigger::Exists(_)
+                | PathTrigger::Changed(_)
+                | PathTrigger::DirectoryNotEmpty(_) => CHANGED_MASK,
+            };
+            if !matches!(trigger, PathTrigger::Exists(_)) && path.exists() {
+                *masks.entry(path).or_insert(AddWatchFlags::empty()) |= mask;
+            }
+            if let Some(ancestor) = path.ancestors().skip(1).find(|ancestor| ancestor.is_dir()) {
+                *masks.entry(ancestor).or_insert(AddWatchFlags::empty()) |= mask;
+            }
+        }
+        let inotify = *self.inotify.get_ref();
+        let mut watches = HashMap::new();
+        for (path, mask) in masks {
+            match inotify.add_watch(path, mask) {
+                Ok(wd) => {
+                    watches.insert(wd, path.to_path_buf());
+                }
+                // The path has been removed in the meantime, the next event
+                // will tell us
+                Err(nix::errno::Errno::ENOENT) => {}
+                Err(err) => return Err(err).with_context(|| format!("unable to watch {path:?}")),
+            }
+        }
+        for wd in self.watches.keys() {
+            if !watches.contains_key(wd) {
+                // The watch might have been removed by the kernel already
+                let _ = inotify.rm_watch(*wd);
+            }
+        }
+        self.watches = watches;
+        Ok(())
+    }
+
+    /// Check the level triggers, without waiting for an event
+    pub fn check(&self) -> bool {
+        self.triggers.iter().any(|trigger| {
+            matches!(
+                trigger,
+                PathTrigger::Exists(_) | PathTrigger::DirectoryNotEmpty(_)
+            ) && fires(trigger, None)
+        })
+    }
+
+    fn event_fires(
+        &self,
+        event: &InotifyEvent,
+    ) -> bool {
+        // Some events have been lost, check everything again
+        if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+            return self.check();
+        }
+        let path = match self.watches.get(&event.wd) {
+            Some(path) => {
+                match &event.name {
+                    Some(name) => path.join(name),
+                    None => path.clone(),
+                }
+            }
+            None => return false,
+        };
+        self.triggers
+            .iter()
+            .any(|trigger| fires(trigger, Some((&path, event.mask))))
+    }
+
+    /// Wait until one of the triggers fires
+    pub async fn wait_trigger(&mut self) -> Result<()> {
+        loop {
+            let mut guard = self
+                .inotify
+                .readable()
+                .await
+                .context("unable to wait for inotify events")?;
+            let events = match guard.try_io(|inotify| {
+                inotify
+                    .get_ref()
+                    .read_events()
+                    .map_err(std::io::Error::from)
+            }) {
+                Ok(events) => events.context("unable to read inotify events")?,
+                Err(_would_block) => continue,
+            };
+            drop(guard);
+            let fired = events.iter().any(|event| self.event_fires(event));
+            self.add_watches()?;
+            if fired {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Start the service every time one of its path triggers fires, at most
+/// trigger_limit_burst times every trigger_limit_interval_sec seconds
+pub async fn watch_paths(
+    service: String,
+    path: PathTriggers,
+    send: mpsc::Sender<Request>,
+) -> Result<()> {
+    let mut watcher = PathWatcher::new(path.triggers)?;
+    let interval = Duration::from_secs(path.trigger_limit_interval_sec);
+    let mut fired: VecDeque<Instant> = VecDeque::new();
+    let mut pending = watcher.check();
+    loop {
+        if !pending {
+            watcher.wait_trigger().await?;
+        }
+        pending = false;
+
+        let now = Instant::now();
+        while fired
+            .front()
+            .is_some_and(|instant| now.duration_since(*instant) >= interval)
+        {
+            fired.pop_front();
+        }
+        if fired.len() >= path.trigger_limit_burst as usize {
+            warn!("Service {service} has been triggered too often, waiting");
+            sleep_until(fired.pop_front().unwrap() + interval).await;
+        }
+        fired.push_back(Instant::now());
+
+        info!("Path trigger of {service} fired");
+        if let Err(err) = send.send(Request::TriggerService(service.clone())).await {
+            warn!("Could not trigger service: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn path_triggers() {
+        let dir = std::env::temp_dir().join(format!("rinit-path-test-{}", std::process::id()));
+        let spool = dir.join("spool");
+        let ready = dir.join("ready");
+        fs::create_dir_all(&dir).unwrap();
+        let mut watcher = PathWatcher::new(vec![
+            PathTrigger::Exists(ready.clone()),
+            PathTrigger::DirectoryNotEmpty(spool.clone()),
+        ])
+        .unwrap();
+        assert!(!watcher.check());
+
+        // The spool directory is created empty, then a file is added to it
+        fs::create_dir(&spool).unwrap();
+        let wait = tokio::time::timeout(Duration::from_millis(100), watcher.wait_trigger()).await;
+        assert!(wait.is_err());
+        fs::write(spool.join("job"), "").unwrap();
+        tokio::time::timeout(Duration::from_millis(500), watcher.wait_trigger())
+            .await
+            .unwrap()
+            .unwrap();
+
+        fs::write(&ready, "").unwrap();
+        tokio::time::timeout(Duration::from_millis(500), watcher.wait_trigger())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(watcher.check());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn changed_trigger() {
+        let trigger = PathTrigger::Changed(PathBuf::from("/etc/foo.conf"));
+        assert!(fires(
+            &trigger,
+            Some((Path::new("/etc/foo.conf"), AddWatchFlags::IN_CLOSE_WRITE))
+        ));
+        assert!(!fires(
+            &trigger,
+            Some((Path::new("/etc/foo.conf"), AddWatchFlags::IN_MODIFY))
+        ));
+        assert!(!fires(
+            &trigger,
+            Some((Path::new("/etc/bar.conf"), AddWatchFlags::IN_CLOSE_WRITE))
+        ));
+        assert!(fires(
+            &PathTrigger::Modified(PathBuf::from("/etc/foo.conf")),
+            Some((Path::new("/etc/foo.conf"), AddWatchFlags::IN_MODIFY))
+        ));
+    }
+}
@@ -9,10 +9,7 @@ use rinit_ipc::{
     Reply,
     Request,
 };
-use rinit_service::service_state::{
-    IdleServiceState,
-    ServiceState,
-};
+use rinit_service::service_state::IdleServiceState;
 use tokio::{
     net::UnixStream,
     sync::{
@@ -103,15 +100,11 @@ impl RequestHandler {
                     .map(|(_, live_service)| live_service)
                     .map(Result::Ok)
                     .collect();
+                let graph = &graph;
                 let states = stream::iter(services)
                     .then(async move |res| {
                         match res {
-                            Ok(live_service) => {
-                                Ok((
-                                    live_service.node.name().to_owned(),
-                                    *live_service.state.borrow(),
-                                ))
-                            }
+                            Ok(live_service) => Ok(graph.get_status(live_service)),
                             Err(err) => Err(err),
                         }
                     })
@@ -122,29 +115,33 @@ impl RequestHandler {
             Request::ServiceStatus(service) => {
                 let state = graph.get_service(&service)?.wait_idle_state();
                 drop(graph);
-                Reply::ServiceState(service.clone(), ServiceState::Idle(state.await))
+                state.await;
+                let graph = self.graph.read().await;
+                Reply::ServiceState(graph.get_status(graph.get_service(&service)?))
             }
             Request::StartService { service, runlevel } => {
-                graph.check_runlevel(&service, runlevel)?;
+                graph.check_runlevel(&service, &runlevel)?;
                 graph.start_service(graph.get_service(&service)?).await?;
                 let state = graph.get_service(&service)?.wait_idle_state();
                 drop(graph);
-                Reply::Success(state.await == IdleServiceState::Up)
+                Reply::Success(state.await.is_satisfied())
+            }
+            Request::TriggerService(service) => {
+                graph.trigger_service(&service).await?;
+                Reply::Empty
             }
             Request::StopService { service, runlevel } => {
-                graph.check_runlevel(&service, runlevel)?;
+                graph.check_runlevel(&service, &runlevel)?;
                 graph.stop_service(graph.get_service(&service)?).await?;
                 let state = graph.get_service(&service)?.wait_idle_state();
                 drop(graph);
                 Reply::Success(state.await == IdleServiceState::Down)
             }
             Request::StartAllServices => {
+                // Start every runlevel up to default
                 graph
-                    .start_all_services(rinit_service::types::RunLevel::Boot)
-                    .await;
-                graph
-                    .start_all_services(rinit_service::types::RunLevel::Default)
-                    .await;
+                    .switch_runlevel(&rinit_service::types::RunLevel::Default)
+                    .await?;
                 Reply::Empty
             }
             // This request can be generated by rctl or by sending a SIGTERM/SIGINT
@@ -153,20 +150,35 @@ impl RequestHandler {
                 if let Err(err) = self.stop_ipc.send(true) {
                     error!("could not stop listening on IPC socket: {err}");
                 }
-                graph
-                    .stop_all_services(rinit_service::types::RunLevel::Default)
-                    .await;
-                graph
-                    .stop_all_services(rinit_service::types::RunLevel::Boot)
-                    .await;
+                graph.stop_all_runlevels().await;
                 Reply::Empty
             }
+            Request::SwitchRunLevel(runlevel) => {
+                Reply::Success(graph.switch_runlevel(&runlevel).await?)
+            }
+            Request::ResetFailedService(service) => Reply::Success(graph.reset_failed(&service)?),
             Request::ReloadGraph => {
                 drop(graph);
                 let mut graph = self.graph.write().await;
                 graph.reload_dependency_graph().await?;
                 Reply::Empty
             }
+            Request::UpdateNotifyState(name, state) => {
+                graph.update_notify_state(&name, state)?;
+                Reply::Empty
+            }
+            Request::UpdateHealth(name, health) => {
+                graph.update_health(&name, health)?;
+                Reply::Empty
+            }
+            Request::UpdateTimer(name, state) => {
+                graph.update_timer(&name, state)?;
+                Reply::Empty
+            }
+            Request::UpdateFailureReason(name, reason) => {
+                graph.update_failure_reason(&name, reason)?;
+                Reply::Empty
+            }
             Request::UpdateServiceStatus(name, state) => {
                 graph.update_service_state(&name, state)?;
                 // To update the service, we need the get a write lock
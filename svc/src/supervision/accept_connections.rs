@@ -0,0 +1,294 @@
+use std::{
+    io,
+    net::Ipv4Addr,
+    os::fd::{
+        FromRawFd,
+        OwnedFd,
+        RawFd,
+    },
+    rc::Rc,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use futures::future;
+use nix::sys::socket::{
+    accept4,
+    getpeername,
+    getsockopt,
+    sockopt::PeerCredentials,
+    SockFlag,
+    SockaddrStorage,
+};
+use rinit_service::types::{
+    Longrun,
+    Socket,
+    SocketAddress,
+};
+use tokio::{
+    io::unix::AsyncFd,
+    select,
+    sync::{
+        oneshot,
+        watch,
+    },
+    task::{
+        self,
+        JoinSet,
+    },
+};
+use tracing::{
+    info,
+    instrument::WithSubscriber,
+    warn,
+};
+
+use crate::supervision::{
+    exec_script,
+    kill_process,
+    kill_remaining,
+    log_output,
+    Cgroup,
+    ListenSockets,
+};
+
+/// Accept the connections on the stream sockets of an inetd-style service and
+/// run a new instance of its run script for each of them, with the connection
+/// as stdin and stdout. The instances share the cgroup of the service
+pub async fn accept_connections(
+    longrun: Longrun,
+    sockets: Rc<ListenSockets>,
+    mut terminate: watch::Receiver<()>,
+    cgroup: Option<Cgroup>,
+) -> Result<()> {
+    let max_connections = longrun
+        .socket
+        .as_ref()
+        .map_or(Socket::DEFAULT_MAX_CONNECTIONS, |socket| {
+            socket.max_connections
+        }) as usize;
+    let listeners = sockets
+        .listeners()
+        .into_iter()
+        .map(|(fd, address)| AsyncFd::new(fd).map(|fd| (fd, address)))
+        .collect::<io::Result<Vec<_>>>()
+        .context("unable to watch the sockets")?;
+    let longrun = Rc::new(longrun);
+    let cgroup = cgroup.map(Rc::new);
+    let mut instances = JoinSet::new();
+    loop {
+        select! {
+            _ = terminate.changed() => break,
+            Some(res) = instances.join_next() => {
+                log_instance_result(res);
+            }
+            res = accept_any(&listeners), if instances.len() < max_connections => {
+                match res {
+                    Ok((connection, env)) => {
+                        instances.spawn_local(
+                            run_instance(
+                                longrun.clone(),
+                                connection,
+                                env,
+                                terminate.clone(),
+                                cgroup.clone(),
+                            )
+                            .with_current_subscriber(),
+                        );
+                    }
+                    Err(err) => warn!("unable to accept a connection: {err}"),
+                }
+            }
+        }
+    }
+    // The instances still running are killed on their own
+    while let Some(res) = instances.join_next().await {
+        log_instance_result(res);
+    }
+    kill_remaining(longrun.run.kill_mode, cgroup.as_deref()).await?;
+
+    Ok(())
+}
+
+fn log_instance_result(res: Result<Result<()>, task::JoinError>) {
+    match res {
+        Ok(Err(err)) => warn!("{err:?}"),
+        Err(err) => warn!("{err}"),
+        Ok(Ok(())) => {}
+    }
+}
+
+async fn accept_any(
+    listeners: &[(AsyncFd<RawFd>, &SocketAddress)]
+) -> io::Result<(OwnedFd, Vec<(&'static str, String)>)> {
+    future::select_all(
+        listeners
+            .iter()
+            .map(|(listener, address)| Box::pin(accept(listener, address))),
+    )
+    .await
+    .0
+}
+
+async fn accept(
+    listener: &AsyncFd<RawFd>,
+    address: &SocketAddress,
+) -> io::Result<(OwnedFd, Vec<(&'static str, String)>)> {
+    loop {
+        let mut guard = listener.readable().await?;
+        if let Ok(res) = guard.try_io(|listener| {
+            accept4(*listener.get_ref(), SockFlag::SOCK_CLOEXEC).map_err(io::Error::from)
+        }) {
+            let fd = res?;
+            let env = remote_env(fd, address);
+            // Safe, accept4 has just returned this fd
+            return Ok((unsafe { OwnedFd::from_raw_fd(fd) }, env));
+        }
+    }
+}
+
+/// The environment describing the client of the connection
+fn remote_env(
+    connection: RawFd,
+    address: &SocketAddress,
+) -> Vec<(&'static str, String)> {
+    let env = match address {
+        SocketAddress::Tcp(_) => {
+            getpeername::<SockaddrStorage>(connection).map(|peer| {
+                peer.as_sockaddr_in()
+                    .map(|peer| {
+                        vec![
+                            ("REMOTE_ADDR", Ipv4Addr::from(peer.ip()).to_string()),
+                            ("REMOTE_PORT", peer.port().to_string()),
+                        ]
+                    })
+                    .unwrap_or_default()
+            })
+        }
+        SocketAddress::UnixStream(_) => {
+            getsockopt(connection, PeerCredentials).map(|credentials| {
+                vec![
+                    ("REMOTE_PID", credentials.pid().to_string()),
+                    ("REMOTE_UID", credentials.uid().to_string()),
+                    ("REMOTE_GID", credentials.gid().to_string()),
+                ]
+            })
+        }
+        SocketAddress::UnixDatagram(_) | SocketAddress::Fifo(_) | SocketAddress::Udp(_) => {
+            unreachable!()
+        }
+    };
+    env.unwrap_or_else(|err| {
+        warn!("unable to get the address of the client: {err}");
+        Vec::new()
+    })
+}
+
+async fn run_instance(
+    longrun: Rc<Longrun>,
+    connection: OwnedFd,
+    remote_env: Vec<(&'static str, String)>,
+    mut terminate: watch::Receiver<()>,
+    cgroup: Option<Rc<Cgroup>>,
+) -> Result<()> {
+    let script = &longrun.run;
+    let mut env = longrun.environment.clone();
+    for (key, value) in remote_env {
+        env.add(key, value);
+    }
+    let (mut child, _) = exec_script(
+        script,
+        &env,
+        None,
+        Some(connection),
+        cgroup.as_deref(),
+        longrun.sandbox.as_ref(),
+    )
+    .await
+    .context("unable to execute script")?;
+    let (tx, rx) = oneshot::channel();
+    let logger = task::spawn_local(
+        log_output(child.stdout.take(), child.stderr.take().unwrap(), rx).with_current_subscriber(),
+    );
+    select! {
+        status = child.wait() => {
+            let status = status.context("unable to call wait on child")?;
+            if !status.success() {
+                info!("instance exited with {status}");
+            }
+        }
+        _ = terminate.changed() => {
+            // Only this instance is killed, the others are still serving
+            // their clients
+            kill_process(&mut child, &script.get_down_steps(), script.kill_mode, None).await?;
+        }
+    }
+    if !tx.is_closed() {
+        tx.send(()).unwrap();
+    }
+    logger.await??;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{
+            Read,
+            Write,
+        },
+        os::unix::net::UnixStream,
+        time::Duration,
+    };
+
+    use rinit_service::types::{
+        Script,
+        ScriptPrefix,
+    };
+    use tokio::time::sleep;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn accept_connections_echo() {
+        let path = std::env::temp_dir().join(format!("rinit-accept-test-{}", std::process::id()));
+        let socket = Socket {
+            accept: true,
+            ..Socket::new(vec![SocketAddress::UnixStream(path.clone())])
+        };
+        let sockets = Rc::new(ListenSockets::bind(&socket).unwrap());
+        let longrun = Longrun {
+            socket: Some(socket),
+            ..Longrun::new(
+                "test".to_string(),
+                Script::new(
+                    ScriptPrefix::Bash,
+                    "read line; echo \"$line $REMOTE_UID\"".to_string(),
+                ),
+            )
+        };
+        let (tx, rx) = watch::channel(());
+        let local_set = task::LocalSet::new();
+        local_set
+            .run_until(async move {
+                let handle = task::spawn_local(accept_connections(longrun, sockets, rx, None));
+                let reply = task::spawn_blocking(move || {
+                    let mut stream = UnixStream::connect(&path).unwrap();
+                    stream.write_all(b"hello\n").unwrap();
+                    let mut reply = String::new();
+                    stream.read_to_string(&mut reply).unwrap();
+                    reply
+                })
+                .await
+                .unwrap();
+                assert_eq!(reply, format!("hello {}\n", nix::unistd::getuid()));
+                sleep(Duration::from_millis(10)).await;
+                tx.send(()).unwrap();
+                handle.await.unwrap().unwrap();
+            })
+            .await;
+    }
+}
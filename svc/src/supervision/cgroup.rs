@@ -0,0 +1,386 @@
+use std::{
+    fs::{
+        self,
+        File,
+        OpenOptions,
+    },
+    io,
+    os::fd::{
+        AsRawFd,
+        FromRawFd,
+        OwnedFd,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    bail,
+    Context,
+    Result,
+};
+use nix::{
+    sys::{
+        inotify::{
+            AddWatchFlags,
+            InitFlags,
+            Inotify,
+        },
+        signal::{
+            kill,
+            Signal,
+        },
+    },
+    unistd::Pid,
+};
+use rinit_service::types::Resources;
+use tokio::io::unix::AsyncFd;
+use tracing::warn;
+
+// The controllers needed by [resources]
+const CONTROLLERS: [&str; 4] = ["cpu", "io", "memory", "pids"];
+
+/// Read the populated key of cgroup.events
+fn parse_populated(events: &str) -> Option<bool> {
+    events.lines().find_map(|line| {
+        match line.split_once(' ')? {
+            ("populated", value) => Some(value.trim() == "1"),
+            _ => None,
+        }
+    })
+}
+
+/// Read the value of a key in a flat keyed file, like memory.events
+fn read_key(
+    path: &Path,
+    key: &str,
+) -> u64 {
+    fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| {
+            contents.lines().find_map(|line| {
+                match line.split_once(' ')? {
+                    (k, value) if k == key => value.trim().parse().ok(),
+                    _ => None,
+                }
+            })
+        })
+        .unwrap_or(0)
+}
+
+/// Enable the controllers used by [resources] for the services under root.
+/// A cgroup can only delegate controllers to its children when it has no
+/// processes, rsvc is moved into a leaf cgroup if needed
+pub fn enable_controllers(root: &Path) -> Result<()> {
+    let parent = root
+        .parent()
+        .with_context(|| format!("{root:?} has no parent cgroup"))?;
+    let available = parent.join("cgroup.controllers");
+    let available =
+        fs::read_to_string(&available).with_context(|| format!("unable to read {available:?}"))?;
+    let controllers = CONTROLLERS
+        .iter()
+        .filter(|controller| available.split_whitespace().any(|c| c == **controller))
+        .map(|controller| format!("+{controller}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+    if controllers.is_empty() {
+        return Ok(());
+    }
+    let subtree_control = parent.join("cgroup.subtree_control");
+    match fs::write(&subtree_control, &controllers) {
+        Ok(()) => {}
+        Err(err) if err.raw_os_error() == Some(libc::EBUSY) => {
+            let scope = parent.join("rsvc.scope");
+            fs::create_dir_all(&scope)
+                .with_context(|| format!("unable to create cgroup {scope:?}"))?;
+            fs::write(scope.join("cgroup.procs"), "0")
+                .with_context(|| format!("unable to move rsvc into {scope:?}"))?;
+            fs::write(&subtree_control, &controllers)
+                .with_context(|| format!("unable to write {subtree_control:?}"))?;
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("unable to write {subtree_control:?}"));
+        }
+    }
+    fs::create_dir_all(root).with_context(|| format!("unable to create cgroup {root:?}"))?;
+    let subtree_control = root.join("cgroup.subtree_control");
+    fs::write(&subtree_control, &controllers)
+        .with_context(|| format!("unable to write {subtree_control:?}"))
+}
+
+/// Write the limits to the controller files of a cgroup. A limit that can't
+/// be set doesn't prevent the service from running
+pub fn set_resources(
+    path: &Path,
+    resources: &Resources,
+) {
+    for (file, contents) in resources.controller_files() {
+        match fs::write(path.join(file), &contents) {
+            Ok(()) => {}
+            // The controller is not enabled, there is nothing to reset
+            Err(err)
+                if err.kind() == io::ErrorKind::NotFound && resources == &Resources::default() => {}
+            Err(err) => warn!("unable to set {file} to {contents:?} in {path:?}: {err}"),
+        }
+    }
+}
+
+/// How many times the processes of a cgroup hit one of its limits
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LimitEvents {
+    oom_kill: u64,
+    pids_max: u64,
+}
+
+impl LimitEvents {
+    /// Describe the limits hit since the previous events, if any
+    pub fn reason_since(
+        &self,
+        previous: &LimitEvents,
+    ) -> Option<String> {
+        let mut reasons = Vec::new();
+        if self.oom_kill > previous.oom_kill {
+            reasons.push("killed by the OOM killer, memory_max was reached");
+        }
+        if self.pids_max > previous.pids_max {
+            reasons.push("unable to fork, pids_max was reached");
+        }
+        if reasons.is_empty() {
+            None
+        } else {
+            Some(reasons.join(", "))
+        }
+    }
+}
+
+/// The cgroup v2 of a service. Every process started by the service is placed
+/// in it, even the ones that create a new session or double fork can't escape
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    /// Create the cgroup of the service under the subtree delegated to rinit
+    pub fn create(
+        root: &Path,
+        name: &str,
+    ) -> Result<Self> {
+        let path = root.join(name);
+        fs::create_dir_all(&path).with_context(|| format!("unable to create cgroup {path:?}"))?;
+        // The directory could have been created on a tmpfs, as on systems with
+        // the legacy or hybrid hierarchy
+        if !path.join("cgroup.procs").exists() {
+            let _ = fs::remove_dir(&path);
+            bail!("{root:?} is not part of a cgroup v2 hierarchy");
+        }
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_resources(
+        &self,
+        resources: &Resources,
+    ) {
+        set_resources(&self.path, resources);
+    }
+
+    /// Read the limit events of the cgroup. The events of the controllers not
+    /// enabled are always 0
+    pub fn limit_events(&self) -> LimitEvents {
+        LimitEvents {
+            oom_kill: read_key(&self.path.join("memory.events"), "oom_kill"),
+            pids_max: read_key(&self.path.join("pids.events"), "max"),
+        }
+    }
+
+    /// Open cgroup.procs for writing. It is opened before fork, so that the
+    /// child only has to write 0 to it to move itself into the cgroup
+    pub fn open_procs(&self) -> Result<File> {
+        let procs = self.path.join("cgroup.procs");
+        OpenOptions::new()
+            .write(true)
+            .open(&procs)
+            .with_context(|| format!("unable to open {procs:?}"))
+    }
+
+    /// Move the current process into the cgroup. This runs after fork, so it
+    /// must not allocate
+    pub fn enter(procs: &File) -> io::Result<()> {
+        nix::unistd::write(procs.as_raw_fd(), b"0")?;
+        Ok(())
+    }
+
+    pub fn is_populated(&self) -> Result<bool> {
+        let events = self.path.join("cgroup.events");
+        let contents =
+            fs::read_to_string(&events).with_context(|| format!("unable to read {events:?}"))?;
+        parse_populated(&contents).with_context(|| format!("invalid contents in {events:?}"))
+    }
+
+    fn procs(&self) -> Result<Vec<Pid>> {
+        let procs = self.path.join("cgroup.procs");
+        Ok(fs::read_to_string(&procs)
+            .with_context(|| format!("unable to read {procs:?}"))?
+            .lines()
+            .filter_map(|pid| pid.parse().ok())
+            .map(Pid::from_raw)
+            .collect())
+    }
+
+    /// Whether the process is in the cgroup
+    pub fn contains(
+        &self,
+        pid: Pid,
+    ) -> bool {
+        self.procs().is_ok_and(|procs| procs.contains(&pid))
+    }
+
+    /// Send SIGKILL to every process in the cgroup
+    pub fn kill(&self) -> Result<()> {
+        match fs::write(self.path.join("cgroup.kill"), "1") {
+            Ok(()) => Ok(()),
+            // cgroup.kill is only available since Linux 5.14
+            Err(err) if err.kind() == io::ErrorKind::NotFound => self.signal(Signal::SIGKILL),
+            Err(err) => {
+                Err(err).with_context(|| format!("unable to kill the processes in {:?}", self.path))
+            }
+        }
+    }
+
+    /// Send a signal to every process in the cgroup. The cgroup is frozen
+    /// first, so that no process can fork or exit while they are being
+    /// signalled, and their PIDs can't be reused. The signals are delivered
+    /// once the cgroup is thawed, SIGKILL is delivered to frozen processes too
+    pub fn signal(
+        &self,
+        signal: Signal,
+    ) -> Result<()> {
+        let freeze = self.path.join("cgroup.freeze");
+        fs::write(&freeze, "1").with_context(|| format!("unable to write {freeze:?}"))?;
+        for pid in self.procs()? {
+            match kill(pid, signal) {
+                Ok(()) | Err(nix::errno::Errno::ESRCH) => {}
+                Err(err) => warn!("unable to send signal {signal} to process {pid}: {err}"),
+            }
+        }
+        fs::write(&freeze, "0").with_context(|| format!("unable to write {freeze:?}"))
+    }
+
+    /// Wait until every process in the cgroup has exited
+    pub async fn wait_empty(&self) -> Result<()> {
+        let events = self.path.join("cgroup.events");
+        let inotify = Inotify::init(InitFlags::IN_NONBLOCK | InitFlags::IN_CLOEXEC)
+            .context("unable to initialize inotify")?;
+        // Inotify does not close its file descriptor, this is dropped last
+        let _fd = unsafe { OwnedFd::from_raw_fd(inotify.as_raw_fd()) };
+        // cgroup.events is modified every time one of its values changes
+        inotify
+            .add_watch(&events, AddWatchFlags::IN_MODIFY)
+            .with_context(|| format!("unable to watch {events:?}"))?;
+        let inotify = AsyncFd::new(inotify).context("unable to watch the inotify descriptor")?;
+        while self.is_populated()? {
+            let mut guard = inotify
+                .readable()
+                .await
+                .context("unable to wait for inotify events")?;
+            if let Ok(res) =
+                guard.try_io(|inotify| inotify.get_ref().read_events().map_err(io::Error::from))
+            {
+                res.context("unable to read inotify events")?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Kill the processes left in the cgroup, then wait until it is empty
+    pub async fn kill_remaining(&self) -> Result<()> {
+        if self.is_populated()? {
+            warn!("There were lingering processes in the cgroup. Killing them with SIGKILL.");
+            self.kill()?;
+            self.wait_empty().await?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        match fs::remove_dir(&self.path) {
+            Ok(()) => {}
+            // The processes have been left running because of kill_mode = process
+            Err(err) if err.raw_os_error() == Some(libc::EBUSY) => {}
+            Err(err) => warn!("unable to remove cgroup {:?}: {err}", self.path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::process::Command;
+
+    use super::*;
+
+    // Use the cgroup v2 hierarchy mounted on this system, if it is writable
+    fn test_cgroup(name: &str) -> Option<Cgroup> {
+        ["/sys/fs/cgroup", "/sys/fs/cgroup/unified"]
+            .iter()
+            .find_map(|root| {
+                Cgroup::create(
+                    Path::new(root),
+                    &format!("rinit-test-{name}-{}", std::process::id()),
+                )
+                .ok()
+            })
+    }
+
+    #[test]
+    fn populated() {
+        assert_eq!(parse_populated("populated 1\nfrozen 0\n"), Some(true));
+        assert_eq!(parse_populated("populated 0\nfrozen 0\n"), Some(false));
+        assert_eq!(parse_populated("frozen 0\n"), None);
+    }
+
+    #[test]
+    fn limit_reason() {
+        let previous = LimitEvents {
+            oom_kill: 1,
+            pids_max: 0,
+        };
+        assert_eq!(previous.reason_since(&previous), None);
+        assert_eq!(
+            LimitEvents {
+                oom_kill: 2,
+                pids_max: 0,
+            }
+            .reason_since(&previous)
+            .as_deref(),
+            Some("killed by the OOM killer, memory_max was reached")
+        );
+    }
+
+    #[tokio::test]
+    async fn kill_escaped_processes() {
+        let cgroup = match test_cgroup("kill") {
+            Some(cgroup) => cgroup,
+            None => return,
+        };
+        let procs = cgroup.open_procs().unwrap();
+        let mut cmd = Command::new("sh");
+        // The grandchild leaves the process group and outlives its parent
+        cmd.args(["-c", "setsid sleep 60 & exit 0"]);
+        unsafe {
+            std::os::unix::process::CommandExt::pre_exec(&mut cmd, move || Cgroup::enter(&procs));
+        }
+        assert!(cmd.status().unwrap().success());
+        assert!(cgroup.is_populated().unwrap());
+
+        cgroup.kill_remaining().await.unwrap();
+        assert!(!cgroup.is_populated().unwrap());
+    }
+}
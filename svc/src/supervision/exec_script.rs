@@ -1,7 +1,10 @@
 use std::{
     collections::HashMap,
     env,
-    os::fd::RawFd,
+    os::fd::{
+        OwnedFd,
+        RawFd,
+    },
     process::Stdio,
 };
 
@@ -10,6 +13,11 @@ use anyhow::{
     Result,
 };
 use nix::{
+    fcntl::{
+        fcntl,
+        FcntlArg,
+        OFlag,
+    },
     sys::signal::{
         SigSet,
         SigmaskHow,
@@ -17,12 +25,11 @@ use nix::{
     unistd::{
         close,
         dup2,
-        Group,
         Pid,
-        User,
     },
 };
 use rinit_service::types::{
+    Sandbox,
     Script,
     ScriptEnvironment,
     ScriptPrefix,
@@ -42,9 +49,71 @@ use tracing::{
     warn,
 };
 
+use crate::supervision::{
+    Cgroup,
+    ListenSockets,
+    ProcessAttributes,
+    ProcessSandbox,
+};
+
+// The first fd passed to socket activated services
+const LISTEN_FDS_START: RawFd = 3;
+const LISTEN_PID_PREFIX: &str = "LISTEN_PID=";
+// The value of LISTEN_PID is only known after fork, reserve enough space for
+// it so that it can be written in place
+const LISTEN_PID_PLACEHOLDER: &str = "0000000000";
+
+extern "C" {
+    static mut environ: *const *const libc::c_char;
+}
+
+/// Move the listening sockets to the fds starting from 3, as expected by
+/// sd_listen_fds. This runs after fork, moved must have enough capacity to
+/// never allocate
+fn pass_listen_fds(
+    fds: &[RawFd],
+    moved: &mut Vec<RawFd>,
+) -> nix::Result<()> {
+    let first = LISTEN_FDS_START;
+    // Duplicate the fds above the target range first, otherwise dup2 could
+    // overwrite one of them
+    for fd in fds {
+        moved.push(fcntl(*fd, FcntlArg::F_DUPFD(first + fds.len() as RawFd))?);
+    }
+    for (index, fd) in moved.iter().enumerate() {
+        // dup2 clears FD_CLOEXEC
+        dup2(*fd, first + index as RawFd)?;
+        close(*fd)?;
+    }
+    Ok(())
+}
+
+/// Write the pid of the current process in place of LISTEN_PID_PLACEHOLDER.
+/// This runs after fork, so it must not allocate
+fn set_listen_pid(var: &mut [u8]) {
+    let pid = std::process::id();
+    let mut len = 1;
+    let mut rest = pid / 10;
+    while rest != 0 {
+        len += 1;
+        rest /= 10;
+    }
+    let value = &mut var[LISTEN_PID_PREFIX.len()..];
+    let mut rest = pid;
+    for digit in value[..len].iter_mut().rev() {
+        *digit = b'0' + (rest % 10) as u8;
+        rest /= 10;
+    }
+    value[len] = 0;
+}
+
 pub async fn exec_script(
     script: &Script,
     env: &ScriptEnvironment,
+    sockets: Option<&ListenSockets>,
+    connection: Option<OwnedFd>,
+    cgroup: Option<&Cgroup>,
+    sandbox: Option<&Sandbox>,
 ) -> Result<(Child, Option<AsyncFd<i32>>)> {
     let (exe, args) = match &script.prefix {
         ScriptPrefix::Bash => ("bash", vec!["-c", &script.execute]),
@@ -63,27 +132,20 @@ pub async fn exec_script(
     let mut cmd = Command::new(exe);
     // TODO: Use a proper splitting function
     cmd.args(args);
-    if let Some(user) = &script.user {
-        cmd.uid(
-            User::from_name(user)
-                .with_context(|| format!("unable to get UID for user {}", user))?
-                .with_context(|| format!("unable to find UID for user {}", user))?
-                .uid
-                .as_raw(),
-        );
-    }
-    if let Some(group) = &script.group {
-        cmd.gid(
-            Group::from_name(group)
-                .with_context(|| format!("unable to get GID for group {}", group))?
-                .with_context(|| format!("unable to find GID for group {}", group))?
-                .gid
-                .as_raw(),
-        );
+    let attributes = ProcessAttributes::new(script)?;
+    let sandbox = sandbox.map(ProcessSandbox::new).transpose()?;
+    if let Some(connection) = connection {
+        // inetd-style services talk to the client through stdin and stdout
+        cmd.stdin(Stdio::from(
+            connection
+                .try_clone()
+                .context("unable to duplicate the connection fd")?,
+        ))
+        .stdout(Stdio::from(connection));
+    } else {
+        cmd.stdin(Stdio::null()).stdout(Stdio::piped());
     }
-    cmd.stdin(Stdio::null())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
+    cmd.stderr(Stdio::piped());
     unsafe {
         cmd.pre_exec(move || {
             let mask = SigSet::empty();
@@ -97,12 +159,22 @@ pub async fn exec_script(
             Ok(())
         })
     };
+    if let Some(cgroup) = cgroup {
+        let procs = cgroup.open_procs()?;
+        unsafe {
+            cmd.pre_exec(move || Cgroup::enter(&procs));
+        }
+    }
 
     let mut pipe = None;
     if let Some(notify) = &script.notify {
         let res = nix::unistd::pipe();
         match res {
             Ok((read, write)) => unsafe {
+                // The keepalives sent for the watchdog are read without blocking
+                if let Err(err) = fcntl(read, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)) {
+                    error!("Could not set the notify pipe as non blocking: {err}");
+                }
                 pipe = Some((read, write));
                 let notify: RawFd = (*notify).into();
                 cmd.pre_exec(move || {
@@ -116,10 +188,54 @@ pub async fn exec_script(
         }
     }
 
-    let merged_env: HashMap<String, String> = env::vars()
+    // The login environment of the user replaces the one of rsvc, while the
+    // variables set by the service have the last word
+    let mut merged_env: HashMap<String, String> = env::vars()
+        .chain(
+            attributes
+                .login_env()
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone())),
+        )
         .chain(env.contents.clone().into_iter())
         .collect();
-    cmd.envs(merged_env);
+    if let Some(sockets) = sockets {
+        let fds = sockets.fds();
+        merged_env.insert("LISTEN_FDS".to_string(), fds.len().to_string());
+        merged_env.insert("LISTEN_FDNAMES".to_string(), sockets.names());
+        // The environment is built by Command before fork, while LISTEN_PID is
+        // only known in the child. Build it here and set environ in the child
+        let mut vars: Vec<Vec<u8>> = merged_env
+            .into_iter()
+            .map(|(key, value)| format!("{key}={value}\0").into_bytes())
+            .collect();
+        vars.push(format!("{LISTEN_PID_PREFIX}{LISTEN_PID_PLACEHOLDER}\0").into_bytes());
+        // Raw pointers are not Send, store their addresses instead
+        let mut envp: Vec<usize> = Vec::with_capacity(vars.len() + 1);
+        let mut moved = Vec::with_capacity(fds.len());
+        unsafe {
+            cmd.pre_exec(move || {
+                pass_listen_fds(&fds, &mut moved)?;
+                set_listen_pid(vars.last_mut().unwrap());
+                envp.extend(vars.iter().map(|var| var.as_ptr() as usize));
+                envp.push(0);
+                environ = envp.as_ptr().cast();
+                Ok(())
+            });
+        }
+    } else {
+        cmd.envs(merged_env);
+    }
+    if let Some(sandbox) = sandbox {
+        unsafe {
+            cmd.pre_exec(move || sandbox.apply());
+        }
+    }
+    // The credentials are changed here instead of using Command::uid, which
+    // drops the privileges before the pre_exec closures are run
+    unsafe {
+        cmd.pre_exec(move || attributes.apply());
+    }
     let child = cmd.spawn().context("unable to spawn script")?;
     Ok((
         child,
@@ -137,3 +253,159 @@ pub async fn exec_script(
         }),
     ))
 }
+
+#[cfg(test)]
+mod test {
+    use rinit_service::types::{
+        RLimit,
+        RLimitResource,
+        Socket,
+        SocketAddress,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn pass_listen_sockets() {
+        let path = env::temp_dir().join(format!("rinit-listen-test-{}", std::process::id()));
+        let sockets =
+            ListenSockets::bind(&Socket::new(vec![SocketAddress::UnixStream(path)])).unwrap();
+        let script = Script::new(
+            ScriptPrefix::Bash,
+            "[ \"$LISTEN_PID\" = $$ ] && [ \"$LISTEN_FDS\" = 1 ] && [ -S /proc/self/fd/3 ]"
+                .to_string(),
+        );
+        let (mut child, _) = exec_script(
+            &script,
+            &ScriptEnvironment::new(),
+            Some(&sockets),
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(child.wait().await.unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn apply_exec_options() {
+        let mut script = Script::new(
+            ScriptPrefix::Sh,
+            "[ \"$(ulimit -n)\" = 512 ] && [ \"$(cut -d' ' -f19 /proc/self/stat)\" = 5 ]"
+                .to_string(),
+        );
+        script.exec.rlimits.push(RLimit {
+            resource: RLimitResource::Nofile,
+            soft: Some(512),
+            hard: Some(512),
+        });
+        script.exec.nice = Some(5);
+        let (mut child, _) =
+            exec_script(&script, &ScriptEnvironment::new(), None, None, None, None)
+                .await
+                .unwrap();
+        assert!(child.wait().await.unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn working_directory_and_umask() {
+        let mut script = Script::new(
+            ScriptPrefix::Sh,
+            "[ \"$(pwd)\" = /tmp ] && [ \"$(umask)\" = 0027 ]".to_string(),
+        );
+        script.exec.working_directory = Some("/tmp".into());
+        script.exec.umask = Some(0o027);
+        let (mut child, _) =
+            exec_script(&script, &ScriptEnvironment::new(), None, None, None, None)
+                .await
+                .unwrap();
+        assert!(child.wait().await.unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn sandbox() {
+        // Only root can create the namespaces
+        if !nix::unistd::Uid::effective().is_root() {
+            return;
+        }
+        let script = Script::new(
+            ScriptPrefix::Sh,
+            "[ -z \"$(ls -A /tmp)\" ] && ! touch /etc/rinit-sandbox-test 2>/dev/null && [ \"$(ls \
+             /dev | tr '\\n' ' ')\" = 'fd full null random shm stderr stdin stdout tty urandom \
+             zero ' ] && [ \"$(wc -l < /proc/net/dev)\" = 3 ] && grep -q 'NoNewPrivs:\\s*1' \
+             /proc/self/status"
+                .to_string(),
+        );
+        let sandbox = Sandbox {
+            private_tmp: true,
+            protect_system: true,
+            private_network: true,
+            private_devices: true,
+            no_new_privileges: true,
+            ..Sandbox::default()
+        };
+        let (mut child, _) = exec_script(
+            &script,
+            &ScriptEnvironment::new(),
+            None,
+            None,
+            None,
+            Some(&sandbox),
+        )
+        .await
+        .unwrap();
+        assert!(child.wait().await.unwrap().success());
+    }
+
+    #[tokio::test]
+    async fn sandbox_read_only_submounts() {
+        if !nix::unistd::Uid::effective().is_root() {
+            return;
+        }
+        let path = env::temp_dir().join(format!("rinit-read-only-test-{}", std::process::id()));
+        let submount = path.join("sub");
+        std::fs::create_dir_all(&submount).unwrap();
+        nix::mount::mount(
+            Some("tmpfs"),
+            &submount,
+            Some("tmpfs"),
+            nix::mount::MsFlags::MS_NOEXEC,
+            None::<&str>,
+        )
+        .unwrap();
+        // Both the path and the mount below it are read-only, and the
+        // submount is still noexec. The bind of the submount is the last one
+        // listed at its mount point
+        let script = Script::new(
+            ScriptPrefix::Sh,
+            format!(
+                "! touch {path}/foo 2>/dev/null && ! touch {submount}/foo 2>/dev/null && awk '$5 \
+                 == \"{submount}\" {{ print $6 }}' /proc/self/mountinfo | tail -n 1 | grep -q \
+                 ro,.*noexec",
+                path = path.display(),
+                submount = submount.display(),
+            ),
+        );
+        let sandbox = Sandbox {
+            read_only_paths: vec![path.clone()],
+            ..Sandbox::default()
+        };
+        let res = exec_script(
+            &script,
+            &ScriptEnvironment::new(),
+            None,
+            None,
+            None,
+            Some(&sandbox),
+        )
+        .await;
+        let success = match res {
+            Ok((mut child, _)) => child.wait().await.unwrap().success(),
+            Err(_) => false,
+        };
+        nix::mount::umount(&submount).unwrap();
+        std::fs::remove_dir_all(&path).unwrap();
+        assert!(success);
+    }
+}
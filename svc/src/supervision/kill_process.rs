@@ -1,4 +1,11 @@
-use std::time::Duration;
+use std::{
+    io,
+    os::fd::{
+        AsRawFd,
+        OwnedFd,
+    },
+    time::Duration,
+};
 
 use anyhow::{
     Context,
@@ -11,50 +18,299 @@ use nix::{
     },
     unistd::Pid,
 };
+use rinit_service::types::{
+    KillMode,
+    SignalStep,
+};
 use tokio::{
+    io::{
+        unix::AsyncFd,
+        Interest,
+    },
     process::Child,
     time::timeout,
 };
 use tracing::warn;
 
+use crate::supervision::{
+    pidfd_open,
+    pidfd_send_signal,
+    Cgroup,
+};
+
+/// Send a signal to the child through a pidfd, so that it can't reach another
+/// process that reused its PID. The child can't be reaped in the meantime,
+/// since it is only waited by us
+fn signal_child(
+    child: &Child,
+    signal: Signal,
+) -> Result<()> {
+    // The child has already been reaped
+    let pid = match child.id() {
+        Some(pid) => pid as i32,
+        None => return Ok(()),
+    };
+    let res = match pidfd_open(pid) {
+        Ok(pidfd) => pidfd_send_signal(pidfd.as_raw_fd(), signal as i32),
+        // pidfds are only available since Linux 5.3
+        Err(err) if err.raw_os_error() == Some(libc::ENOSYS) => {
+            kill(Pid::from_raw(pid), signal).map_err(io::Error::from)
+        }
+        Err(err) => Err(err),
+    };
+    match res {
+        // The child has exited but it has not been reaped yet
+        Err(err) if err.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+        res => res.with_context(|| format!("unable to send signal {signal}")),
+    }
+}
+
+/// Send a signal to the process group of the child. Return whether there was
+/// any process left in it
+fn signal_group(
+    pgid: Option<Pid>,
+    signal: Signal,
+) -> Result<bool> {
+    let pgid = match pgid {
+        Some(pgid) => pgid,
+        None => return Ok(false),
+    };
+    match kill(Pid::from_raw(-pgid.as_raw()), signal) {
+        Ok(()) => Ok(true),
+        Err(nix::errno::Errno::ESRCH) => Ok(false),
+        Err(err) => {
+            Err(err).with_context(|| format!("unable to send signal {signal} to process group"))
+        }
+    }
+}
+
+/// Send a signal to the processes in the scope of kill_mode
+fn signal_scope(
+    child: &Child,
+    signal: Signal,
+    kill_mode: KillMode,
+    cgroup: Option<&Cgroup>,
+) -> Result<()> {
+    match (kill_mode, cgroup) {
+        (KillMode::Process | KillMode::Mixed, _) => signal_child(child, signal),
+        (KillMode::Cgroup, Some(cgroup)) => cgroup.signal(signal),
+        // The process group is the closest thing to a cgroup
+        (KillMode::Group | KillMode::Cgroup, _) => {
+            signal_group(child.id().map(|id| Pid::from_raw(id as i32)), signal).map(|_| ())
+        }
+    }
+}
+
+/// Wait until the child has exited. Through a pidfd the child is not reaped, so
+/// that its PID and its process group can't be reused in the meantime
+async fn wait_exit(
+    child: &mut Child,
+    pidfd: Option<&AsyncFd<OwnedFd>>,
+) -> Result<()> {
+    match pidfd {
+        Some(pidfd) => {
+            pidfd
+                .readable()
+                .await
+                .map(|_| ())
+                .context("unable to poll the pidfd")
+        }
+        None => {
+            child
+                .wait()
+                .await
+                .map(|_| ())
+                .context("unable to call wait")
+        }
+    }
+}
+
+/// Kill the processes left behind by a main process that has exited. Only the
+/// cgroup is checked, since the process group might have been reused
+pub async fn kill_remaining(
+    kill_mode: KillMode,
+    cgroup: Option<&Cgroup>,
+) -> Result<()> {
+    match (kill_mode, cgroup) {
+        (KillMode::Cgroup | KillMode::Mixed, Some(cgroup)) => cgroup.kill_remaining().await,
+        _ => Ok(()),
+    }
+}
+
+/// Send the signals of each step in order, until the process has exited. It is
+/// killed with SIGKILL after the last step
 pub async fn kill_process(
     child: &mut Child,
-    down_signal: i32,
-    timeout_kill: u32,
+    steps: &[SignalStep],
+    kill_mode: KillMode,
+    cgroup: Option<&Cgroup>,
 ) -> Result<()> {
-    let child_id = child.id().unwrap() as i32;
-    let child_pid = Pid::from_raw(child_id);
-    kill(
-        child_pid,
-        // Safe, down_signal is always parsed from Signal
-        Signal::try_from(down_signal).unwrap(),
-    )
-    .with_context(|| format!("unable to send signal {:?}", down_signal))?;
-    let timeout_res = timeout(Duration::from_millis(timeout_kill as u64), child.wait()).await;
-    if let Ok(exit_status) = timeout_res {
-        exit_status.context("unable to call wait")?;
-    } else {
+    // pidfds are only available since Linux 5.3, without them the child is
+    // reaped as soon as it exits
+    let pidfd = child
+        .id()
+        .and_then(|pid| pidfd_open(pid as i32).ok())
+        .and_then(|pidfd| AsyncFd::with_interest(pidfd, Interest::READABLE).ok());
+    let mut exited = false;
+    for step in steps {
+        // Safe, the signals are always parsed from Signal
+        let signal = Signal::try_from(step.signal).unwrap();
+        signal_scope(child, signal, kill_mode, cgroup)?;
+        let timeout_res = timeout(
+            Duration::from_millis(step.wait as u64),
+            wait_exit(child, pidfd.as_ref()),
+        )
+        .await;
+        if let Ok(res) = timeout_res {
+            res?;
+            exited = true;
+            break;
+        }
         warn!(
-            "the process didn't exit after signal {} and waiting {}ms. Sending SIGKILL",
-            // This is always valid as it's parsed from the name
-            Signal::try_from(down_signal).unwrap(),
-            timeout_kill
+            "the process didn't exit after signal {} and waiting {}ms",
+            signal, step.wait
         );
-        kill(child_pid, Signal::SIGKILL).context("unable to send signal SIGKILL")?;
+    }
+    if !exited {
+        warn!("Sending SIGKILL");
+        signal_scope(child, Signal::SIGKILL, kill_mode, cgroup)?;
     }
 
-    // The process might have spawned other processes, if it didn't cleanup it's a
-    // bug. Kill them with SIGKILL. This isn't a lot of overhead, kill_process
-    // shouldn't be called in normal circumstances, and
-    let res = kill(Pid::from_raw(-child_id), Signal::SIGKILL);
-    match res {
-        Ok(_) => warn!("The were lingering children of the process. Killing them with SIGKILL."),
-        Err(errno) => {
-            if !matches!(errno, nix::errno::Errno::ESRCH) {
-                res.context("unable to send signal SIGKILL to process group")?
-            }
+    let res = match (kill_mode, cgroup) {
+        // The processes spawned by the main process are meant to outlive it
+        (KillMode::Process, _) => Ok(()),
+        // Every process spawned by the service is in its cgroup, even if it left
+        // the process group
+        (KillMode::Cgroup | KillMode::Mixed, Some(cgroup)) => cgroup.kill_remaining().await,
+        // The process might have spawned other processes, if it didn't cleanup it's
+        // a bug. Kill them with SIGKILL. This isn't a lot of overhead, kill_process
+        // shouldn't be called in normal circumstances. The child has no id once it
+        // has been reaped, its process group might have been reused then
+        _ => {
+            signal_group(
+                child.id().map(|id| Pid::from_raw(id as i32)),
+                Signal::SIGKILL,
+            )
+            .map(|lingering| {
+                if lingering {
+                    warn!("The were lingering children of the process. Killing them with SIGKILL.");
+                }
+            })
+        }
+    };
+    if exited {
+        // The child has only been waited through its pidfd
+        child.wait().await.context("unable to call wait")?;
+    }
+    res
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        fs,
+        process::Stdio,
+    };
+
+    use tokio::{
+        io::{
+            AsyncBufReadExt,
+            BufReader,
+        },
+        process::Command,
+    };
+
+    use super::*;
+
+    const TERM: SignalStep = SignalStep {
+        signal: libc::SIGTERM,
+        wait: 1000,
+    };
+
+    // Spawn a shell in its own process group, with a helper process running in
+    // the background
+    async fn spawn_with_helper(helper: &str) -> (Child, Pid) {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", &format!("{helper} & echo $!; wait")])
+            .stdout(Stdio::piped());
+        unsafe {
+            cmd.pre_exec(|| {
+                nix::unistd::setpgid(Pid::from_raw(0), Pid::from_raw(0))?;
+                Ok(())
+            });
         }
+        let mut child = cmd.spawn().unwrap();
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .await
+            .unwrap();
+        (child, Pid::from_raw(line.trim().parse().unwrap()))
     }
 
-    Ok(())
+    // The helper has been reparented, it might not have been reaped yet
+    fn is_running(pid: Pid) -> bool {
+        fs::read_to_string(format!("/proc/{pid}/stat")).is_ok_and(|stat| !stat.contains(") Z "))
+    }
+
+    #[tokio::test]
+    async fn kill_mode_process() {
+        let (mut child, helper) = spawn_with_helper("sleep 60").await;
+        kill_process(&mut child, &[TERM], KillMode::Process, None)
+            .await
+            .unwrap();
+        assert!(child.try_wait().unwrap().is_some());
+        assert!(is_running(helper));
+        kill(helper, Signal::SIGKILL).unwrap();
+    }
+
+    #[tokio::test]
+    async fn kill_mode_group() {
+        let (mut child, helper) = spawn_with_helper("sleep 60").await;
+        kill_process(&mut child, &[TERM], KillMode::Group, None)
+            .await
+            .unwrap();
+        assert!(child.try_wait().unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!is_running(helper));
+    }
+
+    #[tokio::test]
+    async fn kill_mode_group_lingering() {
+        // The helper survives SIGTERM, it is killed after the shell has exited
+        let (mut child, helper) = spawn_with_helper("sh -c \"trap '' TERM; exec sleep 60\"").await;
+        kill_process(&mut child, &[TERM], KillMode::Group, None)
+            .await
+            .unwrap();
+        assert!(child.try_wait().unwrap().is_some());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!is_running(helper));
+    }
+
+    #[tokio::test]
+    async fn signal_ladder() {
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                "trap '' TERM; trap 'exit 3' INT; while :; do sleep 0.01; done",
+            ])
+            .spawn()
+            .unwrap();
+        // Give the shell the time to set its traps
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let steps = [
+            SignalStep {
+                signal: libc::SIGTERM,
+                wait: 100,
+            },
+            SignalStep {
+                signal: libc::SIGINT,
+                wait: 1000,
+            },
+        ];
+        kill_process(&mut child, &steps, KillMode::Process, None)
+            .await
+            .unwrap();
+        assert_eq!(child.try_wait().unwrap().unwrap().code(), Some(3));
+    }
 }
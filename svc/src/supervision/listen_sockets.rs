@@ -0,0 +1,234 @@
+use std::{
+    fs::{
+        self,
+        OpenOptions,
+        Permissions,
+    },
+    io,
+    net::{
+        Ipv4Addr,
+        TcpListener,
+        UdpSocket,
+    },
+    os::{
+        fd::{
+            AsRawFd,
+            OwnedFd,
+            RawFd,
+        },
+        unix::{
+            fs::{
+                OpenOptionsExt,
+                PermissionsExt,
+            },
+            net::{
+                UnixDatagram,
+                UnixListener,
+            },
+        },
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use futures::future;
+use nix::{
+    sys::stat::Mode,
+    unistd::mkfifo,
+};
+use rinit_service::types::{
+    Socket,
+    SocketAddress,
+};
+use tokio::io::unix::AsyncFd;
+use tracing::warn;
+
+/// The sockets declared in the [socket] section of a longrun. They are owned by
+/// rsvc, so that no connection is lost while the service is being restarted
+pub struct ListenSockets {
+    fds: Vec<OwnedFd>,
+    addresses: Vec<SocketAddress>,
+    // The files created for the unix sockets and FIFOs
+    paths: Vec<PathBuf>,
+}
+
+impl ListenSockets {
+    pub fn bind(socket: &Socket) -> Result<Self> {
+        let mut sockets = Self {
+            fds: Vec::with_capacity(socket.listen.len()),
+            addresses: Vec::with_capacity(socket.listen.len()),
+            paths: Vec::new(),
+        };
+        for address in &socket.listen {
+            let fd = match address {
+                SocketAddress::UnixStream(path) => {
+                    prepare_path(path)?;
+                    let listener = UnixListener::bind(path)
+                        .with_context(|| format!("unable to bind unix socket {path:?}"))?;
+                    sockets.paths.push(path.to_owned());
+                    // Connections are accepted by rsvc in accept mode
+                    listener
+                        .set_nonblocking(socket.accept)
+                        .context("unable to set the socket as non blocking")?;
+                    OwnedFd::from(listener)
+                }
+                SocketAddress::UnixDatagram(path) => {
+                    prepare_path(path)?;
+                    let socket = UnixDatagram::bind(path)
+                        .with_context(|| format!("unable to bind unix socket {path:?}"))?;
+                    sockets.paths.push(path.to_owned());
+                    OwnedFd::from(socket)
+                }
+                SocketAddress::Fifo(path) => {
+                    prepare_path(path)?;
+                    mkfifo(path.as_path(), Mode::from_bits_truncate(socket.mode))
+                        .with_context(|| format!("unable to create FIFO {path:?}"))?;
+                    sockets.paths.push(path.to_owned());
+                    // Keep the writing end open too, so that the service never reads EOF
+                    let fifo = OpenOptions::new()
+                        .read(true)
+                        .write(true)
+                        .custom_flags(libc::O_NONBLOCK)
+                        .open(path)
+                        .with_context(|| format!("unable to open FIFO {path:?}"))?;
+                    OwnedFd::from(fifo)
+                }
+                SocketAddress::Tcp(port) => {
+                    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, *port))
+                        .with_context(|| format!("unable to bind TCP port {port}"))?;
+                    listener
+                        .set_nonblocking(socket.accept)
+                        .context("unable to set the socket as non blocking")?;
+                    OwnedFd::from(listener)
+                }
+                SocketAddress::Udp(port) => {
+                    OwnedFd::from(
+                        UdpSocket::bind((Ipv4Addr::LOCALHOST, *port))
+                            .with_context(|| format!("unable to bind UDP port {port}"))?,
+                    )
+                }
+            };
+            if let SocketAddress::UnixStream(path)
+            | SocketAddress::UnixDatagram(path)
+            | SocketAddress::Fifo(path) = address
+            {
+                fs::set_permissions(path, Permissions::from_mode(socket.mode))
+                    .with_context(|| format!("unable to set the permissions of {path:?}"))?;
+            }
+            sockets.fds.push(fd);
+            sockets.addresses.push(address.to_owned());
+        }
+        Ok(sockets)
+    }
+
+    pub fn fds(&self) -> Vec<RawFd> {
+        self.fds.iter().map(AsRawFd::as_raw_fd).collect()
+    }
+
+    /// The value of LISTEN_FDNAMES
+    pub fn names(&self) -> String {
+        self.addresses
+            .iter()
+            .map(SocketAddress::name)
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// The stream sockets, where connections can be accepted
+    pub fn listeners(&self) -> Vec<(RawFd, &SocketAddress)> {
+        self.fds
+            .iter()
+            .zip(&self.addresses)
+            .filter(|(_, address)| address.is_stream())
+            .map(|(fd, address)| (fd.as_raw_fd(), address))
+            .collect()
+    }
+
+    /// Wait until there is a pending connection or some data on any of the
+    /// sockets, without consuming it
+    pub async fn wait_activity(&self) -> Result<()> {
+        let fds = self
+            .fds()
+            .into_iter()
+            .map(AsyncFd::new)
+            .collect::<io::Result<Vec<_>>>()
+            .context("unable to watch the sockets")?;
+        future::select_all(fds.iter().map(|fd| Box::pin(fd.readable())))
+            .await
+            .0
+            .context("unable to watch the sockets")?;
+        Ok(())
+    }
+}
+
+// Remove the file left by a previous instance of rsvc
+fn prepare_path(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("unable to create directory {parent:?}"))?;
+    }
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => {
+            Err(err).with_context(|| format!("unable to remove {path:?}"))
+        }
+        _ => Ok(()),
+    }
+}
+
+impl Drop for ListenSockets {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            if let Err(err) = fs::remove_file(path) {
+                warn!("unable to remove {path:?}: {err}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::net::UnixStream;
+
+    use super::*;
+
+    #[test]
+    fn bind() {
+        let path = std::env::temp_dir().join(format!("rinit-socket-test-{}", std::process::id()));
+        let sockets =
+            ListenSockets::bind(&Socket::new(vec![SocketAddress::UnixStream(path.clone())]))
+                .unwrap();
+        assert_eq!(sockets.fds().len(), 1);
+        assert_eq!(
+            sockets.names(),
+            format!("rinit-socket-test-{}", std::process::id())
+        );
+        // The socket is already listening
+        UnixStream::connect(&path).unwrap();
+        drop(sockets);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn wait_activity() {
+        let path = std::env::temp_dir().join(format!("rinit-activity-test-{}", std::process::id()));
+        let sockets = ListenSockets::bind(&Socket::new(vec![SocketAddress::UnixDatagram(
+            path.clone(),
+        )]))
+        .unwrap();
+        let client = std::os::unix::net::UnixDatagram::unbound().unwrap();
+        client.send_to(b"ping", &path).unwrap();
+        tokio::time::timeout(
+            std::time::Duration::from_millis(500),
+            sockets.wait_activity(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+    }
+}
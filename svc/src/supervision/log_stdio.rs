@@ -65,21 +65,22 @@ pub fn log_buf(
 }
 
 /// We need the handle open, otherwise the tracing subscriber won't work
+/// stdout is None when it is not a pipe, e.g. for inetd-style services
 pub async fn log_output(
-    mut stdout: ChildStdout,
+    mut stdout: Option<ChildStdout>,
     mut stderr: ChildStderr,
     mut rx: tokio::sync::oneshot::Receiver<()>,
 ) -> Result<()> {
     let mut stdout_line = String::new();
     let mut stderr_line = String::new();
-    let mut stdout_open = true;
+    let mut stdout_open = stdout.is_some();
     let mut stderr_open = true;
     loop {
         let mut stdout_buf = [0; 512];
         let mut stderr_buf = [0; 512];
         select! {
             read = async {
-                if stdout_open {
+                if let Some(stdout) = stdout.as_mut().filter(|_| stdout_open) {
                     stdout.read(&mut stdout_buf[..]).await
                 } else {
                     future::pending::<()>().await;
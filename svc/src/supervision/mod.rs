@@ -1,9 +1,45 @@
+mod accept_connections;
+pub use accept_connections::accept_connections;
+mod cgroup;
+pub use cgroup::{
+    enable_controllers,
+    set_resources,
+    Cgroup,
+    LimitEvents,
+};
 mod exec_script;
 pub use exec_script::exec_script;
 mod kill_process;
-pub use kill_process::kill_process;
+pub use kill_process::{
+    kill_process,
+    kill_remaining,
+};
+mod listen_sockets;
+pub use listen_sockets::ListenSockets;
 mod log_stdio;
 pub use log_stdio::log_output;
+mod notify_socket;
+pub use notify_socket::{
+    NotifyMessage,
+    NotifySocket,
+};
+mod pidfd_send_signal;
+pub use pidfd_send_signal::{
+    pidfd_open,
+    pidfd_send_signal,
+};
+mod probe;
+pub use probe::{
+    check_probe,
+    wait_probe,
+};
+mod process_attributes;
+pub use process_attributes::{
+    find_credentials,
+    ProcessAttributes,
+};
+mod process_sandbox;
+pub use process_sandbox::ProcessSandbox;
 mod run_short_lived_script;
 pub use run_short_lived_script::run_short_lived_script;
 mod supervisor;
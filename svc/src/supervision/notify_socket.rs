@@ -0,0 +1,290 @@
+use std::{
+    fs,
+    io::{
+        self,
+        IoSliceMut,
+    },
+    os::fd::AsRawFd,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use nix::{
+    cmsg_space,
+    sys::socket::{
+        recvmsg,
+        setsockopt,
+        sockopt::PassCred,
+        ControlMessageOwned,
+        MsgFlags,
+        UnixCredentials,
+    },
+    unistd::{
+        chown,
+        getpgid,
+        Gid,
+        Pid,
+        Uid,
+    },
+};
+use rinit_service::service_state::NotifyState;
+use tokio::{
+    io::Interest,
+    net::UnixDatagram,
+};
+use tracing::warn;
+
+use crate::supervision::Cgroup;
+
+/// A message sent by a service through its notify socket
+#[derive(Debug, PartialEq, Eq)]
+pub enum NotifyMessage {
+    Ready,
+    Reloading,
+    Stopping,
+    Status(String),
+    MainPid(i32),
+    Watchdog,
+}
+
+impl NotifyMessage {
+    pub fn apply(
+        self,
+        state: &mut NotifyState,
+    ) {
+        match self {
+            NotifyMessage::Ready => state.reloading = false,
+            NotifyMessage::Reloading => state.reloading = true,
+            NotifyMessage::Stopping => state.stopping = true,
+            NotifyMessage::Status(status) => state.status = Some(status),
+            NotifyMessage::MainPid(pid) => state.main_pid = Some(pid),
+            // Keepalives are handled by the supervisor
+            NotifyMessage::Watchdog => {}
+        }
+    }
+}
+
+/// Parse a datagram sent to the notify socket. It contains newline-separated
+/// assignments, the unknown ones are ignored
+pub fn parse_notify_message(message: &str) -> Vec<NotifyMessage> {
+    message
+        .lines()
+        .filter_map(|line| {
+            match line.split_once('=')? {
+                ("READY", "1") => Some(NotifyMessage::Ready),
+                ("RELOADING", "1") => Some(NotifyMessage::Reloading),
+                ("STOPPING", "1") => Some(NotifyMessage::Stopping),
+                ("STATUS", status) => Some(NotifyMessage::Status(status.to_owned())),
+                ("MAINPID", pid) => pid.parse().ok().map(NotifyMessage::MainPid),
+                ("WATCHDOG", "1") => Some(NotifyMessage::Watchdog),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// The socket exported in NOTIFY_SOCKET, where the service sends its
+/// readiness and status updates
+pub struct NotifySocket {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+/// Whether the process sending a message belongs to the service, i.e. it is
+/// in its process group or in its cgroup
+fn is_sender_allowed(
+    sender: Pid,
+    pgid: Option<Pid>,
+    cgroup: Option<&Cgroup>,
+) -> bool {
+    pgid.is_some_and(|pgid| getpgid(Some(sender)) == Ok(pgid))
+        || cgroup.is_some_and(|cgroup| cgroup.contains(sender))
+}
+
+impl NotifySocket {
+    /// Bind the socket and give it to the user and group of the service, so
+    /// that it can send messages after dropping its privileges
+    pub fn bind(
+        path: PathBuf,
+        uid: Option<Uid>,
+        gid: Option<Gid>,
+    ) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("unable to create directory {parent:?}"))?;
+        }
+        // Remove the socket left by a previous instance
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                Err(err).with_context(|| format!("unable to remove {path:?}"))?
+            }
+            _ => {}
+        }
+        let socket = UnixDatagram::bind(&path)
+            .with_context(|| format!("unable to bind notify socket {path:?}"))?;
+        // Receive the credentials of the sender along with each message
+        setsockopt(socket.as_raw_fd(), PassCred, &true)
+            .context("unable to set SO_PASSCRED on the notify socket")?;
+        if uid.is_some() || gid.is_some() {
+            chown(&path, uid, gid)
+                .with_context(|| format!("unable to change owner of {path:?}"))?;
+        }
+        Ok(Self { socket, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Read the next datagram along with the pid of its sender
+    async fn recv_from(&self) -> io::Result<(String, Option<Pid>)> {
+        let mut buf = [0; 4096];
+        loop {
+            self.socket.readable().await?;
+            let res = self.socket.try_io(Interest::READABLE, || {
+                let mut iov = [IoSliceMut::new(&mut buf)];
+                let mut cmsg = cmsg_space!(UnixCredentials);
+                let msg = recvmsg::<()>(
+                    self.socket.as_raw_fd(),
+                    &mut iov,
+                    Some(&mut cmsg),
+                    MsgFlags::MSG_DONTWAIT,
+                )?;
+                let sender = msg.cmsgs().find_map(|cmsg| {
+                    match cmsg {
+                        ControlMessageOwned::ScmCredentials(credentials) => {
+                            Some(Pid::from_raw(credentials.pid()))
+                        }
+                        _ => None,
+                    }
+                });
+                Ok((msg.bytes, sender))
+            });
+            match res {
+                Ok((len, sender)) => {
+                    return Ok((String::from_utf8_lossy(&buf[..len]).into_owned(), sender));
+                }
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Wait for the next datagram sent by a process of the service and parse
+    /// the messages it contains. The process group is the one of the main
+    /// process, the datagrams sent by any other process are dropped
+    pub async fn recv(
+        &self,
+        pgid: Option<Pid>,
+        cgroup: Option<&Cgroup>,
+    ) -> Result<Vec<NotifyMessage>> {
+        loop {
+            let (message, sender) = self
+                .recv_from()
+                .await
+                .context("unable to read from the notify socket")?;
+            match sender {
+                Some(sender) if is_sender_allowed(sender, pgid, cgroup) => {
+                    return Ok(parse_notify_message(&message));
+                }
+                _ => warn!("dropping notify message sent by {sender:?}, outside of the service"),
+            }
+        }
+    }
+
+    /// Wait until the service sends READY=1, the other messages received in
+    /// the meantime update the state
+    pub async fn wait_ready(
+        &self,
+        state: &mut NotifyState,
+        pgid: Option<Pid>,
+        cgroup: Option<&Cgroup>,
+    ) -> Result<()> {
+        loop {
+            let mut ready = false;
+            for message in self.recv(pgid, cgroup).await? {
+                ready |= message == NotifyMessage::Ready;
+                message.apply(state);
+            }
+            if ready {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl Drop for NotifySocket {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_file(&self.path) {
+            warn!("unable to remove notify socket {:?}: {err}", self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_message() {
+        assert_eq!(
+            parse_notify_message("READY=1\nSTATUS=Accepting connections\nMAINPID=42\nFOO=bar"),
+            vec![
+                NotifyMessage::Ready,
+                NotifyMessage::Status("Accepting connections".to_string()),
+                NotifyMessage::MainPid(42),
+            ]
+        );
+        assert_eq!(
+            parse_notify_message("RELOADING=1\nSTOPPING=1\nREADY=0\nWATCHDOG=1"),
+            vec![
+                NotifyMessage::Reloading,
+                NotifyMessage::Stopping,
+                NotifyMessage::Watchdog,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn wait_ready() {
+        let path = std::env::temp_dir().join(format!("rinit-notify-test-{}", std::process::id()));
+        let notify_socket = NotifySocket::bind(path.clone(), None, None).unwrap();
+        let client = UnixDatagram::unbound().unwrap();
+        client.send_to(b"STATUS=Starting", &path).await.unwrap();
+        client.send_to(b"MAINPID=42\nREADY=1", &path).await.unwrap();
+
+        let mut state = NotifyState::default();
+        notify_socket
+            .wait_ready(&mut state, Some(getpgid(None).unwrap()), None)
+            .await
+            .unwrap();
+        assert_eq!(state.status, Some("Starting".to_string()));
+        assert_eq!(state.main_pid, Some(42));
+        drop(notify_socket);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn drop_foreign_messages() {
+        let path =
+            std::env::temp_dir().join(format!("rinit-notify-foreign-{}", std::process::id()));
+        let notify_socket = NotifySocket::bind(path.clone(), None, None).unwrap();
+        let client = UnixDatagram::unbound().unwrap();
+        client.send_to(b"READY=1", &path).await.unwrap();
+
+        // The sender is not in the process group of init
+        let mut state = NotifyState::default();
+        let res = tokio::time::timeout(
+            std::time::Duration::from_millis(100),
+            notify_socket.wait_ready(&mut state, Some(Pid::from_raw(1)), None),
+        )
+        .await;
+        assert!(res.is_err());
+    }
+}
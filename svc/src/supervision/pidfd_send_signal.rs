@@ -1,9 +1,26 @@
 use std::{
     io,
-    os::unix::prelude::RawFd,
+    os::{
+        fd::{
+            FromRawFd,
+            OwnedFd,
+        },
+        unix::prelude::RawFd,
+    },
     ptr,
 };
 
+/// Open a file descriptor referring to the process. Unlike its PID, it can't
+/// be reused by another process after this one has been reaped
+pub fn pidfd_open(pid: i32) -> io::Result<OwnedFd> {
+    let ret = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(unsafe { OwnedFd::from_raw_fd(ret as RawFd) })
+    }
+}
+
 pub fn pidfd_send_signal(
     pidfd: RawFd,
     signal: i32,
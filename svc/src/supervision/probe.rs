@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use rinit_service::types::{
+    Probe,
+    ScriptEnvironment,
+};
+use tokio::{
+    net::{
+        TcpStream,
+        UnixStream,
+    },
+    time::sleep,
+};
+use tracing::warn;
+
+use crate::supervision::run_short_lived_script;
+
+/// How often a readiness probe is checked
+const PROBE_INTERVAL: Duration = Duration::from_millis(100);
+
+pub async fn check_probe(
+    probe: &Probe,
+    env: &ScriptEnvironment,
+) -> bool {
+    match probe {
+        Probe::UnixSocket(path) => UnixStream::connect(path).await.is_ok(),
+        Probe::TcpPort(port) => TcpStream::connect(("localhost", *port)).await.is_ok(),
+        Probe::PathExists(path) => path.exists(),
+        Probe::Script(script) => {
+            match run_short_lived_script(script, env, None).await {
+                Ok(res) => res,
+                Err(err) => {
+                    warn!("unable to run the probe script: {err}");
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// Poll the probe until it succeeds
+pub async fn wait_probe(
+    probe: &Probe,
+    env: &ScriptEnvironment,
+) {
+    while !check_probe(probe, env).await {
+        sleep(PROBE_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use tokio::net::UnixListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn check_unix_socket() {
+        let path = std::env::temp_dir().join(format!("rinit-probe-test-{}", std::process::id()));
+        let probe = Probe::UnixSocket(path.clone());
+        let env = ScriptEnvironment::new();
+        assert!(!check_probe(&probe, &env).await);
+
+        let _listener = UnixListener::bind(&path).unwrap();
+        assert!(check_probe(&probe, &env).await);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn check_path() {
+        let env = ScriptEnvironment::new();
+        assert!(check_probe(&Probe::PathExists(PathBuf::from("/")), &env).await);
+        assert!(!check_probe(&Probe::PathExists(PathBuf::from("/nonexistent")), &env).await);
+    }
+}
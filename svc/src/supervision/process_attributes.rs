@@ -0,0 +1,320 @@
+use std::{
+    ffi::CString,
+    io,
+    path::PathBuf,
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use nix::{
+    fcntl::{
+        open,
+        OFlag,
+    },
+    sched::{
+        sched_setaffinity,
+        CpuSet,
+    },
+    sys::{
+        resource::{
+            setrlimit,
+            Resource,
+        },
+        stat::Mode,
+    },
+    unistd::{
+        chdir,
+        chroot,
+        close,
+        getgrouplist,
+        setgid,
+        setgroups,
+        setuid,
+        write,
+        Gid,
+        Group,
+        Pid,
+        Uid,
+        User,
+    },
+};
+use rinit_service::types::{
+    IoPrioClass,
+    RLimitResource,
+    SchedPolicy,
+    Script,
+};
+
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+fn resource(resource: RLimitResource) -> Resource {
+    match resource {
+        RLimitResource::As => Resource::RLIMIT_AS,
+        RLimitResource::Core => Resource::RLIMIT_CORE,
+        RLimitResource::Cpu => Resource::RLIMIT_CPU,
+        RLimitResource::Data => Resource::RLIMIT_DATA,
+        RLimitResource::Fsize => Resource::RLIMIT_FSIZE,
+        RLimitResource::Memlock => Resource::RLIMIT_MEMLOCK,
+        RLimitResource::Msgqueue => Resource::RLIMIT_MSGQUEUE,
+        RLimitResource::Nice => Resource::RLIMIT_NICE,
+        RLimitResource::Nofile => Resource::RLIMIT_NOFILE,
+        RLimitResource::Nproc => Resource::RLIMIT_NPROC,
+        RLimitResource::Rtprio => Resource::RLIMIT_RTPRIO,
+        RLimitResource::Rttime => Resource::RLIMIT_RTTIME,
+        RLimitResource::Sigpending => Resource::RLIMIT_SIGPENDING,
+        RLimitResource::Stack => Resource::RLIMIT_STACK,
+    }
+}
+
+fn sched_policy(policy: SchedPolicy) -> libc::c_int {
+    match policy {
+        SchedPolicy::Other => libc::SCHED_OTHER,
+        SchedPolicy::Batch => libc::SCHED_BATCH,
+        SchedPolicy::Idle => libc::SCHED_IDLE,
+        SchedPolicy::Fifo => libc::SCHED_FIFO,
+        SchedPolicy::Rr => libc::SCHED_RR,
+    }
+}
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// The attributes of the process executing a script. They are converted
+/// before fork, so that they can be applied in the child without allocating
+pub struct ProcessAttributes {
+    rlimits: Vec<(Resource, libc::rlim_t, libc::rlim_t)>,
+    nice: Option<libc::c_int>,
+    io_priority: Option<libc::c_int>,
+    cpu_set: Option<CpuSet>,
+    scheduling: Option<(libc::c_int, libc::c_int)>,
+    // The value written to /proc/self/oom_score_adj
+    oom_score_adj: Option<([u8; 8], usize)>,
+    root_directory: Option<PathBuf>,
+    working_directory: Option<PathBuf>,
+    umask: Option<Mode>,
+    uid: Option<Uid>,
+    gid: Option<Gid>,
+    // None keeps the groups of rsvc
+    groups: Option<Vec<Gid>>,
+    login_env: Vec<(&'static str, String)>,
+}
+
+fn find_group(group: &str) -> Result<Gid> {
+    Ok(Group::from_name(group)
+        .with_context(|| format!("unable to get GID for group {}", group))?
+        .with_context(|| format!("unable to find GID for group {}", group))?
+        .gid)
+}
+
+/// Find the user and the group a script runs as. The primary group of the
+/// user is used, unless another one is set
+pub fn find_credentials(script: &Script) -> Result<(Option<User>, Option<Gid>)> {
+    let user = script
+        .user
+        .as_ref()
+        .map(|user| -> Result<User> {
+            User::from_name(user)
+                .with_context(|| format!("unable to get UID for user {}", user))?
+                .with_context(|| format!("unable to find UID for user {}", user))
+        })
+        .transpose()?;
+    let gid = match &script.group {
+        Some(group) => Some(find_group(group)?),
+        None => user.as_ref().map(|user| user.gid),
+    };
+    Ok((user, gid))
+}
+
+impl ProcessAttributes {
+    pub fn new(script: &Script) -> Result<Self> {
+        let exec = &script.exec;
+        let cpu_set = if exec.cpu_affinity.is_empty() {
+            None
+        } else {
+            let mut cpu_set = CpuSet::new();
+            for cpu in &exec.cpu_affinity {
+                cpu_set
+                    .set(*cpu)
+                    .with_context(|| format!("CPU {cpu} is out of range"))?;
+            }
+            Some(cpu_set)
+        };
+        let (user, gid) = find_credentials(script)?;
+        let supplementary_groups = exec
+            .supplementary_groups
+            .iter()
+            .map(|group| find_group(group))
+            .collect::<Result<Vec<_>>>()?;
+        let (groups, login_env) = match &user {
+            Some(user) => {
+                // Only root can change the groups, as initgroups does
+                let mut groups = if Uid::effective().is_root() {
+                    let name = CString::new(user.name.as_str())
+                        .context("the user name contains a nul byte")?;
+                    getgrouplist(&name, gid.unwrap())
+                        .with_context(|| format!("unable to get the groups of {}", user.name))?
+                } else {
+                    Vec::new()
+                };
+                groups.extend(supplementary_groups);
+                (
+                    Some(groups),
+                    vec![
+                        ("HOME", user.dir.to_string_lossy().into_owned()),
+                        ("USER", user.name.clone()),
+                        ("LOGNAME", user.name.clone()),
+                        ("SHELL", user.shell.to_string_lossy().into_owned()),
+                    ],
+                )
+            }
+            None if supplementary_groups.is_empty() => (None, Vec::new()),
+            None => (Some(supplementary_groups), Vec::new()),
+        };
+        let oom_score_adj = exec.oom_score_adj.map(|score| {
+            let score = score.to_string();
+            let mut buf = [0; 8];
+            buf[..score.len()].copy_from_slice(score.as_bytes());
+            (buf, score.len())
+        });
+        Ok(Self {
+            rlimits: exec
+                .rlimits
+                .iter()
+                .map(|rlimit| {
+                    (
+                        resource(rlimit.resource),
+                        rlimit.soft.unwrap_or(libc::RLIM_INFINITY),
+                        rlimit.hard.unwrap_or(libc::RLIM_INFINITY),
+                    )
+                })
+                .collect(),
+            nice: exec.nice,
+            io_priority: exec.io_priority.map(|io_priority| {
+                let class = match io_priority.class {
+                    IoPrioClass::Realtime => 1,
+                    IoPrioClass::BestEffort => 2,
+                    IoPrioClass::Idle => 3,
+                };
+                class << IOPRIO_CLASS_SHIFT | io_priority.level as libc::c_int
+            }),
+            cpu_set,
+            scheduling: exec.scheduling.map(|scheduling| {
+                (
+                    sched_policy(scheduling.policy),
+                    scheduling.priority as libc::c_int,
+                )
+            }),
+            oom_score_adj,
+            root_directory: exec.root_directory.clone(),
+            working_directory: exec.working_directory.clone(),
+            umask: exec.umask.map(Mode::from_bits_truncate),
+            uid: user.as_ref().map(|user| user.uid),
+            gid,
+            groups,
+            login_env,
+        })
+    }
+
+    /// The environment of a login shell of the user, if it is set
+    pub fn login_env(&self) -> &[(&'static str, String)] {
+        &self.login_env
+    }
+
+    /// Apply the attributes to the current process. The credentials are
+    /// changed last, since raising the limits or the priority needs
+    /// privileges. This runs after fork, so it must not allocate
+    pub fn apply(&self) -> io::Result<()> {
+        for (resource, soft, hard) in &self.rlimits {
+            setrlimit(*resource, *soft, *hard)?;
+        }
+        if let Some(nice) = self.nice {
+            check(unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) })?;
+        }
+        if let Some(io_priority) = self.io_priority {
+            check(unsafe {
+                libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, io_priority)
+                    as libc::c_int
+            })?;
+        }
+        if let Some(cpu_set) = &self.cpu_set {
+            sched_setaffinity(Pid::from_raw(0), cpu_set)?;
+        }
+        if let Some((policy, priority)) = self.scheduling {
+            let param = libc::sched_param {
+                sched_priority: priority,
+            };
+            check(unsafe { libc::sched_setscheduler(0, policy, &param) })?;
+        }
+        if let Some((buf, len)) = &self.oom_score_adj {
+            // The path is copied on the stack by nix, it doesn't allocate
+            let fd = open(
+                "/proc/self/oom_score_adj",
+                OFlag::O_WRONLY | OFlag::O_CLOEXEC,
+                Mode::empty(),
+            )?;
+            let res = write(fd, &buf[..*len]);
+            close(fd)?;
+            res?;
+        }
+        if let Some(root_directory) = &self.root_directory {
+            chroot(root_directory)?;
+            // Do not leave the working directory outside of the new root
+            chdir("/")?;
+        }
+        if let Some(working_directory) = &self.working_directory {
+            chdir(working_directory)?;
+        }
+        if let Some(umask) = self.umask {
+            nix::sys::stat::umask(umask);
+        }
+        // A user that is not root can't change its groups, unless some
+        // supplementary groups have been set explicitly
+        match &self.groups {
+            Some(groups) if !groups.is_empty() || Uid::effective().is_root() => setgroups(groups)?,
+            _ => {}
+        }
+        if let Some(gid) = self.gid {
+            setgid(gid)?;
+        }
+        if let Some(uid) = self.uid {
+            setuid(uid)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rinit_service::types::{
+        RLimit,
+        ScriptPrefix,
+    };
+
+    use super::*;
+
+    #[test]
+    fn convert_attributes() {
+        let mut script = Script::new(ScriptPrefix::Sh, "true".to_string());
+        script.exec.rlimits.push(RLimit {
+            resource: RLimitResource::Nofile,
+            soft: Some(1024),
+            hard: None,
+        });
+        script.exec.oom_score_adj = Some(-1000);
+        let attributes = ProcessAttributes::new(&script).unwrap();
+        assert_eq!(
+            attributes.rlimits,
+            vec![(Resource::RLIMIT_NOFILE, 1024, libc::RLIM_INFINITY)]
+        );
+        let (buf, len) = attributes.oom_score_adj.unwrap();
+        assert_eq!(&buf[..len], b"-1000");
+    }
+}
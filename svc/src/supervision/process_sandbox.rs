@@ -0,0 +1,349 @@
+use std::{
+    ffi::OsString,
+    fs,
+    io,
+    mem,
+    os::unix::ffi::OsStringExt,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+use anyhow::{
+    bail,
+    ensure,
+    Context,
+    Result,
+};
+use nix::{
+    mount::{
+        mount,
+        MsFlags,
+    },
+    sched::{
+        unshare,
+        CloneFlags,
+    },
+    sys::{
+        stat::{
+            makedev,
+            mknod,
+            umask,
+            Mode,
+            SFlag,
+        },
+        statvfs::{
+            statvfs,
+            FsFlags,
+        },
+    },
+    unistd::{
+        close,
+        mkdir,
+        symlinkat,
+        Uid,
+    },
+};
+use rinit_service::types::Sandbox;
+
+const NONE: Option<&str> = None;
+
+// The pseudo devices available with private_devices, with their major and
+// minor numbers
+const DEVICES: [(&str, u64, u64); 6] = [
+    ("/dev/null", 1, 3),
+    ("/dev/zero", 1, 5),
+    ("/dev/full", 1, 7),
+    ("/dev/random", 1, 8),
+    ("/dev/urandom", 1, 9),
+    ("/dev/tty", 5, 0),
+];
+const DEVICE_LINKS: [(&str, &str); 4] = [
+    ("/proc/self/fd", "/dev/fd"),
+    ("/proc/self/fd/0", "/dev/stdin"),
+    ("/proc/self/fd/1", "/dev/stdout"),
+    ("/proc/self/fd/2", "/dev/stderr"),
+];
+// The flags of a mount that a bind remount would clear if not passed again
+const KEPT_FLAGS: [(FsFlags, MsFlags); 6] = [
+    (FsFlags::ST_RDONLY, MsFlags::MS_RDONLY),
+    (FsFlags::ST_NOSUID, MsFlags::MS_NOSUID),
+    (FsFlags::ST_NODEV, MsFlags::MS_NODEV),
+    (FsFlags::ST_NOEXEC, MsFlags::MS_NOEXEC),
+    (FsFlags::ST_NOATIME, MsFlags::MS_NOATIME),
+    (FsFlags::ST_NODIRATIME, MsFlags::MS_NODIRATIME),
+];
+
+fn check(ret: libc::c_int) -> io::Result<()> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// Keep the paths that exist. The default paths of protect_system and
+/// protect_home are skipped when missing, the ones set by the user are not
+fn existing_paths(
+    paths: Vec<PathBuf>,
+    defaults: &[&str],
+) -> Result<Vec<(PathBuf, bool)>> {
+    let mut existing = Vec::new();
+    for path in paths {
+        match path.symlink_metadata() {
+            Ok(metadata) => existing.push((path, metadata.is_dir())),
+            Err(_) if defaults.iter().any(|default| path == Path::new(default)) => {}
+            Err(err) => bail!("unable to access {path:?}: {err}"),
+        }
+    }
+    Ok(existing)
+}
+
+/// Decode the octal escapes, like \040 for a space, of a field of
+/// /proc/self/mountinfo
+fn unescape_mountinfo(field: &[u8]) -> PathBuf {
+    let mut path = Vec::with_capacity(field.len());
+    let mut i = 0;
+    while i < field.len() {
+        match field.get(i + 1..i + 4) {
+            Some(octal) if field[i] == b'\\' && octal.iter().all(|c| (b'0'..=b'7').contains(c)) => {
+                path.push(
+                    octal
+                        .iter()
+                        .fold(0u32, |acc, c| acc * 8 + u32::from(c - b'0'))
+                        as u8,
+                );
+                i += 4;
+            }
+            _ => {
+                path.push(field[i]);
+                i += 1;
+            }
+        }
+    }
+    PathBuf::from(OsString::from_vec(path))
+}
+
+/// The mount points below the read-only paths. MS_REC binds them too, but the
+/// read-only remount only applies to the top mount, so each of them is
+/// remounted separately
+fn submounts(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mountinfo =
+        fs::read("/proc/self/mountinfo").context("unable to read /proc/self/mountinfo")?;
+    Ok(mountinfo
+        .split(|c| *c == b'\n')
+        .filter_map(|line| line.split(|c| *c == b' ').nth(4))
+        .map(unescape_mountinfo)
+        .filter(|mount_point| {
+            paths
+                .iter()
+                .any(|path| mount_point != path && mount_point.starts_with(path))
+        })
+        .collect())
+}
+
+fn mount_tmpfs(
+    target: &Path,
+    flags: MsFlags,
+    options: &str,
+) -> nix::Result<()> {
+    mount(Some("tmpfs"), target, Some("tmpfs"), flags, Some(options))
+}
+
+/// Add flags to a bind mount. The ones it already has, like nosuid, are read
+/// back and passed again, otherwise the remount would clear them
+fn remount(
+    target: &Path,
+    flags: MsFlags,
+) -> nix::Result<()> {
+    let current = statvfs(target)?.flags();
+    let kept = KEPT_FLAGS
+        .iter()
+        .filter(|(fs_flag, _)| current.contains(*fs_flag))
+        .fold(MsFlags::empty(), |kept, (_, ms_flag)| kept | *ms_flag);
+    mount(
+        NONE,
+        target,
+        NONE,
+        MsFlags::MS_BIND | MsFlags::MS_REMOUNT | kept | flags,
+        NONE,
+    )
+}
+
+/// Bind mount a path on another one and remount it with flags, since they are
+/// ignored by the first call
+fn bind_mount(
+    source: &Path,
+    target: &Path,
+    flags: MsFlags,
+) -> nix::Result<()> {
+    mount(
+        Some(source),
+        target,
+        NONE,
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        NONE,
+    )?;
+    remount(target, flags)
+}
+
+/// Populate the new /dev with the pseudo devices only
+fn create_devices() -> nix::Result<()> {
+    // The permissions must not be restricted by the umask of rsvc
+    let previous = umask(Mode::empty());
+    let res = (|| {
+        for (path, major, minor) in DEVICES {
+            mknod(
+                path,
+                SFlag::S_IFCHR,
+                Mode::from_bits_truncate(0o666),
+                makedev(major, minor),
+            )?;
+        }
+        for (target, link) in DEVICE_LINKS {
+            symlinkat(target, None, link)?;
+        }
+        mkdir("/dev/shm", Mode::from_bits_truncate(0o1777))
+    })();
+    umask(previous);
+    res
+}
+
+/// A new network namespace has its loopback interface down
+fn loopback_up() -> io::Result<()> {
+    let fd = unsafe { libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0) };
+    check(fd)?;
+    let mut ifreq: libc::ifreq = unsafe { mem::zeroed() };
+    for (dst, src) in ifreq.ifr_name.iter_mut().zip(b"lo") {
+        *dst = *src as libc::c_char;
+    }
+    let res = check(unsafe { libc::ioctl(fd, libc::SIOCGIFFLAGS, &mut ifreq) }).and_then(|_| {
+        unsafe {
+            ifreq.ifr_ifru.ifru_flags |= (libc::IFF_UP | libc::IFF_RUNNING) as libc::c_short;
+        }
+        check(unsafe { libc::ioctl(fd, libc::SIOCSIFFLAGS, &ifreq) })
+    });
+    close(fd)?;
+    res
+}
+
+/// The namespaces and mounts of a sandboxed service. The paths are checked
+/// before fork, so that they can be mounted in the child without allocating
+pub struct ProcessSandbox {
+    unshare: CloneFlags,
+    private_tmp: Vec<PathBuf>,
+    read_only: Vec<PathBuf>,
+    read_only_submounts: Vec<PathBuf>,
+    // Whether each path is a directory, the files are hidden differently
+    inaccessible: Vec<(PathBuf, bool)>,
+    private_devices: bool,
+    private_network: bool,
+    no_new_privileges: bool,
+}
+
+impl ProcessSandbox {
+    pub fn new(sandbox: &Sandbox) -> Result<Self> {
+        let mut unshare = CloneFlags::empty();
+        if sandbox.needs_mount_namespace() {
+            unshare |= CloneFlags::CLONE_NEWNS;
+        }
+        if sandbox.private_network {
+            unshare |= CloneFlags::CLONE_NEWNET;
+        }
+        ensure!(
+            unshare.is_empty() || Uid::effective().is_root(),
+            "rsvc must run as root to create the namespaces of the sandbox"
+        );
+        let private_tmp = if sandbox.private_tmp {
+            existing_paths(
+                vec![PathBuf::from("/tmp"), PathBuf::from("/var/tmp")],
+                &["/tmp", "/var/tmp"],
+            )?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect()
+        } else {
+            Vec::new()
+        };
+        let read_only: Vec<PathBuf> = existing_paths(
+            sandbox.all_read_only_paths(),
+            &Sandbox::PROTECT_SYSTEM_PATHS,
+        )?
+        .into_iter()
+        .map(|(path, _)| path)
+        .collect();
+        let read_only_submounts = if read_only.is_empty() {
+            Vec::new()
+        } else {
+            submounts(&read_only)?
+        };
+        Ok(Self {
+            unshare,
+            private_tmp,
+            read_only,
+            read_only_submounts,
+            inaccessible: existing_paths(
+                sandbox.all_inaccessible_paths(),
+                &Sandbox::PROTECT_HOME_PATHS,
+            )?,
+            private_devices: sandbox.private_devices,
+            private_network: sandbox.private_network,
+            no_new_privileges: sandbox.no_new_privileges,
+        })
+    }
+
+    fn mount(&self) -> nix::Result<()> {
+        // Do not propagate the mounts below to the namespace of rsvc
+        mount(NONE, "/", NONE, MsFlags::MS_REC | MsFlags::MS_SLAVE, NONE)?;
+        // The read-only paths are remounted first, the parser ensures that none
+        // of them is hidden by the mounts below
+        for path in &self.read_only {
+            bind_mount(path, path, MsFlags::MS_RDONLY)?;
+        }
+        for path in &self.read_only_submounts {
+            remount(path, MsFlags::MS_RDONLY)?;
+        }
+        for path in &self.private_tmp {
+            mount_tmpfs(path, MsFlags::MS_NOSUID | MsFlags::MS_NODEV, "mode=1777")?;
+        }
+        let hidden =
+            MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC;
+        for (path, is_dir) in &self.inaccessible {
+            if *is_dir {
+                mount_tmpfs(path, hidden, "mode=000")?;
+            } else {
+                // /dev/null can't be opened on a nodev mount
+                bind_mount(Path::new("/dev/null"), path, hidden)?;
+            }
+        }
+        if self.private_devices {
+            mount_tmpfs(
+                Path::new("/dev"),
+                MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC,
+                "mode=755",
+            )?;
+            create_devices()?;
+        }
+        Ok(())
+    }
+
+    /// Enter the new namespaces and set up the mounts. It must run before the
+    /// credentials are dropped and before chroot, since the paths are the ones
+    /// seen by rsvc. This runs after fork, so it must not allocate
+    pub fn apply(&self) -> io::Result<()> {
+        if !self.unshare.is_empty() {
+            unshare(self.unshare)?;
+        }
+        if self.unshare.contains(CloneFlags::CLONE_NEWNS) {
+            self.mount()?;
+        }
+        if self.private_network {
+            loopback_up()?;
+        }
+        if self.no_new_privileges {
+            check(unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) })?;
+        }
+        Ok(())
+    }
+}
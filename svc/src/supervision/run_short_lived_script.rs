@@ -8,6 +8,7 @@ use anyhow::{
     Result,
 };
 use rinit_service::types::{
+    Sandbox,
     Script,
     ScriptEnvironment,
 };
@@ -36,23 +37,20 @@ enum ScriptResult {
 pub async fn run_short_lived_script(
     script: &Script,
     env: &ScriptEnvironment,
+    sandbox: Option<&Sandbox>,
 ) -> Result<bool> {
     let script_timeout = Duration::from_millis(script.timeout as u64);
 
     let mut time_tried = 0;
     let success = loop {
-        let (mut child, _) = exec_script(script, env)
+        let (mut child, _) = exec_script(script, env, None, None, None, sandbox)
             .await
             .context("unable to execute script")?;
         let (tx, rx) = oneshot::channel();
         // TODO
         let logger = task::spawn(
-            log_output(
-                child.stdout.take().unwrap(),
-                child.stderr.take().unwrap(),
-                rx,
-            )
-            .with_current_subscriber(),
+            log_output(child.stdout.take(), child.stderr.take().unwrap(), rx)
+                .with_current_subscriber(),
         );
         let timeout_res = timeout(script_timeout, child.wait()).await;
         let script_res = if let Ok(exit_status) = timeout_res {
@@ -82,7 +80,7 @@ pub async fn run_short_lived_script(
             // The script didn't exit within timeout
             ScriptResult::TimedOut => {
                 // Kill it and try again
-                kill_process(&mut child, script.down_signal, script.timeout_kill).await?;
+                kill_process(&mut child, &script.get_down_steps(), script.kill_mode, None).await?;
             }
         }
 
@@ -116,7 +114,7 @@ mod tests {
     async fn test_run_script_success() {
         let script = Script::new(ScriptPrefix::Bash, "exit 0".to_string());
         assert!(
-            run_short_lived_script(&script, &ScriptEnvironment::default())
+            run_short_lived_script(&script, &ScriptEnvironment::default(), None)
                 .await
                 .unwrap()
         );
@@ -126,7 +124,7 @@ mod tests {
     async fn test_run_script_failure() {
         let script = Script::new(ScriptPrefix::Bash, "exit 1".to_string());
         assert!(
-            !run_short_lived_script(&script, &ScriptEnvironment::default())
+            !run_short_lived_script(&script, &ScriptEnvironment::default(), None)
                 .await
                 .unwrap()
         );
@@ -137,7 +135,7 @@ mod tests {
         let mut script = Script::new(ScriptPrefix::Bash, "sleep 15".to_string());
         script.timeout = 10;
         assert!(
-            !run_short_lived_script(&script, &ScriptEnvironment::default())
+            !run_short_lived_script(&script, &ScriptEnvironment::default(), None)
                 .await
                 .unwrap()
         );
@@ -154,7 +152,7 @@ mod tests {
         script.down_signal = 10;
         script.max_deaths = 1;
         assert!(
-            !run_short_lived_script(&script, &ScriptEnvironment::default())
+            !run_short_lived_script(&script, &ScriptEnvironment::default(), None)
                 .await
                 .unwrap()
         );
@@ -165,7 +163,7 @@ mod tests {
         let filename = "test_run_script_side_effects";
         let script = Script::new(ScriptPrefix::Bash, format!("touch {filename}"));
         assert!(
-            run_short_lived_script(&script, &ScriptEnvironment::default())
+            run_short_lived_script(&script, &ScriptEnvironment::default(), None)
                 .await
                 .unwrap()
         );
@@ -180,7 +178,7 @@ mod tests {
         let script = Script::new(ScriptPrefix::Bash, "touch ${filename}".to_string());
         let mut env = ScriptEnvironment::new();
         env.add("filename", filename.to_string());
-        assert!(run_short_lived_script(&script, &env).await.unwrap());
+        assert!(run_short_lived_script(&script, &env, None).await.unwrap());
         assert!(Path::new(filename).exists());
         // cleanup
         remove_file(filename).await.unwrap();
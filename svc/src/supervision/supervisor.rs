@@ -1,6 +1,13 @@
 use std::{
+    collections::VecDeque,
+    fs,
+    io,
     process::ExitStatus,
-    time::Duration,
+    rc::Rc,
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
 use anyhow::{
@@ -10,10 +17,22 @@ use anyhow::{
 };
 use flexi_logger::writers::FileLogWriterHandle;
 use futures::future;
+use nix::unistd::Pid;
 use rinit_ipc::Request;
-use rinit_service::types::Longrun;
+use rinit_service::{
+    service_state::{
+        Health,
+        IdleServiceState,
+        NotifyState,
+    },
+    types::{
+        Longrun,
+        SignalStep,
+    },
+};
 use tokio::{
     self,
+    io::unix::AsyncFd,
     process::Child,
     select,
     sync::{
@@ -28,7 +47,13 @@ use tokio::{
         self,
         JoinHandle,
     },
-    time::timeout,
+    time::{
+        self,
+        interval_at,
+        sleep,
+        sleep_until,
+        timeout,
+    },
 };
 use tracing::{
     error,
@@ -37,14 +62,25 @@ use tracing::{
 };
 
 use crate::supervision::{
+    check_probe,
     exec_script,
     kill_process,
+    kill_remaining,
     log_output,
     run_short_lived_script,
+    wait_probe,
+    Cgroup,
+    LimitEvents,
+    ListenSockets,
+    NotifyMessage,
+    NotifySocket,
 };
 
 struct RunningScript {
     child: Child,
+    started: Instant,
+    // Kept open to receive the watchdog keepalives
+    notify: Option<AsyncFd<i32>>,
     logger: JoinHandle<Result<(), anyhow::Error>>,
     logger_stop: Sender<()>,
 }
@@ -53,13 +89,25 @@ pub struct Supervisor {
     running_script: Option<RunningScript>,
     terminate: watch::Receiver<()>,
     longrun: Longrun,
+    // When the process died while being supervised, within restart_limit_interval
+    deaths: VecDeque<Instant>,
+    notify_socket: Option<NotifySocket>,
+    notify_state: NotifyState,
+    // Owned by LiveService, they stay open when the process is restarted
+    sockets: Option<Rc<ListenSockets>>,
+    // None when cgroups are not available, the process group is used instead
+    cgroup: Option<Cgroup>,
+    // The limit events of the cgroup when the process was started
+    limit_events: LimitEvents,
+    /// Why the process exited last, when it hit one of the limits of its cgroup
+    pub failure_reason: Option<String>,
     // Store the fds of the logger so that they will stay open
     _fw_handle: FileLogWriterHandle,
 }
 
 enum ScriptResult {
     Exited(ExitStatus),
-    Running(RunningScript),
+    Running(Box<RunningScript>),
     Terminated,
 }
 
@@ -71,10 +119,20 @@ impl Supervisor {
         longrun: Longrun,
         terminate: watch::Receiver<()>,
         fw_handle: FileLogWriterHandle,
+        notify_socket: Option<NotifySocket>,
+        sockets: Option<Rc<ListenSockets>>,
+        cgroup: Option<Cgroup>,
     ) -> Self {
         Self {
             longrun,
             running_script: None,
+            deaths: VecDeque::new(),
+            notify_socket,
+            notify_state: NotifyState::default(),
+            sockets,
+            cgroup,
+            limit_events: LimitEvents::default(),
+            failure_reason: None,
             terminate,
             _fw_handle: fw_handle,
         }
@@ -89,20 +147,28 @@ impl Supervisor {
                 ScriptResult::Exited(status) => {
                     // TODO: Proper logging
                     warn!("process exited with {status}");
+                    self.check_limits();
                     time_tried += 1;
                     if let Some(finish_script) = &self.longrun.finish {
-                        if let Err(err) =
-                            run_short_lived_script(finish_script, &self.longrun.environment).await
+                        if let Err(err) = run_short_lived_script(
+                            finish_script,
+                            &self.longrun.environment,
+                            self.longrun.sandbox.as_ref(),
+                        )
+                        .await
                         {
                             error!("{err}");
                         }
                     }
-                    if time_tried == self.longrun.run.max_deaths {
+                    if time_tried == self.longrun.run.max_deaths
+                        || !self.longrun.run.restart.should_restart(&status)
+                        || !self.wait_restart_delay(time_tried as u32 - 1).await
+                    {
                         break false;
                     }
                 }
                 ScriptResult::Running(running_script) => {
-                    self.running_script = Some(running_script);
+                    self.running_script = Some(*running_script);
                     break true;
                 }
                 ScriptResult::Terminated => break false,
@@ -110,38 +176,160 @@ impl Supervisor {
         })
     }
 
+    /// Check whether the process should be restarted after it has exited, and
+    /// the state of the service until then. When the process has died too
+    /// many times within restart_limit_interval, the service has failed
+    fn check_restart(
+        &mut self,
+        status: &ExitStatus,
+    ) -> (IdleServiceState, bool) {
+        if !self.longrun.run.restart.should_restart(status) {
+            return (IdleServiceState::Down, false);
+        }
+        let now = Instant::now();
+        let interval = Duration::from_millis(self.longrun.run.restart_limit_interval as u64);
+        self.deaths
+            .retain(|death| now.duration_since(*death) < interval);
+        self.deaths.push_back(now);
+        if self.deaths.len() > self.longrun.run.restart_limit_burst as usize {
+            error!(
+                "process died {} times in {}ms, it won't be restarted",
+                self.deaths.len(),
+                self.longrun.run.restart_limit_interval
+            );
+            (IdleServiceState::Failed, false)
+        } else {
+            (IdleServiceState::Down, true)
+        }
+    }
+
+    /// Check whether the process has exited because it hit one of the limits
+    /// of its cgroup
+    fn check_limits(&mut self) {
+        self.failure_reason = self
+            .cgroup
+            .as_ref()
+            .and_then(|cgroup| cgroup.limit_events().reason_since(&self.limit_events));
+        if let Some(reason) = &self.failure_reason {
+            error!("process {reason}");
+        }
+    }
+
+    async fn send_failure_reason(
+        &self,
+        send: &mpsc::Sender<Request>,
+    ) {
+        // Only the limits of a cgroup are checked
+        if self.cgroup.is_none() {
+            return;
+        }
+        if let Err(err) = send
+            .send(Request::UpdateFailureReason(
+                self.longrun.name.to_owned(),
+                self.failure_reason.clone(),
+            ))
+            .await
+        {
+            error!("Could not notify the main thread: {err}");
+        }
+    }
+
+    async fn send_state(
+        &self,
+        send: &mpsc::Sender<Request>,
+        state: IdleServiceState,
+    ) {
+        if let Err(err) = send
+            .send(Request::UpdateServiceStatus(
+                self.longrun.name.to_owned(),
+                state,
+            ))
+            .await
+        {
+            error!("Could not notify the main thread: {err}");
+        }
+    }
+
+    /// Wait before restarting the process. Return false if the service has been
+    /// stopped in the meantime
+    async fn wait_restart_delay(
+        &mut self,
+        restarts: u32,
+    ) -> bool {
+        let delay = Duration::from_millis(self.longrun.run.get_restart_delay(restarts) as u64);
+        select! {
+            _ = sleep(delay) => true,
+            _ = self.terminate.changed() => false,
+        }
+    }
+
     async fn start_process(&mut self) -> Result<ScriptResult> {
+        let mut env = self.longrun.environment.clone();
+        if let Some(notify_socket) = &self.notify_socket {
+            env.add(
+                "NOTIFY_SOCKET",
+                notify_socket.path().to_string_lossy().into_owned(),
+            );
+        }
+        if let Some(watchdog) = self.longrun.run.watchdog {
+            env.add("WATCHDOG_USEC", (watchdog as u64 * 1000).to_string());
+        }
+        self.notify_state = NotifyState::default();
+        let notify_socket = self.notify_socket.as_ref();
+        let notify_state = &mut self.notify_state;
         let script = &self.longrun.run;
         let script_timeout = Duration::from_millis(script.timeout as u64);
+        let started = Instant::now();
 
-        let (mut child, notify) = exec_script(script, &self.longrun.environment)
-            .await
-            .context("unable to execute script")?;
+        let cgroup = self.cgroup.as_ref();
+        if let Some(cgroup) = cgroup {
+            self.limit_events = cgroup.limit_events();
+        }
+        let (mut child, notify) = exec_script(
+            script,
+            &env,
+            self.sockets.as_deref(),
+            None,
+            cgroup,
+            self.longrun.sandbox.as_ref(),
+        )
+        .await
+        .context("unable to execute script")?;
+        // The process creates its own process group
+        let pgid = child.id().map(|pid| Pid::from_raw(pid as i32));
         let (tx, rx) = oneshot::channel();
         // let (fw_handle, subscriber) = self.logger_subscriber();
         let logger = task::spawn_local(
-            log_output(
-                child.stdout.take().unwrap(),
-                child.stderr.take().unwrap(),
-                rx,
-            )
-            .with_current_subscriber(),
+            log_output(child.stdout.take(), child.stderr.take().unwrap(), rx)
+                .with_current_subscriber(),
         );
         Ok(select! {
             timeout_res = timeout(script_timeout, child.wait()) => {
                 if let Ok(exit_status) = timeout_res {
                     let status = exit_status.context("unable to call wait on child")?;
+                    kill_remaining(script.kill_mode, cgroup).await?;
+                    if !tx.is_closed() {
+                        tx.send(()).unwrap();
+                    }
+                    logger.await??;
+                    ScriptResult::Exited(status)
+                } else if notify_socket.is_some() || script.readiness.is_some() {
+                    // The process is only up after it has sent READY=1 or the
+                    // readiness probe has succeeded
+                    warn!("process was not ready within {}ms", script.timeout);
+                    kill_process(&mut child, &script.get_down_steps(), script.kill_mode, cgroup).await?;
+                    let status = child.wait().await.context("unable to call wait on child")?;
                     if !tx.is_closed() {
                         tx.send(()).unwrap();
                     }
                     logger.await??;
                     ScriptResult::Exited(status)
                 } else {
-                    ScriptResult::Running(RunningScript {child, logger, logger_stop: tx})
+                    ScriptResult::Running(Box::new(RunningScript {child, started, notify, logger, logger_stop: tx}))
                 }
             }
             _ = self.terminate.changed() => {
-                kill_process(&mut child, script.down_signal, script.timeout_kill).await?;
+                stop_process(&self.longrun, &mut child, cgroup).await?;
                 if !tx.is_closed() {
                     tx.send(()).unwrap();
                 }
@@ -149,7 +337,7 @@ impl Supervisor {
                 ScriptResult::Terminated
             }
             res = async {
-                if let Some(notify) = notify {
+                if let Some(notify) = &notify {
                     if let Err(err) = notify.readable().await {
                         tracing::info!("HEREREE");
                         Some(err)
@@ -164,92 +352,364 @@ impl Supervisor {
                 if let Some(err) = res {
                     bail!("Could not read notify fd: {err}");
                 } else {
-                    ScriptResult::Running(RunningScript {child, logger, logger_stop: tx})
+                    ScriptResult::Running(Box::new(RunningScript {child, started, notify, logger, logger_stop: tx}))
                 }
             }
+            res = async {
+                if let Some(notify_socket) = notify_socket {
+                    notify_socket.wait_ready(notify_state, pgid, cgroup).await
+                } else {
+                    future::pending().await
+                }
+            } => {
+                res?;
+                ScriptResult::Running(Box::new(RunningScript {child, started, notify, logger, logger_stop: tx}))
+            }
+            _ = async {
+                if let Some(readiness) = &script.readiness {
+                    wait_probe(readiness, &env).await
+                } else {
+                    future::pending().await
+                }
+            } => {
+                ScriptResult::Running(Box::new(RunningScript {child, started, notify, logger, logger_stop: tx}))
+            }
         })
     }
 
+    async fn send_health(
+        &self,
+        send: &mpsc::Sender<Request>,
+        health: Option<Health>,
+    ) {
+        if let Err(err) = send
+            .send(Request::UpdateHealth(self.longrun.name.to_owned(), health))
+            .await
+        {
+            error!("Could not notify the main thread: {err}");
+        }
+    }
+
+    async fn send_notify_state(
+        &self,
+        send: &mpsc::Sender<Request>,
+    ) {
+        if self.notify_socket.is_none() {
+            return;
+        }
+        if let Err(err) = send
+            .send(Request::UpdateNotifyState(
+                self.longrun.name.to_owned(),
+                self.notify_state.clone(),
+            ))
+            .await
+        {
+            error!("Could not notify the main thread: {err}");
+        }
+    }
+
     pub async fn supervise(
         &mut self,
         send: mpsc::Sender<Request>,
     ) -> Result<()> {
         debug_assert!(self.running_script.is_some());
+        // Send the status received while the process was starting
+        self.send_notify_state(&send).await;
+        // How many times the process has been restarted in a row
+        let mut restarts = 0;
         loop {
             // This is never empty. Move out the value so that we can use logger and
             // logger_stop
             let mut running_script = self.running_script.take().unwrap();
-            let res = select! {
-                exit_status = running_script.child.wait() => {
-                    ScriptResult::Exited(exit_status.context("unable to wait on child process")?)
-                }
-                _ = self.terminate.changed() => {
-                    ScriptResult::Terminated
+            let liveness_period = Duration::from_millis(self.longrun.run.liveness_interval as u64);
+            let mut liveness_interval =
+                interval_at(time::Instant::now() + liveness_period, liveness_period);
+            let mut health = None;
+            let watchdog_period = self
+                .longrun
+                .run
+                .watchdog
+                .map(|watchdog| Duration::from_millis(watchdog as u64));
+            let mut watchdog_deadline = time::Instant::now() + watchdog_period.unwrap_or_default();
+            let res = loop {
+                let notify_socket = self.notify_socket.as_ref();
+                let pgid = running_script.child.id().map(|pid| Pid::from_raw(pid as i32));
+                let cgroup = self.cgroup.as_ref();
+                let notify = running_script.notify.as_ref();
+                let liveness = self.longrun.run.liveness.as_ref();
+                select! {
+                    exit_status = running_script.child.wait() => {
+                        break ScriptResult::Exited(
+                            exit_status.context("unable to wait on child process")?
+                        );
+                    }
+                    _ = self.terminate.changed() => {
+                        break ScriptResult::Terminated;
+                    }
+                    messages = async {
+                        if let Some(notify_socket) = notify_socket {
+                            notify_socket.recv(pgid, cgroup).await
+                        } else {
+                            future::pending().await
+                        }
+                    } => {
+                        for message in messages? {
+                            if message == NotifyMessage::Watchdog {
+                                if let Some(period) = watchdog_period {
+                                    watchdog_deadline = time::Instant::now() + period;
+                                }
+                            }
+                            message.apply(&mut self.notify_state);
+                        }
+                        self.send_notify_state(&send).await;
+                    }
+                    res = async {
+                        if let Some(notify) = notify {
+                            read_notify_fd(notify).await
+                        } else {
+                            future::pending().await
+                        }
+                    } => {
+                        match res {
+                            // Any write on the notify fd is a keepalive
+                            Ok(Some(len)) if len > 0 => {
+                                if let Some(period) = watchdog_period {
+                                    watchdog_deadline = time::Instant::now() + period;
+                                }
+                            }
+                            Ok(Some(_)) => running_script.notify = None,
+                            Ok(None) => {}
+                            Err(err) => {
+                                warn!("could not read notify fd: {err}");
+                                running_script.notify = None;
+                            }
+                        }
+                    }
+                    _ = async {
+                        if watchdog_period.is_some() {
+                            sleep_until(watchdog_deadline).await;
+                        } else {
+                            future::pending::<()>().await;
+                        }
+                    } => {
+                        let pid = running_script.child.id().unwrap_or_default();
+                        error!(
+                            "process did not send a keepalive within {}ms, it is hung: {}",
+                            self.longrun.run.watchdog.unwrap(),
+                            process_state(pid)
+                        );
+                        kill_process(
+                            &mut running_script.child,
+                            &[SignalStep {
+                                signal: self.longrun.run.watchdog_signal,
+                                wait: self.longrun.run.timeout_kill,
+                            }],
+                            self.longrun.run.kill_mode,
+                            self.cgroup.as_ref(),
+                        )
+                        .await?;
+                        break ScriptResult::Exited(
+                            running_script
+                                .child
+                                .wait()
+                                .await
+                                .context("unable to wait on child process")?
+                        );
+                    }
+                    _ = async {
+                        if liveness.is_some() {
+                            liveness_interval.tick().await;
+                        } else {
+                            future::pending::<()>().await;
+                        }
+                    } => {
+                        let healthy = timeout(
+                            Duration::from_millis(self.longrun.run.liveness_timeout as u64),
+                            check_probe(liveness.unwrap(), &self.longrun.environment),
+                        )
+                        .await
+                        .unwrap_or(false);
+                        let new_health = match (healthy, health) {
+                            (true, _) => Health::Healthy,
+                            (false, Some(Health::Failing(failures))) => Health::Failing(failures + 1),
+                            (false, _) => Health::Failing(1),
+                        };
+                        if health != Some(new_health) {
+                            health = Some(new_health);
+                            self.send_health(&send, health).await;
+                        }
+                        if let Health::Failing(failures) = new_health {
+                            if failures >= self.longrun.run.liveness_threshold {
+                                error!("liveness probe failed {failures} times, killing the process");
+                                kill_process(
+                                    &mut running_script.child,
+                                    &self.longrun.run.get_down_steps(),
+                                    self.longrun.run.kill_mode,
+                                    self.cgroup.as_ref(),
+                                )
+                                .await?;
+                                break ScriptResult::Exited(
+                                    running_script
+                                        .child
+                                        .wait()
+                                        .await
+                                        .context("unable to wait on child process")?
+                                );
+                            }
+                        }
+                    }
                 }
             };
-            match res {
+            if health.is_some() {
+                self.send_health(&send, None).await;
+            }
+            let (state, mut restart) = match res {
                 ScriptResult::Terminated => {
                     // stop running
-                    kill_process(
+                    stop_process(
+                        &self.longrun,
                         &mut running_script.child,
-                        self.longrun.run.down_signal,
-                        self.longrun.run.timeout_kill,
+                        self.cgroup.as_ref(),
                     )
                     .await?;
+                    (IdleServiceState::Down, false)
+                }
+                ScriptResult::Exited(status) => {
+                    warn!("process exited with {status}");
+                    // The service is only down once all its processes have exited
+                    kill_remaining(self.longrun.run.kill_mode, self.cgroup.as_ref()).await?;
+                    self.check_limits();
+                    self.send_failure_reason(&send).await;
+                    self.check_restart(&status)
                 }
-                ScriptResult::Exited(status) => warn!("process exited with {status}"),
                 ScriptResult::Running(_) => unreachable!(),
-            }
-            if let Err(err) = send
-                .send(Request::UpdateServiceStatus(
-                    self.longrun.name.to_owned(),
-                    rinit_service::service_state::IdleServiceState::Down,
-                ))
-                .await
-            {
-                error!("Could not notify the main thread: {err}");
             };
+            self.send_state(&send, state).await;
+            // The status sent by the process is not valid anymore
+            self.notify_state = NotifyState::default();
+            self.send_notify_state(&send).await;
             if !running_script.logger_stop.is_closed() {
                 if let Err(_err) = running_script.logger_stop.send(()) {
                     warn!("logger was not working properly");
                 }
             }
             running_script.logger.await??;
-            if let ScriptResult::Terminated = res {
-                break;
+            // The process has been running long enough, it is not crashing in a loop
+            if running_script.started.elapsed()
+                >= Duration::from_millis(self.longrun.run.restart_delay_max as u64)
+            {
+                restarts = 0;
             }
-            match self.start_process().await? {
-                ScriptResult::Exited(_) | ScriptResult::Terminated => break,
-                ScriptResult::Running(running_script) => {
-                    if let Err(err) = send
-                        .send(Request::UpdateServiceStatus(
-                            self.longrun.name.to_owned(),
-                            rinit_service::service_state::IdleServiceState::Up,
-                        ))
-                        .await
-                    {
-                        error!("Could not notify the main thread: {err}");
+            while restart {
+                if !self.wait_restart_delay(restarts).await {
+                    return Ok(());
+                }
+                restarts = restarts.saturating_add(1);
+                match self.start_process().await? {
+                    ScriptResult::Exited(status) => {
+                        warn!("process exited with {status}");
+                        self.check_limits();
+                        self.send_failure_reason(&send).await;
+                        let (state, should_restart) = self.check_restart(&status);
+                        restart = should_restart;
+                        if state == IdleServiceState::Failed {
+                            self.send_state(&send, state).await;
+                        }
+                    }
+                    ScriptResult::Terminated => return Ok(()),
+                    ScriptResult::Running(running_script) => {
+                        self.send_state(&send, IdleServiceState::Up).await;
+                        self.send_notify_state(&send).await;
+                        self.running_script = Some(*running_script);
+                        break;
                     }
-                    self.running_script = Some(running_script);
                 }
             }
+            if self.running_script.is_none() {
+                break;
+            }
         }
 
         Ok(())
     }
 }
 
+/// Ask the process to exit by running the stop script, if any. If the process
+/// is still running afterwards, send the signals of down_signals
+async fn stop_process(
+    longrun: &Longrun,
+    child: &mut Child,
+    cgroup: Option<&Cgroup>,
+) -> Result<()> {
+    if let Some(stop) = &longrun.stop {
+        let mut env = longrun.environment.clone();
+        if let Some(pid) = child.id() {
+            env.add("MAINPID", pid.to_string());
+        }
+        match run_short_lived_script(stop, &env, longrun.sandbox.as_ref()).await {
+            Ok(true) => {
+                let wait = Duration::from_millis(longrun.run.timeout_kill as u64);
+                if let Ok(status) = timeout(wait, child.wait()).await {
+                    status.context("unable to call wait on child")?;
+                    return kill_remaining(longrun.run.kill_mode, cgroup).await;
+                }
+                warn!(
+                    "the process didn't exit within {}ms after the stop script",
+                    longrun.run.timeout_kill
+                );
+            }
+            Ok(false) => warn!("the stop script failed"),
+            Err(err) => warn!("{err:?}"),
+        }
+    }
+    kill_process(
+        child,
+        &longrun.run.get_down_steps(),
+        longrun.run.kill_mode,
+        cgroup,
+    )
+    .await
+}
+
+/// Read the data available on the notify fd. Return None if there was none
+/// and Some(0) if it has been closed
+async fn read_notify_fd(notify: &AsyncFd<i32>) -> io::Result<Option<usize>> {
+    let mut guard = notify.readable().await?;
+    let mut buf = [0; 64];
+    match guard.try_io(|fd| nix::unistd::read(*fd.get_ref(), &mut buf).map_err(io::Error::from)) {
+        Ok(res) => res.map(Some),
+        Err(_would_block) => Ok(None),
+    }
+}
+
+/// Describe what a process is doing, as reported by /proc
+fn process_state(pid: u32) -> String {
+    let state = fs::read_to_string(format!("/proc/{pid}/status"))
+        .ok()
+        .and_then(|status| {
+            status.lines().find_map(|line| {
+                line.strip_prefix("State:")
+                    .map(|state| state.trim().to_owned())
+            })
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    let wchan = fs::read_to_string(format!("/proc/{pid}/wchan")).unwrap_or_default();
+    format!("pid {pid}, state {state}, waiting in {wchan:?}")
+}
+
 #[cfg(test)]
 mod test {
+    use std::path::PathBuf;
+
     use flexi_logger::{
         writers::FileLogWriter,
         FileSpec,
     };
     use rinit_service::types::{
+        Probe,
+        RestartPolicy,
         Script,
-        ScriptEnvironment,
         ScriptPrefix,
-        ServiceOptions,
     };
     use tokio::join;
 
@@ -268,7 +728,7 @@ mod test {
             let (_file_writer, fw_handle) = FileLogWriter::builder(FileSpec::default())
                 .try_build_with_handle()
                 .unwrap();
-            let mut $supervisor = Supervisor::new($longrun, rx, fw_handle);
+            let mut $supervisor = Supervisor::new($longrun, rx, fw_handle, None, None, None);
         };
     }
 
@@ -278,13 +738,7 @@ mod test {
         let mut script = Script::new(ScriptPrefix::Bash, "sleep 0.01".to_string());
         // wait for 1ms
         script.timeout = 1;
-        let longrun = Longrun {
-            name: "test".to_string(),
-            run: script,
-            finish: None,
-            options: ServiceOptions::new(),
-            environment: ScriptEnvironment::new(),
-        };
+        let longrun = Longrun::new("test".to_string(), script);
         new_supervisor!(supervisor, _tx, longrun);
         spawn_local!(async move {
             assert!(supervisor.start().await.unwrap());
@@ -295,13 +749,7 @@ mod test {
     async fn test_start_process_failure() {
         let mut script = Script::new(ScriptPrefix::Bash, "sleep 0".to_string());
         script.timeout = 50;
-        let longrun = Longrun {
-            name: "test".to_string(),
-            run: script,
-            finish: None,
-            options: ServiceOptions::new(),
-            environment: ScriptEnvironment::new(),
-        };
+        let longrun = Longrun::new("test".to_string(), script);
         new_supervisor!(supervisor, _tx, longrun);
         spawn_local!(async move {
             assert!(!supervisor.start().await.unwrap());
@@ -312,13 +760,7 @@ mod test {
     async fn test_supervise_terminate() {
         let mut script = Script::new(ScriptPrefix::Bash, "sleep 1".to_string());
         script.timeout = 1;
-        let longrun = Longrun {
-            name: "test".to_string(),
-            run: script,
-            finish: None,
-            options: ServiceOptions::new(),
-            environment: ScriptEnvironment::new(),
-        };
+        let longrun = Longrun::new("test".to_string(), script);
         new_supervisor!(supervisor, tx, longrun);
         spawn_local!(async move {
             assert!(supervisor.start().await.unwrap());
@@ -332,4 +774,147 @@ mod test {
             res1.unwrap().unwrap();
         });
     }
+
+    #[tokio::test]
+    async fn test_supervise_no_restart() {
+        let mut script = Script::new(ScriptPrefix::Bash, "sleep 0.01".to_string());
+        script.timeout = 1;
+        script.restart = RestartPolicy::Never;
+        let longrun = Longrun::new("test".to_string(), script);
+        new_supervisor!(supervisor, _tx, longrun);
+        spawn_local!(async move {
+            assert!(supervisor.start().await.unwrap());
+            let (send, _) = mpsc::channel(1);
+            // The process exits on its own and it is not restarted
+            timeout(Duration::from_millis(500), supervisor.supervise(send))
+                .await
+                .unwrap()
+                .unwrap();
+        });
+    }
+
+    #[tokio::test]
+    async fn test_supervise_restart_limit() {
+        let mut script = Script::new(ScriptPrefix::Bash, "sleep 0.01".to_string());
+        script.timeout = 1;
+        script.restart_delay = 1;
+        script.restart_limit_burst = 2;
+        let longrun = Longrun::new("test".to_string(), script);
+        new_supervisor!(supervisor, _tx, longrun);
+        spawn_local!(async move {
+            assert!(supervisor.start().await.unwrap());
+            let (send, mut recv) = mpsc::channel(10);
+            // The process is restarted twice, then the service fails
+            timeout(Duration::from_millis(1000), supervisor.supervise(send))
+                .await
+                .unwrap()
+                .unwrap();
+            let mut states = Vec::new();
+            while let Ok(Request::UpdateServiceStatus(_, state)) = recv.try_recv() {
+                states.push(state);
+            }
+            assert_eq!(
+                states,
+                vec![
+                    IdleServiceState::Down,
+                    IdleServiceState::Up,
+                    IdleServiceState::Down,
+                    IdleServiceState::Up,
+                    IdleServiceState::Failed
+                ]
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_start_process_readiness() {
+        let path = std::env::temp_dir().join(format!("rinit-ready-test-{}", std::process::id()));
+        let mut script = Script::new(
+            ScriptPrefix::Bash,
+            format!("sleep 0.1; touch {}; sleep 1", path.to_string_lossy()),
+        );
+        script.timeout = 50;
+        script.max_deaths = 1;
+        script.readiness = Some(Probe::PathExists(path.clone()));
+        let longrun = Longrun::new("test".to_string(), script);
+        new_supervisor!(supervisor, _tx, longrun);
+        spawn_local!(async move {
+            // The path is created after the timeout has expired
+            assert!(!supervisor.start().await.unwrap());
+        });
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_start_process_ready() {
+        let path = std::env::temp_dir().join(format!("rinit-ready-test-{}-ok", std::process::id()));
+        let mut script = Script::new(
+            ScriptPrefix::Bash,
+            format!("sleep 0.05; touch {}; sleep 1", path.to_string_lossy()),
+        );
+        script.timeout = 1000;
+        script.readiness = Some(Probe::PathExists(path.clone()));
+        let longrun = Longrun::new("test".to_string(), script);
+        new_supervisor!(supervisor, _tx, longrun);
+        spawn_local!(async move {
+            // The process is up as soon as the path exists
+            assert!(
+                timeout(Duration::from_millis(500), supervisor.start())
+                    .await
+                    .unwrap()
+                    .unwrap()
+            );
+        });
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_supervise_liveness() {
+        let mut script = Script::new(ScriptPrefix::Bash, "sleep 1".to_string());
+        script.timeout = 1;
+        script.restart = RestartPolicy::Never;
+        script.liveness = Some(Probe::PathExists(PathBuf::from("/nonexistent")));
+        script.liveness_interval = 10;
+        script.liveness_threshold = 2;
+        let longrun = Longrun::new("test".to_string(), script);
+        new_supervisor!(supervisor, _tx, longrun);
+        spawn_local!(async move {
+            assert!(supervisor.start().await.unwrap());
+            let (send, mut recv) = mpsc::channel(10);
+            // The process is killed once the probe has failed twice
+            timeout(Duration::from_millis(500), supervisor.supervise(send))
+                .await
+                .unwrap()
+                .unwrap();
+            let mut health = Vec::new();
+            while let Ok(request) = recv.try_recv() {
+                if let Request::UpdateHealth(_, state) = request {
+                    health.push(state);
+                }
+            }
+            assert_eq!(
+                health,
+                vec![Some(Health::Failing(1)), Some(Health::Failing(2)), None]
+            );
+        });
+    }
+
+    #[tokio::test]
+    async fn test_supervise_watchdog() {
+        let mut script = Script::new(ScriptPrefix::Bash, "sleep 1".to_string());
+        script.timeout = 1;
+        script.restart = RestartPolicy::Never;
+        script.watchdog = Some(20);
+        let longrun = Longrun::new("test".to_string(), script);
+        new_supervisor!(supervisor, _tx, longrun);
+        spawn_local!(async move {
+            assert!(supervisor.start().await.unwrap());
+            let (send, _) = mpsc::channel(10);
+            // The process never sends a keepalive, so it is killed
+            timeout(Duration::from_millis(500), supervisor.supervise(send))
+                .await
+                .unwrap()
+                .unwrap();
+        });
+    }
 }
@@ -0,0 +1,265 @@
+use std::{
+    collections::hash_map::RandomState,
+    fs,
+    hash::{
+        BuildHasher,
+        Hasher,
+    },
+    io,
+    path::{
+        Path,
+        PathBuf,
+    },
+    time::{
+        Duration,
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+use anyhow::{
+    Context,
+    Result,
+};
+use rinit_ipc::Request;
+use rinit_service::{
+    service_state::TimerState,
+    types::{
+        Oneshot,
+        Timer,
+    },
+};
+use tokio::{
+    sync::mpsc,
+    time::sleep,
+};
+use tracing::{
+    info,
+    warn,
+};
+
+use crate::supervision::run_short_lived_script;
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() as i64)
+}
+
+// The time the system has booted at, in seconds since the epoch
+fn boot_time() -> i64 {
+    let mut uptime = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut uptime);
+    }
+    now() - uptime.tv_sec
+}
+
+fn random_delay(max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+    // Every RandomState is seeded with random keys
+    RandomState::new().build_hasher().finish() % (max + 1)
+}
+
+fn read_last_run(path: &Path) -> Result<Option<i64>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            Ok(Some(contents.trim().parse().with_context(|| {
+                format!("invalid timestamp in {path:?}")
+            })?))
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("unable to read {path:?}")),
+    }
+}
+
+fn write_last_run(
+    path: &Path,
+    last: i64,
+) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("unable to create directory {parent:?}"))?;
+    }
+    fs::write(path, last.to_string()).with_context(|| format!("unable to write {path:?}"))
+}
+
+/// The pending elapses of a timer, in seconds since the epoch
+struct Elapses {
+    boot: Option<i64>,
+    active: Option<i64>,
+}
+
+impl Elapses {
+    fn new(
+        timer: &Timer,
+        boot: i64,
+        activated: i64,
+    ) -> Self {
+        Self {
+            boot: timer.on_boot_sec.map(|sec| boot + sec as i64),
+            active: timer.on_active_sec.map(|sec| activated + sec as i64),
+        }
+    }
+
+    /// The next time the timer elapses. A calendar event missed since the last
+    /// run is in the past, so that it is caught up immediately
+    fn next(
+        &self,
+        timer: &Timer,
+        last: Option<i64>,
+        activated: i64,
+    ) -> Option<i64> {
+        let calendar = timer
+            .on_calendar
+            .as_ref()
+            .and_then(|event| event.next_after(last.unwrap_or(activated)));
+        [self.boot, self.active, calendar]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    /// Forget the monotonic elapses up to the time passed, they only run once
+    fn consume(
+        &mut self,
+        elapsed: i64,
+    ) {
+        self.boot = self.boot.filter(|boot| *boot > elapsed);
+        self.active = self.active.filter(|active| *active > elapsed);
+    }
+}
+
+/// Run the start script of a oneshot every time its timer elapses, until the
+/// timer does not elapse anymore
+pub async fn run_timer(
+    oneshot: Oneshot,
+    timer_file: PathBuf,
+    send: mpsc::Sender<Request>,
+) -> Result<()> {
+    let timer = oneshot.timer.as_ref().context("the oneshot has no timer")?;
+    let activated = now();
+    let mut elapses = Elapses::new(timer, boot_time(), activated);
+    // The timer keeps running without its file, the last run is then only
+    // known until rsvc restarts
+    let mut last = read_last_run(&timer_file).unwrap_or_else(|err| {
+        warn!("{err:?}");
+        None
+    });
+    loop {
+        let next = elapses.next(timer, last, activated);
+        if let Err(err) = send
+            .send(Request::UpdateTimer(
+                oneshot.name.clone(),
+                TimerState { last, next },
+            ))
+            .await
+        {
+            warn!("Could not update timer state: {err}");
+        }
+        let next = match next {
+            Some(next) => next,
+            None => return Ok(()),
+        };
+        let wait = (next - now()).max(0) as u64 + random_delay(timer.randomized_delay_sec);
+        sleep(Duration::from_secs(wait)).await;
+        elapses.consume(next);
+
+        info!("Timer of {} elapsed", oneshot.name);
+        match run_short_lived_script(&oneshot.start, &oneshot.environment, None).await {
+            Ok(true) => {}
+            Ok(false) => warn!("the start script of {} failed", oneshot.name),
+            Err(err) => warn!("{err:?}"),
+        }
+        let run = now();
+        last = Some(run);
+        if let Err(err) = write_last_run(&timer_file, run) {
+            warn!("{err:?}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use rinit_service::types::{
+        CalendarEvent,
+        Script,
+        ScriptEnvironment,
+        ScriptPrefix,
+        ServiceOptions,
+    };
+
+    use super::*;
+
+    #[test]
+    fn next_elapse() {
+        let timer = Timer {
+            on_boot_sec: Some(60),
+            on_active_sec: Some(600),
+            ..Timer::default()
+        };
+        let mut elapses = Elapses::new(&timer, 1000, 1030);
+        assert_eq!(elapses.next(&timer, None, 1030), Some(1060));
+        elapses.consume(1060);
+        assert_eq!(elapses.next(&timer, None, 1030), Some(1630));
+        elapses.consume(1630);
+        assert_eq!(elapses.next(&timer, None, 1030), None);
+    }
+
+    #[test]
+    fn catch_up_missed_run() {
+        let timer = Timer {
+            on_calendar: Some(CalendarEvent::from_str("minutely").unwrap()),
+            ..Timer::default()
+        };
+        let elapses = Elapses::new(&timer, 0, 6000);
+        // The last run was long before the activation
+        assert_eq!(elapses.next(&timer, Some(60), 6000), Some(120));
+        assert_eq!(elapses.next(&timer, None, 6000), Some(6060));
+    }
+
+    #[test]
+    fn last_run() {
+        let path = std::env::temp_dir()
+            .join(format!("rinit-timer-test-{}", std::process::id()))
+            .join("foo");
+        assert_eq!(read_last_run(&path).unwrap(), None);
+        write_last_run(&path, 42).unwrap();
+        assert_eq!(read_last_run(&path).unwrap(), Some(42));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn unwritable_timer_file() {
+        let oneshot = Oneshot {
+            name: "foo".to_string(),
+            start: Script::new(ScriptPrefix::Sh, "true".to_string()),
+            stop: None,
+            timer: Some(Timer {
+                on_active_sec: Some(0),
+                ..Timer::default()
+            }),
+            path: None,
+            options: ServiceOptions::new(),
+            environment: ScriptEnvironment::new(),
+        };
+        let (send, mut recv) = mpsc::channel(10);
+        // /dev/null is not a directory, the last run can't be saved
+        run_timer(oneshot, PathBuf::from("/dev/null/foo"), send)
+            .await
+            .unwrap();
+        let mut states = Vec::new();
+        while let Ok(Request::UpdateTimer(_, state)) = recv.try_recv() {
+            states.push(state);
+        }
+        assert_eq!(states.len(), 2);
+        assert!(states[1].last.is_some());
+    }
+}